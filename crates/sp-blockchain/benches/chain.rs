@@ -0,0 +1,20 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use sp_blockchain::Blockchain;
+use sp_transaction::{Transaction, TransactionType};
+
+fn long_chain(blocks: usize) -> Blockchain {
+    let mut chain = Blockchain::new();
+    for i in 0..blocks {
+        let tx = Transaction::new(TransactionType::PostCreated, i.to_le_bytes().to_vec());
+        chain.add_block(vec![tx]).unwrap();
+    }
+    chain
+}
+
+fn is_valid(c: &mut Criterion) {
+    let chain = long_chain(10_000);
+    c.bench_function("is_valid/10000", |b| b.iter(|| black_box(&chain).is_valid()));
+}
+
+criterion_group!(benches, is_valid);
+criterion_main!(benches);
@@ -1,17 +1,50 @@
+use std::{any::TypeId, sync::OnceLock};
+
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
-use sp_merkle::MerkleTree;
-use sp_transaction::Transaction;
+use sp_merkle::{MerkleProof, MerkleTree};
+use sp_transaction::{Hasher, Sha256Hasher, Transaction};
+use uuid::Uuid;
+
+use crate::{BlockchainError, DEFAULT_NETWORK_ID, FULL_VERIFICATION_WEIGHT, MIN_VERIFICATIONS};
 
-use crate::{BlockchainError, MIN_VERIFICATIONS};
+/// The hashed header fields of a [`Block`], without its transactions.
+///
+/// Together with a [`MerkleProof`] this is enough for a light client to check
+/// that a transaction is included in a block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockHeader {
+    pub index: u64,
+    pub prev_hash: [u8; 32],
+    pub merkle_root: [u8; 32],
+    pub timestamp: i64,
+    pub nonce: u64,
+}
+
+impl BlockHeader {
+    /// SHA-256 over the header fields; identical to [`Block::hash`].
+    pub fn hash(&self) -> [u8; 32] {
+        self.hash_with::<Sha256Hasher>()
+    }
+
+    /// Like [`BlockHeader::hash`], but with the digest algorithm `H`.
+    pub fn hash_with<H: Hasher>(&self) -> [u8; 32] {
+        let mut bytes = Vec::with_capacity(88);
+        bytes.extend_from_slice(&self.index.to_le_bytes());
+        bytes.extend_from_slice(&self.prev_hash);
+        bytes.extend_from_slice(&self.merkle_root);
+        bytes.extend_from_slice(&self.timestamp.to_le_bytes());
+        bytes.extend_from_slice(&self.nonce.to_le_bytes());
+        H::digest(&bytes)
+    }
+}
 
 /// A single block in the Social Production blockchain.
 ///
 /// Transactions are stored directly in the block and their Merkle root is
-/// committed in `merkle_root`.  A block is only *finalised* once at least
-/// [`MIN_VERIFICATIONS`] distinct peer IDs have been recorded in
-/// `verifications`.
+/// committed in `merkle_root`.  A block is only *finalised* once distinct peers
+/// have contributed verification weight worth [`MIN_VERIFICATIONS`] full
+/// verifications.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Block {
     /// Sequential block height (genesis = 0).
@@ -33,21 +66,76 @@ pub struct Block {
     pub nonce: u64,
 
     /// String IDs of peers that have verified this block.
-    /// When `verifications.len() >= MIN_VERIFICATIONS` the block is finalised.
     pub verifications: Vec<String>,
+
+    /// Total weight contributed by `verifications`.  The block is finalised
+    /// once this reaches `MIN_VERIFICATIONS * FULL_VERIFICATION_WEIGHT`.
+    #[serde(default)]
+    pub verified_weight: u32,
+
+    /// Free-form tag set by the node that formed the block, e.g. its region
+    /// or operator, for analytics.  Not part of the header, so it does not
+    /// change [`Block::hash`] and nothing stops a relaying peer from
+    /// altering it: treat it as a hint, not a claim.
+    #[serde(default)]
+    pub metadata: Option<Vec<u8>>,
+
+    /// The header [`Block::hash`] last hashed, and its hash.  Only used while
+    /// the header still matches, so changing a public field is safe.
+    #[serde(skip)]
+    cached_hash: OnceLock<(BlockHeader, [u8; 32])>,
 }
 
 impl Block {
     /// Compute the SHA-256 hash of this block's header fields (excluding
     /// `verifications`, which grow after block creation).
+    ///
+    /// The hash is computed once and cached; later calls only compare the
+    /// header against the cached one.
     pub fn hash(&self) -> [u8; 32] {
-        let mut hasher = Sha256::new();
-        hasher.update(self.index.to_le_bytes());
-        hasher.update(self.prev_hash);
-        hasher.update(self.merkle_root);
-        hasher.update(self.timestamp.to_le_bytes());
-        hasher.update(self.nonce.to_le_bytes());
-        hasher.finalize().into()
+        let header = self.header();
+        match self.cached_hash.get() {
+            Some((cached, hash)) if *cached == header => *hash,
+            Some(_) => header.hash(),
+            None => {
+                let hash = header.hash();
+                let _ = self.cached_hash.set((header, hash));
+                hash
+            }
+        }
+    }
+
+    /// Like [`Block::hash`], but with the digest algorithm `H`.  Only the
+    /// default SHA-256 hash is cached.
+    pub fn hash_with<H: Hasher + 'static>(&self) -> [u8; 32] {
+        if TypeId::of::<H>() == TypeId::of::<Sha256Hasher>() {
+            return self.hash();
+        }
+        self.header().hash_with::<H>()
+    }
+
+    /// Copy out the header fields.
+    pub fn header(&self) -> BlockHeader {
+        BlockHeader {
+            index: self.index,
+            prev_hash: self.prev_hash,
+            merkle_root: self.merkle_root,
+            timestamp: self.timestamp,
+            nonce: self.nonce,
+        }
+    }
+
+    /// Build a Merkle inclusion proof for the transaction `tx_id` in this
+    /// block, verifiable against `merkle_root`.
+    pub fn proof(&self, tx_id: Uuid) -> Result<MerkleProof, BlockchainError> {
+        self.proof_with::<Sha256Hasher>(tx_id)
+    }
+
+    /// Like [`Block::proof`], for a block built with
+    /// [`Block::new_with_hasher`].
+    pub fn proof_with<H: Hasher>(&self, tx_id: Uuid) -> Result<MerkleProof<H>, BlockchainError> {
+        let tree = MerkleTree::<H>::build(&self.transactions)?;
+        Ok(tree.proof(&self.transactions, tx_id)?)
     }
 
     /// Hex-encoded block hash.
@@ -55,17 +143,32 @@ impl Block {
         hex::encode(self.hash())
     }
 
-    /// Returns `true` when this block has been verified by at least
-    /// [`MIN_VERIFICATIONS`] distinct peers.
+    /// Length of this block's bincode encoding, as sent on the wire,
+    /// verifications and metadata included.
+    pub fn serialised_size(&self) -> usize {
+        // Every field has a known length, so measuring cannot fail.
+        bincode::serialized_size(self).expect("block has a fixed layout") as usize
+    }
+
+    /// Returns `true` when this block has accumulated the weight of at least
+    /// [`MIN_VERIFICATIONS`] full verifications.
     pub fn is_finalised(&self) -> bool {
-        self.verifications.len() >= MIN_VERIFICATIONS
+        self.verified_weight >= MIN_VERIFICATIONS as u32 * FULL_VERIFICATION_WEIGHT
     }
 
-    /// Record a peer verification.  Idempotent — duplicate peer IDs are
-    /// ignored.  Returns `true` if the block just became finalised.
+    /// Record a full-weight peer verification.  Idempotent — duplicate peer
+    /// IDs are ignored.  Returns `true` if the block is now finalised.
     pub fn add_verification(&mut self, peer_id: String) -> bool {
+        self.add_weighted_verification(peer_id, FULL_VERIFICATION_WEIGHT)
+    }
+
+    /// Record a peer verification worth `weight` (see
+    /// [`FULL_VERIFICATION_WEIGHT`]).  Only a peer's first verification
+    /// counts.  Returns `true` if the block is now finalised.
+    pub fn add_weighted_verification(&mut self, peer_id: String, weight: u32) -> bool {
         if !self.verifications.contains(&peer_id) {
             self.verifications.push(peer_id);
+            self.verified_weight = self.verified_weight.saturating_add(weight);
         }
         self.is_finalised()
     }
@@ -75,12 +178,24 @@ impl Block {
         index: u64,
         prev_hash: [u8; 32],
         transactions: Vec<Transaction>,
+    ) -> Result<Self, BlockchainError> {
+        Self::new_with_hasher::<Sha256Hasher>(index, prev_hash, transactions)
+    }
+
+    /// Like [`Block::new`], but with its Merkle root computed by `H`.  Hash
+    /// and prove the block with [`Block::hash_with`] and
+    /// [`Block::proof_with`] using the same `H`, and append it to a
+    /// `Blockchain<H>`.
+    pub fn new_with_hasher<H: Hasher>(
+        index: u64,
+        prev_hash: [u8; 32],
+        transactions: Vec<Transaction>,
     ) -> Result<Self, BlockchainError> {
         if transactions.is_empty() {
             return Err(BlockchainError::NoTransactions);
         }
 
-        let tree = MerkleTree::new(&transactions)?;
+        let tree = MerkleTree::<H>::build(&transactions)?;
         let merkle_root = tree.root_hash()?;
 
         Ok(Self {
@@ -91,17 +206,40 @@ impl Block {
             timestamp: Utc::now().timestamp(),
             nonce: 0,
             verifications: Vec::new(),
+            verified_weight: 0,
+            metadata: None,
+            cached_hash: OnceLock::new(),
         })
     }
 
-    /// Create the genesis block with a fixed all-zero previous hash.
+    /// Create the genesis block of the default network.
     pub fn genesis() -> Self {
-        let placeholder = Transaction::new(
-            sp_transaction::TransactionType::NodeAdded,
-            b"genesis".to_vec(),
-        );
+        Self::genesis_for_network(DEFAULT_NETWORK_ID)
+    }
+
+    /// Create the genesis block of `network_id`, with a fixed all-zero
+    /// previous hash.
+    ///
+    /// Every field is derived from the network id alone, so all nodes on a
+    /// network start from the same genesis while different networks never
+    /// do.
+    pub fn genesis_for_network(network_id: &str) -> Self {
+        Self::genesis_for_network_with::<Sha256Hasher>(network_id)
+    }
 
-        let tree = MerkleTree::new(&[placeholder.clone()])
+    /// Like [`Block::genesis_for_network`], with its Merkle root computed by
+    /// `H`.
+    pub fn genesis_for_network_with<H: Hasher>(network_id: &str) -> Self {
+        let placeholder = Transaction {
+            id: Uuid::nil(),
+            kind: sp_transaction::TransactionType::NodeAdded,
+            payload: format!("genesis/{network_id}").into_bytes(),
+            timestamp: 0,
+            priority: 0,
+            signature: Vec::new(),
+        };
+
+        let tree = MerkleTree::<H>::build(std::slice::from_ref(&placeholder))
             .expect("genesis merkle tree should never fail");
         let merkle_root = tree.root_hash().expect("genesis root should exist");
 
@@ -113,6 +251,60 @@ impl Block {
             timestamp: 0,
             nonce: 0,
             verifications: Vec::new(),
+            verified_weight: 0,
+            metadata: None,
+            cached_hash: OnceLock::new(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use sha2::{Digest, Sha256};
+    use sp_transaction::TransactionType;
+
+    use super::*;
+
+    /// Double SHA-256, standing in for an alternative digest such as BLAKE3.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct DoubleSha256;
+
+    impl Hasher for DoubleSha256 {
+        fn digest(data: &[u8]) -> [u8; 32] {
+            Sha256::digest(Sha256::digest(data)).into()
+        }
+    }
+
+    #[test]
+    fn cached_hash_matches_a_fresh_computation() {
+        let tx = Transaction::new(TransactionType::PostCreated, vec![1]);
+        let mut block = Block::new(1, [0u8; 32], vec![tx]).unwrap();
+
+        let first = block.hash();
+        assert_eq!(block.hash(), first);
+        assert_eq!(first, block.header().hash());
+
+        // Changing a header field must not serve the stale hash.
+        block.nonce = 42;
+        assert_ne!(block.hash(), first);
+        assert_eq!(block.hash(), block.header().hash());
+    }
+
+    #[test]
+    fn alternate_hasher_is_used_for_tree_block_and_proof() {
+        let txs: Vec<Transaction> = (0..3)
+            .map(|i| Transaction::new(TransactionType::PostCreated, vec![i]))
+            .collect();
+
+        let tree = MerkleTree::<DoubleSha256>::build(&txs).unwrap();
+        let block = Block::new_with_hasher::<DoubleSha256>(1, [0u8; 32], txs.clone()).unwrap();
+        assert_eq!(block.merkle_root, tree.root_hash().unwrap());
+        assert_ne!(block.merkle_root, MerkleTree::new(&txs).unwrap().root_hash().unwrap());
+        assert_ne!(block.hash_with::<DoubleSha256>(), block.hash());
+
+        let proof = block.proof_with::<DoubleSha256>(txs[2].id).unwrap();
+        assert_eq!(proof, tree.proof(&txs, txs[2].id).unwrap());
+        assert!(proof.verify(&block.merkle_root));
+        assert!(!block.proof(txs[2].id).unwrap().verify(&block.merkle_root));
+    }
+}
@@ -1,17 +1,34 @@
+use std::marker::PhantomData;
+
 use serde::{Deserialize, Serialize};
-use sp_transaction::Transaction;
+use sp_merkle::MerkleTree;
+use sp_transaction::{Hasher, Sha256Hasher, Transaction};
 
-use crate::{block::Block, error::BlockchainError};
+use crate::{block::Block, error::BlockchainError, DEFAULT_NETWORK_ID, FULL_VERIFICATION_WEIGHT};
 
 /// The append-only chain of [`Block`]s that forms the Social Production ledger.
 ///
 /// Invariants maintained by this type:
 /// - Always contains at least the genesis block.
 /// - Every block's `prev_hash` matches the hash of the preceding block.
-/// - Block indices are contiguous starting from 0.
+/// - Block indices are contiguous starting from 0, except that finalised
+///   blocks after genesis may have been dropped by
+///   [`Blockchain::prune_before`].  The first block kept then links to the
+///   last one dropped, whose index and hash the chain remembers.
+/// - Every appended block's `merkle_root` matches its transactions.
+///
+/// Block hashes and Merkle roots are computed with `H`, SHA-256 by default;
+/// blocks appended to a `Blockchain<H>` must have been built with the same
+/// `H` (see [`Block::new_with_hasher`]).
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Blockchain {
+pub struct Blockchain<H: Hasher = Sha256Hasher> {
     blocks: Vec<Block>,
+    /// Index and hash of the last block [`Blockchain::prune_before`]
+    /// dropped, which the first block after genesis links to.
+    #[serde(default)]
+    pruned_to: Option<(u64, [u8; 32])>,
+    #[serde(skip)]
+    hasher: PhantomData<H>,
 }
 
 impl Default for Blockchain {
@@ -21,46 +38,166 @@ impl Default for Blockchain {
 }
 
 impl Blockchain {
-    /// Initialise a new chain with only the genesis block.
+    /// Initialise a new chain on the default network with only the genesis
+    /// block.
     pub fn new() -> Self {
-        Self {
-            blocks: vec![Block::genesis()],
-        }
+        Self::new_with_network(DEFAULT_NETWORK_ID)
+    }
+
+    /// Initialise a new chain with only the genesis block of `network_id`.
+    pub fn new_with_network(network_id: &str) -> Self {
+        Self::with_hasher(network_id)
+    }
+
+    /// Import a chain exported with [`Blockchain::to_json`].  The chain must
+    /// pass [`Blockchain::is_valid`].
+    pub fn from_json(json: &str) -> Result<Self, BlockchainError> {
+        Self::from_json_with(json)
     }
+}
 
-    /// Number of blocks in the chain (including genesis).
+impl<H: Hasher + 'static> Blockchain<H> {
+    /// Initialise a chain hashed with `H`, holding only the genesis block of
+    /// `network_id`.
+    pub fn with_hasher(network_id: &str) -> Self {
+        Self::from_blocks(vec![Block::genesis_for_network_with::<H>(network_id)])
+    }
+
+    fn from_blocks(blocks: Vec<Block>) -> Self {
+        Self { blocks, pruned_to: None, hasher: PhantomData }
+    }
+
+    /// Hash of `block` under `H`.
+    fn hash_of(block: &Block) -> [u8; 32] {
+        block.hash_with::<H>()
+    }
+
+    /// Number of blocks in the chain (including genesis), counting any that
+    /// were pruned; see [`Blockchain::blocks`] for the ones still held.
     pub fn len(&self) -> usize {
-        self.blocks.len()
+        self.blocks.last().map_or(0, |tip| tip.index as usize + 1)
     }
 
     pub fn is_empty(&self) -> bool {
         self.blocks.is_empty()
     }
 
+    /// Hash of the genesis block, which identifies the chain.  It is derived
+    /// from the network id, and nodes must share it to sync: peers whose
+    /// genesis hashes differ hold incompatible chains.
+    pub fn genesis_hash(&self) -> [u8; 32] {
+        Self::hash_of(&self.blocks[0])
+    }
+
+    /// Hex-encoded [`Blockchain::genesis_hash`].
+    pub fn genesis_hash_hex(&self) -> String {
+        hex::encode(self.genesis_hash())
+    }
+
     /// The most recent block.
     pub fn tip(&self) -> &Block {
         // Safety: always at least one block (genesis).
         self.blocks.last().unwrap()
     }
 
+    /// Index of the most recent block.
+    pub fn tip_index(&self) -> u64 {
+        self.tip().index
+    }
+
+    /// Index of the highest finalised block, or `None` if no block has been
+    /// finalised yet.
+    pub fn last_finalised_index(&self) -> Option<u64> {
+        self.blocks.iter().rev().find(|b| b.is_finalised()).map(|b| b.index)
+    }
+
     /// Append a new block containing `transactions`.
     ///
     /// The new block's `prev_hash` is set to the current tip's hash.
     pub fn add_block(&mut self, transactions: Vec<Transaction>) -> Result<&Block, BlockchainError> {
-        let prev_hash = self.tip().hash();
-        let index = self.tip().index + 1;
-        let block = Block::new(index, prev_hash, transactions)?;
+        self.add_block_with_metadata(transactions, None)
+    }
+
+    /// Like [`Blockchain::add_block`], tagging the block with `metadata`.
+    pub fn add_block_with_metadata(
+        &mut self,
+        transactions: Vec<Transaction>,
+        metadata: Option<Vec<u8>>,
+    ) -> Result<&Block, BlockchainError> {
+        let prev_hash = Self::hash_of(self.tip());
+        let index = self.next_index()?;
+        let mut block = Block::new_with_hasher::<H>(index, prev_hash, transactions)?;
+        block.metadata = metadata;
+        // The block was just built on the tip with its root computed from
+        // these transactions, so there is nothing to check again.
         self.blocks.push(block);
         Ok(self.blocks.last().unwrap())
     }
 
-    /// Record a peer verification for the block at `block_index`.
+    /// Index the next block must have.  A tip at `u64::MAX`, which only a
+    /// crafted or corrupted chain can have, has no successor.
+    fn next_index(&self) -> Result<u64, BlockchainError> {
+        let tip = self.tip().index;
+        tip.checked_add(1)
+            .ok_or_else(|| BlockchainError::InvalidChain(format!("no block can follow tip #{tip}")))
+    }
+
+    /// Append a block built elsewhere (e.g. received from a peer).
+    ///
+    /// The block must extend the current tip: its index must be `tip + 1`,
+    /// its `prev_hash` must match the tip's hash and its `merkle_root` must
+    /// match its transactions.
     ///
-    /// Returns `true` if the block just reached [`MIN_VERIFICATIONS`].
+    /// Every block from outside goes through here, so a chain that was valid
+    /// stays valid without another [`Blockchain::is_valid`] sweep.
+    pub fn append_block(&mut self, block: Block) -> Result<&Block, BlockchainError> {
+        let next = self.next_index()?;
+        let tip = self.tip();
+        if block.index != next {
+            return Err(BlockchainError::InvalidChain(format!(
+                "block #{} does not follow tip #{}",
+                block.index, tip.index
+            )));
+        }
+        if block.prev_hash != Self::hash_of(tip) {
+            return Err(BlockchainError::InvalidChain(format!(
+                "block #{} does not link to the tip's hash",
+                block.index
+            )));
+        }
+        if block.transactions.is_empty() {
+            return Err(BlockchainError::NoTransactions);
+        }
+        if MerkleTree::<H>::build(&block.transactions)?.root_hash()? != block.merkle_root {
+            return Err(BlockchainError::InvalidChain(format!(
+                "block #{} does not match its merkle root",
+                block.index
+            )));
+        }
+        self.blocks.push(block);
+        Ok(self.blocks.last().unwrap())
+    }
+
+    /// Record a full-weight peer verification for the block at `block_index`.
+    ///
+    /// Returns `true` if the block has reached [`MIN_VERIFICATIONS`].
     pub fn verify_block(
         &mut self,
         block_index: u64,
         peer_id: String,
+    ) -> Result<bool, BlockchainError> {
+        self.verify_block_weighted(block_index, peer_id, FULL_VERIFICATION_WEIGHT)
+    }
+
+    /// Record a peer verification worth `weight` for the block at
+    /// `block_index`.  Full verifications weigh [`FULL_VERIFICATION_WEIGHT`].
+    ///
+    /// Returns `true` if the block has reached the finalisation threshold.
+    pub fn verify_block_weighted(
+        &mut self,
+        block_index: u64,
+        peer_id: String,
+        weight: u32,
     ) -> Result<bool, BlockchainError> {
         let block = self
             .blocks
@@ -68,68 +205,226 @@ impl Blockchain {
             .find(|b| b.index == block_index)
             .ok_or(BlockchainError::BlockNotFound(block_index))?;
 
-        Ok(block.add_verification(peer_id))
+        Ok(block.add_weighted_verification(peer_id, weight))
     }
 
-    /// Return a reference to a block by its index.
+    /// Return a reference to a block by its index, unless it was pruned.
     pub fn get_block(&self, index: u64) -> Option<&Block> {
-        self.blocks.get(index as usize)
+        self.blocks_range(index, index.saturating_add(1)).first()
+    }
+
+    /// Return the block whose hash under `H` (by default [`Block::hash`]) is
+    /// `hash`.
+    pub fn get_block_by_hash(&self, hash: &[u8; 32]) -> Option<&Block> {
+        self.blocks.iter().find(|b| Self::hash_of(b) == *hash)
+    }
+
+    /// Blocks whose hex hash (as in [`Block::hash_hex`]) starts with `prefix`,
+    /// ignoring case.  A full 64-digit hash matches at most one block.
+    pub fn blocks_by_hash_prefix(&self, prefix: &str) -> Vec<&Block> {
+        let prefix = prefix.to_ascii_lowercase();
+        self.blocks
+            .iter()
+            .filter(|b| hex::encode(Self::hash_of(b)).starts_with(&prefix))
+            .collect()
     }
 
     /// Return all blocks from `start_index` onward (inclusive).
     pub fn blocks_from(&self, start_index: u64) -> &[Block] {
-        let pos = start_index as usize;
-        if pos >= self.blocks.len() {
-            &[]
-        } else {
-            &self.blocks[pos..]
+        self.blocks_range(start_index, u64::MAX)
+    }
+
+    /// Like [`Blockchain::blocks_from`], but a `start` beyond the block after
+    /// the tip is an error rather than an empty slice, so a caller that is
+    /// exactly caught up (`start == len`) can be told apart from one asking
+    /// for blocks that don't exist.  A `start` inside the pruned range is an
+    /// error too, since the run from there would have a gap.
+    pub fn blocks_from_checked(&self, start: u64) -> Result<&[Block], BlockchainError> {
+        let pruned = matches!(self.pruned_to, Some((up_to, _)) if start <= up_to);
+        if pruned || start > self.len() as u64 {
+            return Err(BlockchainError::BlockNotFound(start));
         }
+        Ok(self.blocks_from(start))
     }
 
-    /// All blocks in the chain.
+    /// Return the blocks with `start <= index < end`.  Both bounds are clamped
+    /// to the chain, so an out-of-range or empty range yields an empty slice.
+    /// Pruned blocks are missing from the result.
+    pub fn blocks_range(&self, start: u64, end: u64) -> &[Block] {
+        let end = self.blocks.partition_point(|b| b.index < end);
+        let start = self.blocks.partition_point(|b| b.index < start).min(end);
+        &self.blocks[start..end]
+    }
+
+    /// Every transaction in the chain, paired with the block holding it, in
+    /// chain order (genesis first, then each block's transactions in order).
+    pub fn iter_transactions(&self) -> impl Iterator<Item = (&Block, &Transaction)> {
+        self.blocks
+            .iter()
+            .flat_map(|block| block.transactions.iter().map(move |tx| (block, tx)))
+    }
+
+    /// All blocks held by the chain: genesis, then every block that has not
+    /// been pruned.
     pub fn blocks(&self) -> &[Block] {
         &self.blocks
     }
 
+    /// True once [`Blockchain::prune_before`] has dropped any block.
+    pub fn is_pruned(&self) -> bool {
+        self.pruned_to.is_some()
+    }
+
+    /// Drop the blocks after genesis with an index below `index`, to bound
+    /// the chain's size.  Genesis and the tip are always kept, and pruning
+    /// stops at the first block that is not finalised, since verifications
+    /// for it may still arrive.
+    ///
+    /// Returns the index of the last block dropped, if any were.
+    pub fn prune_before(&mut self, index: u64) -> Option<u64> {
+        let tip = self.tip_index();
+        let count = self.blocks[1..]
+            .iter()
+            .take_while(|b| b.index < index && b.index < tip && b.is_finalised())
+            .count();
+        if count == 0 {
+            return None;
+        }
+        let last = &self.blocks[count];
+        let up_to = last.index;
+        self.pruned_to = Some((up_to, Self::hash_of(last)));
+        self.blocks.drain(1..=count);
+        Some(up_to)
+    }
+
     /// Validate the full chain structure:
     /// - Each block's `prev_hash` matches the hash of the previous block.
     /// - Block indices are contiguous.
+    /// - Each block's `merkle_root` matches its transactions.
+    ///
+    /// See [`Blockchain::verify_chain`] for which block is at fault.
     pub fn is_valid(&self) -> bool {
+        self.verify_chain().is_ok()
+    }
+
+    /// Like [`Blockchain::is_valid`], but on failure returns the position of
+    /// the first offending block and what is wrong with it.
+    ///
+    /// In a pruned chain the first block after genesis must link to the last
+    /// block pruned, as recorded by [`Blockchain::prune_before`], rather than
+    /// to genesis.  A gap the chain did not prune itself is an error.
+    pub fn verify_chain(&self) -> Result<(), (u64, String)> {
         if self.blocks.is_empty() {
-            return false;
+            return Err((0, "chain is empty".into()));
         }
 
-        for window in self.blocks.windows(2) {
-            let prev = &window[0];
-            let next = &window[1];
-
-            if next.prev_hash != prev.hash() {
-                return false;
+        for (position, block) in self.blocks.iter().enumerate() {
+            let at = position as u64;
+            let prev = match (position, self.pruned_to) {
+                (0, _) => None,
+                (1, Some(pruned_to)) => Some(pruned_to),
+                _ => {
+                    let prev = &self.blocks[position - 1];
+                    Some((prev.index, Self::hash_of(prev)))
+                }
+            };
+            if let Some((prev_index, prev_hash)) = prev {
+                if block.prev_hash != prev_hash {
+                    return Err((at, "prev_hash does not match the previous block".into()));
+                }
+                if prev_index.checked_add(1) != Some(block.index) {
+                    let reason = format!("index {} does not follow {prev_index}", block.index);
+                    return Err((at, reason));
+                }
             }
-            if next.index != prev.index + 1 {
-                return false;
+            let root = MerkleTree::<H>::build(&block.transactions).and_then(|t| t.root_hash());
+            if root.ok() != Some(block.merkle_root) {
+                return Err((at, "merkle root does not match the transactions".into()));
             }
         }
 
-        true
+        Ok(())
+    }
+
+    /// Human-readable JSON export of the whole chain, for debugging and
+    /// migration.
+    pub fn to_json(&self) -> Result<String, BlockchainError> {
+        serde_json::to_string_pretty(self).map_err(|e| BlockchainError::Serialisation(e.to_string()))
+    }
+
+    /// Like [`Blockchain::from_json`], for a chain hashed with `H`.
+    pub fn from_json_with(json: &str) -> Result<Self, BlockchainError> {
+        let chain: Self =
+            serde_json::from_str(json).map_err(|e| BlockchainError::Serialisation(e.to_string()))?;
+        if let Err((at, reason)) = chain.verify_chain() {
+            return Err(BlockchainError::InvalidChain(format!(
+                "imported block #{at} is invalid: {reason}"
+            )));
+        }
+        Ok(chain)
     }
 
-    /// Replace the local chain with `other` if `other` is longer and valid.
+    /// Replace the whole chain with `blocks`, e.g. for a bulk import from a
+    /// file.  `blocks` must pass [`Blockchain::verify_chain`] and start from
+    /// this chain's genesis block; otherwise the chain is left untouched.
+    ///
+    /// Unlike [`Blockchain::sync_from`], the new blocks need not be longer and
+    /// replace everything, verifications included.
+    pub fn try_replace(&mut self, blocks: Vec<Block>) -> Result<(), BlockchainError> {
+        let candidate = Self::from_blocks(blocks);
+        if let Err((at, reason)) = candidate.verify_chain() {
+            return Err(BlockchainError::InvalidChain(format!(
+                "replacement block #{at} is invalid: {reason}"
+            )));
+        }
+        if candidate.genesis_hash() != self.genesis_hash() {
+            return Err(BlockchainError::InvalidChain(
+                "replacement starts from a different genesis block".into(),
+            ));
+        }
+        *self = candidate;
+        Ok(())
+    }
+
+    /// Replace the local chain with `other` if `other` is longer, valid,
+    /// unpruned and starts from the same genesis block.
     ///
     /// This is the simple longest-chain conflict resolution rule used during
-    /// peer sync.
-    pub fn sync_from(&mut self, other: &Blockchain) -> bool {
-        if other.len() > self.len() && other.is_valid() {
-            *self = other.clone();
-            true
-        } else {
-            false
+    /// peer sync.  Only the blocks after the last one both chains share are
+    /// copied; the shared prefix, and the verifications recorded on it, stay
+    /// as they are.
+    pub fn sync_from(&mut self, other: &Self) -> bool {
+        if other.len() <= self.len()
+            || other.is_pruned()
+            || !other.is_valid()
+            || other.genesis_hash() != self.genesis_hash()
+        {
+            return false;
         }
+
+        // Blocks are hash-linked, so the last matching block ends the shared
+        // prefix; scanning from the tip makes a plain extension O(1).
+        let common = self
+            .blocks
+            .iter()
+            .rposition(|b| {
+                other.get_block(b.index).is_some_and(|o| Self::hash_of(o) == Self::hash_of(b))
+            })
+            .map_or(0, |i| i + 1);
+        let next = self.blocks[..common].last().map_or(0, |b| b.index + 1);
+        if common <= 1 {
+            // Every block after the pruned gap is replaced.
+            self.pruned_to = None;
+        }
+        self.blocks.truncate(common);
+        self.blocks.extend_from_slice(other.blocks_from(next));
+        true
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use sha2::{Digest, Sha256};
     use sp_transaction::{Transaction, TransactionType};
 
     use super::*;
@@ -138,11 +433,108 @@ mod tests {
         Transaction::new(kind, b"test".to_vec())
     }
 
+    /// Double SHA-256, standing in for an alternative digest such as BLAKE3.
+    #[derive(Debug, Clone)]
+    struct DoubleSha256;
+
+    impl Hasher for DoubleSha256 {
+        fn digest(data: &[u8]) -> [u8; 32] {
+            Sha256::digest(Sha256::digest(data)).into()
+        }
+    }
+
+    #[test]
+    fn a_chain_over_another_hasher_appends_and_validates_its_blocks() {
+        let mut chain = Blockchain::<DoubleSha256>::with_hasher(DEFAULT_NETWORK_ID);
+        assert_ne!(chain.genesis_hash(), Blockchain::new().genesis_hash());
+        chain.add_block(vec![tx(TransactionType::PostCreated)]).unwrap();
+
+        let prev = chain.tip().hash_with::<DoubleSha256>();
+        let txs = vec![tx(TransactionType::VoteCast)];
+        let block = Block::new_with_hasher::<DoubleSha256>(2, prev, txs.clone()).unwrap();
+        chain.append_block(block).unwrap();
+        assert!(chain.is_valid());
+        let tip = chain.tip();
+        assert!(tip.proof_with::<DoubleSha256>(txs[0].id).unwrap().verify(&tip.merkle_root));
+
+        // A SHA-256 block's Merkle root doesn't check out under the chain's hasher.
+        let prev = chain.tip().hash_with::<DoubleSha256>();
+        let sha = Block::new(3, prev, vec![tx(TransactionType::VoteCast)]).unwrap();
+        assert!(matches!(chain.append_block(sha), Err(BlockchainError::InvalidChain(_))));
+
+        let json = chain.to_json().unwrap();
+        assert_eq!(Blockchain::<DoubleSha256>::from_json_with(&json).unwrap().len(), 3);
+        assert!(Blockchain::from_json(&json).is_err());
+    }
+
     #[test]
     fn new_chain_is_valid() {
         assert!(Blockchain::new().is_valid());
     }
 
+    #[test]
+    fn independent_chains_share_a_genesis() {
+        let mut a = Blockchain::new();
+        a.add_block(vec![tx(TransactionType::PostCreated)]).unwrap();
+        let b = Blockchain::new();
+        assert_eq!(a.genesis_hash(), b.genesis_hash());
+        assert_eq!(a.get_block(1).unwrap().prev_hash, b.tip().hash());
+    }
+
+    #[test]
+    fn genesis_hash_identifies_the_network() {
+        let main = Blockchain::new();
+        assert_eq!(main.genesis_hash(), Blockchain::new().genesis_hash());
+        assert_eq!(main.genesis_hash(), main.blocks()[0].hash());
+        assert_eq!(main.genesis_hash_hex(), hex::encode(main.genesis_hash()));
+        let tagged = Blockchain::new_with_network(DEFAULT_NETWORK_ID);
+        assert_eq!(tagged.genesis_hash_hex(), main.genesis_hash_hex());
+
+        let test = Blockchain::new_with_network("testnet");
+        assert_eq!(test.genesis_hash(), Blockchain::new_with_network("testnet").genesis_hash());
+        assert_ne!(test.genesis_hash(), main.genesis_hash());
+        assert_ne!(test.genesis_hash_hex(), main.genesis_hash_hex());
+        assert_eq!(test.genesis_hash_hex().len(), 64);
+    }
+
+    #[test]
+    fn networks_have_distinct_genesis_and_do_not_sync() {
+        let mut test = Blockchain::new_with_network("testnet");
+        let mut main = Blockchain::new();
+        assert_ne!(test.genesis_hash(), main.genesis_hash());
+        assert_eq!(main.genesis_hash(), Blockchain::new_with_network(DEFAULT_NETWORK_ID).genesis_hash());
+
+        test.add_block(vec![tx(TransactionType::PostCreated)]).unwrap();
+        assert!(!main.sync_from(&test));
+        assert_eq!(main.len(), 1);
+    }
+
+    #[test]
+    fn verify_chain_names_the_corrupted_block() {
+        let mut chain = Blockchain::new();
+        for _ in 0..3 {
+            chain.add_block(vec![tx(TransactionType::PostCreated)]).unwrap();
+        }
+        assert_eq!(chain.verify_chain(), Ok(()));
+
+        let mut relinked = chain.clone();
+        relinked.blocks[2].prev_hash = [7; 32];
+        assert_eq!(relinked.verify_chain().unwrap_err().0, 2);
+
+        let mut renumbered = chain.clone();
+        renumbered.blocks[3].index = 9;
+        let (at, reason) = renumbered.verify_chain().unwrap_err();
+        assert_eq!(at, 3);
+        assert!(reason.contains("index 9"), "{reason}");
+
+        let mut rerooted = chain.clone();
+        rerooted.blocks[1].merkle_root = [7; 32];
+        let (at, reason) = rerooted.verify_chain().unwrap_err();
+        assert_eq!(at, 1);
+        assert!(reason.contains("merkle"), "{reason}");
+        assert!(!rerooted.is_valid());
+    }
+
     #[test]
     fn add_block_extends_chain() {
         let mut chain = Blockchain::new();
@@ -165,6 +557,300 @@ mod tests {
         assert!(chain.get_block(1).unwrap().is_finalised());
     }
 
+    #[test]
+    fn last_finalised_index_skips_unfinalised_blocks() {
+        let mut chain = Blockchain::new();
+        assert_eq!(chain.tip_index(), 0);
+        assert_eq!(chain.last_finalised_index(), None);
+
+        for _ in 0..4 {
+            chain.add_block(vec![tx(TransactionType::ProjectPosted)]).unwrap();
+        }
+        assert_eq!(chain.tip_index(), 4);
+        for peer in ["peer-a", "peer-b", "peer-c"] {
+            chain.verify_block(1, peer.into()).unwrap();
+            chain.verify_block(3, peer.into()).unwrap();
+        }
+        // Block 4 is the tip but only partly verified; 2 is never verified.
+        chain.verify_block(4, "peer-a".into()).unwrap();
+        assert_eq!(chain.last_finalised_index(), Some(3));
+    }
+
+    #[test]
+    fn a_tip_at_the_last_index_cannot_be_extended() {
+        let mut chain = Blockchain::new();
+        let last = Block::new(u64::MAX, chain.tip().hash(), vec![tx(TransactionType::PostCreated)])
+            .unwrap();
+        chain.blocks.push(last.clone());
+
+        let err = chain.add_block(vec![tx(TransactionType::PostCreated)]).unwrap_err();
+        assert!(matches!(err, BlockchainError::InvalidChain(_)), "{err}");
+        let mut next = last;
+        next.prev_hash = chain.tip().hash();
+        assert!(matches!(chain.append_block(next), Err(BlockchainError::InvalidChain(_))));
+        assert_eq!(chain.tip_index(), u64::MAX);
+    }
+
+    #[test]
+    fn append_block_accepts_only_the_next_linked_block() {
+        let mut local = Blockchain::new();
+        let mut remote = local.clone();
+        remote.add_block(vec![tx(TransactionType::PostCreated)]).unwrap();
+        remote.add_block(vec![tx(TransactionType::PostCreated)]).unwrap();
+
+        assert!(local.append_block(remote.blocks()[2].clone()).is_err());
+        local.append_block(remote.blocks()[1].clone()).unwrap();
+        local.append_block(remote.blocks()[2].clone()).unwrap();
+        assert_eq!(local.len(), 3);
+        assert!(local.is_valid());
+
+        let mut unlinked = Blockchain::new();
+        unlinked.add_block(vec![tx(TransactionType::PostCreated)]).unwrap();
+        let mut foreign = remote.blocks()[1].clone();
+        foreign.prev_hash = [7u8; 32];
+        assert!(unlinked.append_block(foreign).is_err());
+    }
+
+    #[test]
+    fn appending_a_block_with_a_wrong_prev_hash_is_rejected() {
+        let mut local = Blockchain::new();
+        let mut remote = local.clone();
+        remote.add_block(vec![tx(TransactionType::PostCreated)]).unwrap();
+
+        let mut block = remote.blocks()[1].clone();
+        block.prev_hash = [7u8; 32];
+        assert!(matches!(local.append_block(block), Err(BlockchainError::InvalidChain(_))));
+        assert_eq!(local.len(), 1);
+    }
+
+    #[test]
+    fn appending_a_block_with_tampered_transactions_is_rejected() {
+        let mut local = Blockchain::new();
+        let mut remote = local.clone();
+        remote.add_block(vec![tx(TransactionType::PostCreated)]).unwrap();
+
+        let mut tampered = remote.blocks()[1].clone();
+        tampered.transactions[0].payload = b"forged".to_vec();
+        assert!(matches!(local.append_block(tampered), Err(BlockchainError::InvalidChain(_))));
+
+        let mut emptied = remote.blocks()[1].clone();
+        emptied.transactions.clear();
+        assert!(matches!(local.append_block(emptied), Err(BlockchainError::NoTransactions)));
+
+        local.append_block(remote.blocks()[1].clone()).unwrap();
+        assert!(local.is_valid());
+    }
+
+    #[test]
+    fn zero_weight_verifications_never_finalise() {
+        let mut chain = Blockchain::new();
+        chain.add_block(vec![tx(TransactionType::PostCreated)]).unwrap();
+
+        for peer in ["full-a", "full-b"] {
+            assert!(!chain.verify_block(1, peer.into()).unwrap());
+        }
+        for peer in ["gossip-a", "gossip-b", "gossip-c", "gossip-d"] {
+            assert!(!chain.verify_block_weighted(1, peer.into(), 0).unwrap());
+        }
+        assert!(chain.verify_block(1, "full-c".into()).unwrap());
+    }
+
+    #[test]
+    fn partial_weights_add_up_to_the_threshold() {
+        let mut chain = Blockchain::new();
+        chain.add_block(vec![tx(TransactionType::PostCreated)]).unwrap();
+        let half = FULL_VERIFICATION_WEIGHT / 2;
+
+        assert!(!chain.verify_block(1, "full-a".into()).unwrap());
+        assert!(!chain.verify_block(1, "full-b".into()).unwrap());
+        assert!(!chain.verify_block_weighted(1, "light-a".into(), half).unwrap());
+        // A repeat from the same peer adds nothing.
+        assert!(!chain.verify_block_weighted(1, "light-a".into(), half).unwrap());
+        assert!(chain.verify_block_weighted(1, "light-b".into(), half).unwrap());
+    }
+
+    fn chain_of(len: usize) -> Blockchain {
+        let mut chain = Blockchain::new();
+        for _ in 1..len {
+            chain.add_block(vec![tx(TransactionType::PostCreated)]).unwrap();
+        }
+        chain
+    }
+
+    fn indices(blocks: &[Block]) -> Vec<u64> {
+        blocks.iter().map(|b| b.index).collect()
+    }
+
+    #[test]
+    fn blocks_range_returns_half_open_range() {
+        let chain = chain_of(5);
+        assert_eq!(indices(chain.blocks_range(1, 3)), vec![1, 2]);
+        assert_eq!(indices(chain.blocks_range(0, 5)), vec![0, 1, 2, 3, 4]);
+        assert!(chain.blocks_range(2, 2).is_empty());
+        assert!(chain.blocks_range(3, 1).is_empty());
+    }
+
+    fn finalise(chain: &mut Blockchain, index: u64) {
+        for peer in ["a", "b", "c"] {
+            chain.verify_block(index, peer.into()).unwrap();
+        }
+    }
+
+    #[test]
+    fn pruning_drops_only_finalised_blocks_below_the_window() {
+        let mut chain = chain_of(6);
+        for index in [1, 2, 4] {
+            finalise(&mut chain, index);
+        }
+
+        // Block 3 is not finalised, so block 4 must stay behind it.
+        assert_eq!(chain.prune_before(5), Some(2));
+        assert_eq!(indices(chain.blocks()), vec![0, 3, 4, 5]);
+        assert!(chain.is_pruned());
+        assert_eq!(chain.len(), 6);
+        assert!(chain.get_block(2).is_none());
+        assert_eq!(chain.get_block(4).unwrap().index, 4);
+        assert_eq!(indices(chain.blocks_from(1)), vec![3, 4, 5]);
+        assert!(matches!(chain.blocks_from_checked(2), Err(BlockchainError::BlockNotFound(2))));
+        assert_eq!(indices(chain.blocks_from_checked(3).unwrap()), vec![3, 4, 5]);
+        assert_eq!(chain.prune_before(5), None);
+
+        chain.add_block(vec![tx(TransactionType::PostCreated)]).unwrap();
+        assert!(chain.is_valid());
+        let restored = Blockchain::from_json(&chain.to_json().unwrap()).unwrap();
+        assert!(restored.is_pruned());
+        let mut broken = chain.clone();
+        broken.blocks[2].prev_hash = [9; 32];
+        assert_eq!(broken.verify_chain().unwrap_err().0, 2);
+        // The first kept block must link to the last block pruned.
+        let mut broken = chain.clone();
+        broken.blocks[1].prev_hash = [9; 32];
+        assert_eq!(broken.verify_chain().unwrap_err().0, 1);
+        let mut broken = chain.clone();
+        broken.pruned_to = Some((2, [0; 32]));
+        assert_eq!(broken.verify_chain().unwrap_err().0, 1);
+    }
+
+    #[test]
+    fn a_forged_gap_is_not_mistaken_for_pruning() {
+        let mut chain = chain_of(2);
+        let genesis = chain.blocks[0].clone();
+        let forged = Block::new(2, [0xAB; 32], vec![tx(TransactionType::PostCreated)]).unwrap();
+        let blocks = vec![genesis, forged];
+
+        assert!(matches!(
+            chain.try_replace(blocks.clone()),
+            Err(BlockchainError::InvalidChain(_))
+        ));
+        assert_eq!(chain.len(), 2);
+        let json = Blockchain::<Sha256Hasher>::from_blocks(blocks).to_json().unwrap();
+        assert!(matches!(Blockchain::from_json(&json), Err(BlockchainError::InvalidChain(_))));
+    }
+
+    #[test]
+    fn blocks_range_start_past_tip_is_empty() {
+        let chain = chain_of(3);
+        assert!(chain.blocks_range(3, 10).is_empty());
+        assert!(chain.blocks_range(u64::MAX, u64::MAX).is_empty());
+        assert!(chain.blocks_from(7).is_empty());
+    }
+
+    #[test]
+    fn blocks_from_checked_rejects_starts_past_the_next_block() {
+        let chain = chain_of(3);
+        assert_eq!(indices(chain.blocks_from_checked(1).unwrap()), vec![1, 2]);
+        assert!(chain.blocks_from_checked(3).unwrap().is_empty());
+        assert!(matches!(chain.blocks_from_checked(4), Err(BlockchainError::BlockNotFound(4))));
+    }
+
+    #[test]
+    fn blocks_range_end_past_tip_is_clamped() {
+        let chain = chain_of(3);
+        assert_eq!(indices(chain.blocks_range(1, 100)), vec![1, 2]);
+        assert_eq!(indices(chain.blocks_from(1)), vec![1, 2]);
+    }
+
+    #[test]
+    fn blocks_are_found_by_hash_and_hash_prefix() {
+        let chain = chain_of(3);
+        let block = chain.get_block(2).unwrap();
+        assert_eq!(chain.get_block_by_hash(&block.hash()).unwrap().index, 2);
+        assert!(chain.get_block_by_hash(&[0u8; 32]).is_none());
+
+        let hex = block.hash_hex();
+        for prefix in [hex.clone(), hex[..8].to_uppercase()] {
+            let found = chain.blocks_by_hash_prefix(&prefix);
+            assert_eq!(found.iter().map(|b| b.index).collect::<Vec<_>>(), vec![2]);
+        }
+        // The empty prefix matches everything.
+        assert_eq!(chain.blocks_by_hash_prefix("").len(), chain.len());
+    }
+
+    #[test]
+    fn iter_transactions_visits_every_transaction_in_order() {
+        let mut chain = Blockchain::new();
+        let first = vec![tx(TransactionType::PostCreated), tx(TransactionType::VoteCast)];
+        let second = vec![tx(TransactionType::ProjectPosted)];
+        let genesis_tx = chain.tip().transactions[0].id;
+        let expected = vec![
+            (0, genesis_tx),
+            (1, first[0].id),
+            (1, first[1].id),
+            (2, second[0].id),
+        ];
+        chain.add_block(first).unwrap();
+        chain.add_block(second).unwrap();
+
+        let visited: Vec<(u64, uuid::Uuid)> =
+            chain.iter_transactions().map(|(b, t)| (b.index, t.id)).collect();
+        assert_eq!(visited, expected);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let chain = chain_of(3);
+        let restored = Blockchain::from_json(&chain.to_json().unwrap()).unwrap();
+        assert_eq!(restored.len(), 3);
+        assert_eq!(restored.tip().hash(), chain.tip().hash());
+    }
+
+    #[test]
+    fn try_replace_swaps_in_a_valid_chain_only() {
+        let mut chain = chain_of(2);
+        let original = chain.tip().hash();
+
+        let mut longer = chain_of(4);
+        longer.blocks[3].prev_hash = [9u8; 32];
+        let err = chain.try_replace(longer.blocks.clone()).unwrap_err();
+        assert!(matches!(err, BlockchainError::InvalidChain(ref m) if m.contains("#3")), "{err}");
+        assert!(chain.try_replace(Vec::new()).is_err());
+        let other_network = Blockchain::new_with_network("testnet");
+        assert!(chain.try_replace(other_network.blocks).is_err());
+        assert_eq!((chain.len(), chain.tip().hash()), (2, original));
+
+        longer.blocks.truncate(3);
+        let tip = longer.tip().hash();
+        chain.try_replace(longer.blocks).unwrap();
+        assert_eq!((chain.len(), chain.tip().hash()), (3, tip));
+    }
+
+    #[test]
+    fn importing_an_invalid_chain_errors() {
+        let mut chain = chain_of(3);
+        chain.blocks[2].prev_hash = [9u8; 32];
+        let err = Blockchain::from_json(&chain.to_json().unwrap()).unwrap_err();
+        assert!(matches!(err, BlockchainError::InvalidChain(_)));
+
+        assert!(matches!(
+            Blockchain::from_json("{\"blocks\": []}"),
+            Err(BlockchainError::InvalidChain(_))
+        ));
+        assert!(matches!(
+            Blockchain::from_json("not json"),
+            Err(BlockchainError::Serialisation(_))
+        ));
+    }
+
     #[test]
     fn sync_from_longer_valid_chain() {
         let mut local = Blockchain::new();
@@ -177,6 +863,37 @@ mod tests {
         assert_eq!(local.len(), remote.len());
     }
 
+    #[test]
+    fn sync_from_copies_only_the_missing_suffix() {
+        let mut local = Blockchain::new();
+        local.add_block(vec![tx(TransactionType::NodeAdded)]).unwrap();
+        let mut remote = local.clone();
+        remote.add_block(vec![tx(TransactionType::NodeAdded)]).unwrap();
+
+        // Recorded only locally: it would be lost if the remote were cloned.
+        local.verify_block(1, "peer-a".into()).unwrap();
+
+        assert!(local.sync_from(&remote));
+        assert_eq!(local.len(), 3);
+        assert_eq!(local.tip().hash(), remote.tip().hash());
+        assert_eq!(local.get_block(1).unwrap().verifications, vec!["peer-a".to_string()]);
+        assert!(local.is_valid());
+    }
+
+    #[test]
+    fn sync_from_longer_fork_replaces_the_diverging_blocks() {
+        let mut local = Blockchain::new();
+        local.add_block(vec![tx(TransactionType::NodeAdded)]).unwrap();
+        let mut remote = Blockchain::new();
+        remote.add_block(vec![tx(TransactionType::PostCreated)]).unwrap();
+        remote.add_block(vec![tx(TransactionType::PostCreated)]).unwrap();
+
+        assert!(local.sync_from(&remote));
+        assert_eq!(local.len(), 3);
+        assert_eq!(local.get_block(1).unwrap().hash(), remote.get_block(1).unwrap().hash());
+        assert!(local.is_valid());
+    }
+
     #[test]
     fn sync_from_shorter_chain_ignored() {
         let mut local = Blockchain::new();
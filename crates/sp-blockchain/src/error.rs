@@ -19,4 +19,7 @@ pub enum BlockchainError {
 
     #[error("no transactions supplied for new block")]
     NoTransactions,
+
+    #[error("serialisation error: {0}")]
+    Serialisation(String),
 }
@@ -2,10 +2,21 @@ pub mod block;
 pub mod blockchain;
 pub mod error;
 
-pub use block::Block;
+pub use block::{Block, BlockHeader};
 pub use blockchain::Blockchain;
 pub use error::BlockchainError;
 
 /// Minimum number of distinct peer verifications required before a block is
 /// considered finalised.  Derived directly from the architecture spec.
 pub const MIN_VERIFICATIONS: usize = 3;
+
+/// Weight of one verification from a fully validating peer.  A block is
+/// finalised once its accumulated weight reaches
+/// `MIN_VERIFICATIONS * FULL_VERIFICATION_WEIGHT`, so lighter verifications
+/// (e.g. from peers that don't validate) can be given a fraction of this.
+pub const FULL_VERIFICATION_WEIGHT: u32 = 100;
+
+/// Network id of the main Social Production network.  Other ids (test
+/// networks, local experiments) get their own genesis block and so can never
+/// share a chain with it.
+pub const DEFAULT_NETWORK_ID: &str = "social-production";
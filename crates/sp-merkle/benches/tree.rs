@@ -0,0 +1,36 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use sp_merkle::MerkleTree;
+use sp_transaction::{Transaction, TransactionType};
+
+fn transactions(count: usize) -> Vec<Transaction> {
+    (0..count)
+        .map(|i| Transaction::new(TransactionType::PostCreated, i.to_le_bytes().to_vec()))
+        .collect()
+}
+
+fn build(c: &mut Criterion) {
+    let mut group = c.benchmark_group("merkle_tree_build");
+    for count in [1_000, 10_000] {
+        let txs = transactions(count);
+        group.bench_with_input(BenchmarkId::from_parameter(count), &txs, |b, txs| {
+            b.iter(|| MerkleTree::new(black_box(txs)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn proof(c: &mut Criterion) {
+    let mut group = c.benchmark_group("merkle_tree_proof");
+    for count in [1_000, 10_000] {
+        let txs = transactions(count);
+        let tree = MerkleTree::new(&txs).unwrap();
+        let id = txs[count / 2].id;
+        group.bench_with_input(BenchmarkId::from_parameter(count), &txs, |b, txs| {
+            b.iter(|| tree.proof(black_box(txs), id).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, build, proof);
+criterion_main!(benches);
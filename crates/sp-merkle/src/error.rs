@@ -10,4 +10,10 @@ pub enum MerkleError {
 
     #[error("transaction hashing failed: {0}")]
     HashFailed(String),
+
+    #[error("content does not match its hash")]
+    HashMismatch,
+
+    #[error("malformed proof encoding")]
+    MalformedProof,
 }
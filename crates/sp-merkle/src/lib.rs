@@ -1,7 +1,9 @@
 pub mod error;
 pub mod proof;
+pub mod store;
 pub mod tree;
 
 pub use error::MerkleError;
 pub use proof::{MerkleProof, ProofNode};
+pub use store::{content_hash, AssetStore};
 pub use tree::MerkleTree;
@@ -1,5 +1,12 @@
+use std::marker::PhantomData;
+
 use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
+use sp_transaction::{Hasher, Sha256Hasher};
+
+use crate::{error::MerkleError, tree::hash_pair};
+
+/// Encoded size of one [`ProofNode`]: a side byte followed by the hash.
+const NODE_LEN: usize = 33;
 
 /// Indicates which side the sibling hash sits on when re-computing a parent.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -18,36 +25,113 @@ pub struct ProofNode {
 /// An inclusion proof for a single transaction leaf.
 ///
 /// Verify by hashing the leaf upward through each sibling until the computed
-/// root matches the expected root.
+/// root matches the expected root.  `H` is the hasher of the tree the proof
+/// came from, so verification always uses the same function as building.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct MerkleProof {
+pub struct MerkleProof<H: Hasher = Sha256Hasher> {
     /// The hash of the leaf being proved.
     pub leaf_hash: [u8; 32],
     /// Ordered list of sibling hashes from leaf to root.
     pub path: Vec<ProofNode>,
+    #[serde(skip)]
+    hasher: PhantomData<H>,
 }
 
-impl MerkleProof {
+impl<H: Hasher> MerkleProof<H> {
+    pub fn new(leaf_hash: [u8; 32], path: Vec<ProofNode>) -> Self {
+        Self {
+            leaf_hash,
+            path,
+            hasher: PhantomData,
+        }
+    }
+
     /// Returns `true` if following the proof path reproduces `expected_root`.
     pub fn verify(&self, expected_root: &[u8; 32]) -> bool {
         let mut current = self.leaf_hash;
 
         for node in &self.path {
-            let combined = match node.side {
-                ProofSide::Left => {
-                    let mut v = node.hash.to_vec();
-                    v.extend_from_slice(&current);
-                    v
-                }
-                ProofSide::Right => {
-                    let mut v = current.to_vec();
-                    v.extend_from_slice(&node.hash);
-                    v
-                }
+            current = match node.side {
+                ProofSide::Left => hash_pair::<H>(&node.hash, &current),
+                ProofSide::Right => hash_pair::<H>(&current, &node.hash),
             };
-            current = Sha256::digest(&combined).into();
         }
 
         &current == expected_root
     }
+
+    /// Compact wire encoding: the 32-byte leaf hash followed by one
+    /// `side || hash` entry (33 bytes, side `0` = left, `1` = right) per step.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(32 + self.path.len() * NODE_LEN);
+        bytes.extend_from_slice(&self.leaf_hash);
+        for node in &self.path {
+            bytes.push(match node.side {
+                ProofSide::Left => 0,
+                ProofSide::Right => 1,
+            });
+            bytes.extend_from_slice(&node.hash);
+        }
+        bytes
+    }
+
+    /// Decode a proof produced by [`MerkleProof::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MerkleError> {
+        if bytes.len() < 32 {
+            return Err(MerkleError::MalformedProof);
+        }
+        let (leaf, rest) = bytes.split_at(32);
+        let chunks = rest.chunks_exact(NODE_LEN);
+        if !chunks.remainder().is_empty() {
+            return Err(MerkleError::MalformedProof);
+        }
+        let path = chunks
+            .map(|chunk| {
+                let side = match chunk[0] {
+                    0 => ProofSide::Left,
+                    1 => ProofSide::Right,
+                    _ => return Err(MerkleError::MalformedProof),
+                };
+                let mut hash = [0u8; 32];
+                hash.copy_from_slice(&chunk[1..]);
+                Ok(ProofNode { hash, side })
+            })
+            .collect::<Result<_, _>>()?;
+
+        let mut leaf_hash = [0u8; 32];
+        leaf_hash.copy_from_slice(leaf);
+        Ok(Self::new(leaf_hash, path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sp_transaction::{Transaction, TransactionType};
+
+    use super::*;
+    use crate::MerkleTree;
+
+    #[test]
+    fn proof_bytes_round_trip() {
+        let txs: Vec<Transaction> = (0..5)
+            .map(|i| Transaction::new(TransactionType::CommentAdded, vec![i]))
+            .collect();
+        let tree = MerkleTree::new(&txs).unwrap();
+        let proof = tree.proof(&txs, txs[3].id).unwrap();
+
+        let decoded = MerkleProof::from_bytes(&proof.to_bytes()).unwrap();
+        assert_eq!(decoded, proof);
+        assert!(decoded.verify(&tree.root_hash().unwrap()));
+    }
+
+    #[test]
+    fn truncated_proof_bytes_are_rejected() {
+        let proof: MerkleProof = MerkleProof::new(
+            [1u8; 32],
+            vec![ProofNode { hash: [2u8; 32], side: ProofSide::Left }],
+        );
+        let bytes = proof.to_bytes();
+        assert!(MerkleProof::<Sha256Hasher>::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(MerkleProof::<Sha256Hasher>::from_bytes(&bytes[..16]).is_err());
+    }
 }
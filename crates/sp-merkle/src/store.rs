@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+
+use sha2::{Digest, Sha256};
+
+use crate::error::MerkleError;
+
+/// Content-addressed storage for transaction payloads ("assets").
+///
+/// Every payload is keyed by the SHA-256 digest of its bytes, so a holder can
+/// serve it to any peer that knows the hash and the receiver can check that
+/// what it got back is exactly what it asked for.
+///
+/// The key is *not* the transaction's Merkle leaf hash: a leaf hashes the
+/// whole serialised transaction (id, type, timestamp, signature and payload),
+/// so a peer handed only the payload bytes could never check them against
+/// it.  Hashing the payload alone keeps assets self-verifying; the leaf still
+/// commits to the payload, so a payload fetched here can be tied back to a
+/// block by rebuilding its transaction and proving that.
+#[derive(Debug, Clone, Default)]
+pub struct AssetStore {
+    assets: HashMap<[u8; 32], Vec<u8>>,
+}
+
+/// SHA-256 content hash used as the key in an [`AssetStore`].
+pub fn content_hash(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+impl AssetStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store `data` and return its content hash.  Storing the same bytes twice
+    /// is a no-op.
+    pub fn insert(&mut self, data: Vec<u8>) -> [u8; 32] {
+        let hash = content_hash(&data);
+        self.assets.entry(hash).or_insert(data);
+        hash
+    }
+
+    /// Store `data` fetched from elsewhere under the hash it was requested by.
+    /// Returns an error (and stores nothing) if the bytes don't hash to `hash`.
+    pub fn insert_verified(&mut self, hash: [u8; 32], data: Vec<u8>) -> Result<(), MerkleError> {
+        if content_hash(&data) != hash {
+            return Err(MerkleError::HashMismatch);
+        }
+        self.assets.entry(hash).or_insert(data);
+        Ok(())
+    }
+
+    /// Fetch the payload stored under `hash`, if any.
+    pub fn get(&self, hash: &[u8; 32]) -> Option<&[u8]> {
+        self.assets.get(hash).map(Vec::as_slice)
+    }
+
+    pub fn contains(&self, hash: &[u8; 32]) -> bool {
+        self.assets.contains_key(hash)
+    }
+
+    /// Number of distinct assets held.
+    pub fn len(&self) -> usize {
+        self.assets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.assets.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_then_get_by_hash() {
+        let mut store = AssetStore::new();
+        let hash = store.insert(b"profile picture".to_vec());
+        assert_eq!(hash, content_hash(b"profile picture"));
+        assert_eq!(store.get(&hash), Some(&b"profile picture"[..]));
+    }
+
+    #[test]
+    fn identical_content_is_stored_once() {
+        let mut store = AssetStore::new();
+        let a = store.insert(b"same".to_vec());
+        let b = store.insert(b"same".to_vec());
+        assert_eq!(a, b);
+        assert_eq!(store.len(), 1);
+    }
+
+    #[test]
+    fn insert_verified_rejects_mismatched_content() {
+        let mut store = AssetStore::new();
+        let hash = content_hash(b"expected");
+        assert!(store.insert_verified(hash, b"tampered".to_vec()).is_err());
+        assert!(store.is_empty());
+        assert!(store.insert_verified(hash, b"expected".to_vec()).is_ok());
+        assert!(store.contains(&hash));
+    }
+}
@@ -1,5 +1,6 @@
-use sha2::{Digest, Sha256};
-use sp_transaction::Transaction;
+use std::marker::PhantomData;
+
+use sp_transaction::{Hasher, Sha256Hasher, Transaction};
 use uuid::Uuid;
 
 use crate::{
@@ -9,55 +10,64 @@ use crate::{
 
 /// A binary Merkle tree built from a slice of [`Transaction`]s.
 ///
-/// Leaf hashes are the SHA-256 digests of each serialised transaction.
-/// Parent hashes are SHA-256 of `left_child || right_child`.
+/// Leaf hashes are the `H` digests of each serialised transaction (SHA-256
+/// by default).  Parent hashes are `H` of `left_child || right_child`.
 /// When the number of leaves is odd the last leaf is duplicated so that every
 /// level is always even-width.
 #[derive(Debug, Clone)]
-pub struct MerkleTree {
+pub struct MerkleTree<H: Hasher = Sha256Hasher> {
     /// All levels of the tree, `levels[0]` = leaf hashes,
     /// `levels[last]` = single root hash.
     levels: Vec<Vec<[u8; 32]>>,
+    hasher: PhantomData<H>,
 }
 
-fn hash_pair(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
-    let mut v = left.to_vec();
-    v.extend_from_slice(right);
-    Sha256::digest(&v).into()
+pub(crate) fn hash_pair<H: Hasher>(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut pair = [0u8; 64];
+    pair[..32].copy_from_slice(left);
+    pair[32..].copy_from_slice(right);
+    H::digest(&pair)
 }
 
 impl MerkleTree {
-    /// Build a Merkle tree from `transactions`.  Returns an error if the slice
-    /// is empty or any transaction cannot be hashed.
+    /// Build a SHA-256 Merkle tree from `transactions`.  Returns an error if
+    /// the slice is empty or any transaction cannot be hashed.
     pub fn new(transactions: &[Transaction]) -> Result<Self, MerkleError> {
+        Self::build(transactions)
+    }
+}
+
+impl<H: Hasher> MerkleTree<H> {
+    /// Build a Merkle tree from `transactions` using the hasher `H`.
+    pub fn build(transactions: &[Transaction]) -> Result<Self, MerkleError> {
         if transactions.is_empty() {
             return Err(MerkleError::Empty);
         }
 
-        let mut leaves: Vec<[u8; 32]> = transactions
+        let leaves: Vec<[u8; 32]> = transactions
             .iter()
-            .map(|tx| tx.hash().map_err(|e| MerkleError::HashFailed(e.to_string())))
+            .map(|tx| tx.hash_with::<H>().map_err(|e| MerkleError::HashFailed(e.to_string())))
             .collect::<Result<_, _>>()?;
 
-        let mut levels: Vec<Vec<[u8; 32]>> = vec![leaves.clone()];
+        // A tree over n leaves has ceil(log2(n)) + 1 levels.
+        let depth = (usize::BITS - (leaves.len() - 1).leading_zeros()) as usize + 1;
+        let mut levels: Vec<Vec<[u8; 32]>> = Vec::with_capacity(depth);
+        levels.push(leaves);
 
-        while leaves.len() > 1 {
-            // Duplicate last leaf when odd number of leaves.
-            if leaves.len() % 2 != 0 {
-                let last = *leaves.last().unwrap();
-                leaves.push(last);
-            }
-
-            let parent_level: Vec<[u8; 32]> = leaves
+        // Levels are stored unpadded: an odd trailing node is paired with
+        // itself here and in `proof` instead of being pushed twice.
+        while let Some(level) = levels.last().filter(|level| level.len() > 1) {
+            let parent_level: Vec<[u8; 32]> = level
                 .chunks(2)
-                .map(|chunk| hash_pair(&chunk[0], &chunk[1]))
+                .map(|pair| hash_pair::<H>(&pair[0], pair.last().unwrap()))
                 .collect();
-
-            levels.push(parent_level.clone());
-            leaves = parent_level;
+            levels.push(parent_level);
         }
 
-        Ok(Self { levels })
+        Ok(Self {
+            levels,
+            hasher: PhantomData,
+        })
     }
 
     /// The Merkle root hash.  Returns an error if the tree is empty (shouldn't
@@ -84,7 +94,7 @@ impl MerkleTree {
         &self,
         transactions: &[Transaction],
         tx_id: Uuid,
-    ) -> Result<MerkleProof, MerkleError> {
+    ) -> Result<MerkleProof<H>, MerkleError> {
         // Find the leaf index that corresponds to tx_id.
         let leaf_index = transactions
             .iter()
@@ -96,15 +106,9 @@ impl MerkleTree {
         let mut index = leaf_index;
 
         for level in &self.levels[..self.levels.len() - 1] {
-            // Ensure the level is padded to even length (mirrors build logic).
-            let mut padded = level.clone();
-            if padded.len() % 2 != 0 {
-                let last = *padded.last().unwrap();
-                padded.push(last);
-            }
-
+            // An odd trailing node is its own sibling (mirrors build logic).
             let sibling_index = if index % 2 == 0 { index + 1 } else { index - 1 };
-            let sibling_hash = padded[sibling_index];
+            let sibling_hash = level.get(sibling_index).copied().unwrap_or(level[index]);
             let side = if index % 2 == 0 {
                 ProofSide::Right
             } else {
@@ -119,7 +123,7 @@ impl MerkleTree {
             index /= 2;
         }
 
-        Ok(MerkleProof { leaf_hash, path })
+        Ok(MerkleProof::new(leaf_hash, path))
     }
 }
 
@@ -140,6 +144,27 @@ mod tests {
         assert_eq!(tree.root_hash().unwrap(), tx.hash().unwrap());
     }
 
+    #[test]
+    fn root_of_fixed_input_is_stable() {
+        // Seven leaves, so the odd-width padding is exercised on two levels.
+        let txs: Vec<Transaction> = (0..7u8)
+            .map(|i| Transaction {
+                id: Uuid::from_u128(u128::from(i)),
+                kind: TransactionType::PostCreated,
+                payload: vec![i],
+                timestamp: 1_700_000_000 + i64::from(i),
+                priority: 0,
+                signature: Vec::new(),
+            })
+            .collect();
+
+        let tree = MerkleTree::new(&txs).unwrap();
+        assert_eq!(
+            tree.root_hash_hex().unwrap(),
+            "bc63e15ef6bff0733d639900f2c81a9c35109aa4ca868591c9cda4188b77db35"
+        );
+    }
+
     #[test]
     fn empty_transactions_returns_error() {
         assert!(MerkleTree::new(&[]).is_err());
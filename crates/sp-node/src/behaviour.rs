@@ -10,14 +10,46 @@ use libp2p::{
     swarm::NetworkBehaviour,
 };
 
-use crate::protocol::{TOPIC_BLOCK, TOPIC_TX, TOPIC_VERIFY};
+use crate::{
+    config::NodeConfig,
+    protocol::{agent_version, default_agent, BUILTIN_TOPICS, PROTOCOL_VERSION, SYNC_PROTOCOL},
+};
+
+/// Largest sync request accepted from a peer.  Requests are small fixed-size
+/// enums, so anything near this is malformed or hostile.
+pub const MAX_REQUEST_SIZE: usize = 64 * 1024;
 
 /// Codec for the block sync request-response protocol.
 ///
 /// Both request and response are raw byte vectors; serialisation/deserialisation
-/// is handled in the node layer using `bincode`.
-#[derive(Clone, Default)]
-pub struct SyncCodec;
+/// is handled in the node layer using `bincode`.  Responses longer than
+/// `max_response_size` bytes are refused with an I/O error.
+#[derive(Clone)]
+pub struct SyncCodec {
+    max_response_size: usize,
+}
+
+impl SyncCodec {
+    pub fn new(max_response_size: usize) -> Self {
+        Self { max_response_size }
+    }
+}
+
+/// Read `io` to the end, failing once more than `limit` bytes arrive.
+async fn read_limited<T>(io: &mut T, limit: usize) -> std::io::Result<Vec<u8>>
+where
+    T: AsyncRead + Unpin + Send,
+{
+    let mut buf = Vec::new();
+    io.take(limit as u64 + 1).read_to_end(&mut buf).await?;
+    if buf.len() > limit {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("message exceeds {limit} bytes"),
+        ));
+    }
+    Ok(buf)
+}
 
 #[async_trait]
 impl Codec for SyncCodec {
@@ -33,9 +65,7 @@ impl Codec for SyncCodec {
     where
         T: AsyncRead + Unpin + Send,
     {
-        let mut buf = Vec::new();
-        io.read_to_end(&mut buf).await?;
-        Ok(buf)
+        read_limited(io, MAX_REQUEST_SIZE).await
     }
 
     async fn read_response<T>(
@@ -46,9 +76,7 @@ impl Codec for SyncCodec {
     where
         T: AsyncRead + Unpin + Send,
     {
-        let mut buf = Vec::new();
-        io.read_to_end(&mut buf).await?;
-        Ok(buf)
+        read_limited(io, self.max_response_size).await
     }
 
     async fn write_request<T>(
@@ -103,8 +131,17 @@ pub struct SpBehaviour {
 }
 
 /// Build the combined [`SpBehaviour`] for the given keypair.
+///
+/// Gossip messages larger than `config.max_message_size` bytes are refused
+/// both when publishing and when received from peers.  Sync requests time out
+/// after `config.request_timeout` and responses are capped at
+/// `config.max_response_size` bytes.  `genesis_hash` is advertised through
+/// Identify after `config.agent_version` so peers on a different chain can be
+/// told apart.
 pub fn build_behaviour(
     keypair: &libp2p::identity::Keypair,
+    config: &NodeConfig,
+    genesis_hash: &[u8; 32],
 ) -> Result<SpBehaviour, Box<dyn std::error::Error + Send + Sync>> {
     let peer_id = keypair.public().to_peer_id();
 
@@ -112,6 +149,7 @@ pub fn build_behaviour(
     let gossipsub_config = gossipsub::ConfigBuilder::default()
         .heartbeat_interval(std::time::Duration::from_secs(10))
         .validation_mode(gossipsub::ValidationMode::Strict)
+        .max_transmit_size(config.max_message_size)
         .build()
         .map_err(|e| format!("gossipsub config: {e}"))?;
 
@@ -121,7 +159,8 @@ pub fn build_behaviour(
     )
     .map_err(|e| format!("gossipsub init: {e}"))?;
 
-    for topic_str in [TOPIC_TX, TOPIC_VERIFY, TOPIC_BLOCK] {
+    let extra_topics = config.extra_topics.iter().map(String::as_str);
+    for topic_str in BUILTIN_TOPICS.into_iter().chain(extra_topics) {
         let topic = gossipsub::IdentTopic::new(topic_str);
         gossipsub.subscribe(&topic)?;
     }
@@ -131,24 +170,28 @@ pub fn build_behaviour(
     let kademlia = Kademlia::new(peer_id, store);
 
     // mDNS
-    let mdns = Mdns::new(mdns::Config::default(), peer_id)?;
+    let mdns_config = mdns::Config {
+        ttl: config.mdns_ttl,
+        query_interval: config.mdns_query_interval,
+        ..mdns::Config::default()
+    };
+    let mdns = Mdns::new(mdns_config, peer_id)?;
 
     // Ping — pings each connected peer every 15 s; disconnects after 3 timeouts.
     let ping = Ping::new(ping::Config::new());
 
     // Identify
-    let identify = Identify::new(identify::Config::new(
-        "/sp/1.0.0".into(),
-        keypair.public(),
-    ));
+    let agent = config.agent_version.clone().unwrap_or_else(default_agent);
+    let identify = Identify::new(
+        identify::Config::new(PROTOCOL_VERSION.into(), keypair.public())
+            .with_agent_version(agent_version(&agent, genesis_hash)),
+    );
 
     // Request-response (block sync)
-    let request_response = RequestResponse::new(
-        [(
-            "/sp/sync/1.0.0".to_string(),
-            ProtocolSupport::Full,
-        )],
-        request_response::Config::default(),
+    let request_response = RequestResponse::with_codec(
+        SyncCodec::new(config.max_response_size),
+        [(SYNC_PROTOCOL.to_string(), ProtocolSupport::Full)],
+        request_response::Config::default().with_request_timeout(config.request_timeout),
     );
 
     Ok(SpBehaviour {
@@ -160,3 +203,37 @@ pub fn build_behaviour(
         request_response,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::TOPIC_TX;
+
+    #[tokio::test]
+    async fn gossip_message_limit_is_applied() {
+        let keypair = libp2p::identity::Keypair::generate_ed25519();
+        let config = NodeConfig { max_message_size: 1024, ..NodeConfig::default() };
+        let mut behaviour = build_behaviour(&keypair, &config, &[0u8; 32]).unwrap();
+        let topic = gossipsub::IdentTopic::new(TOPIC_TX);
+
+        let err = behaviour.gossipsub.publish(topic.clone(), vec![0u8; 2048]).unwrap_err();
+        assert!(matches!(err, gossipsub::PublishError::MessageTooLarge));
+
+        // A small message gets past the size check and only fails for lack of peers.
+        let err = behaviour.gossipsub.publish(topic, vec![0u8; 16]).unwrap_err();
+        assert!(!matches!(err, gossipsub::PublishError::MessageTooLarge));
+    }
+
+    #[tokio::test]
+    async fn sync_responses_are_capped() {
+        let mut codec = SyncCodec::new(8);
+        let protocol = SYNC_PROTOCOL.to_string();
+
+        let mut small = futures::io::Cursor::new(vec![1u8; 8]);
+        assert_eq!(codec.read_response(&protocol, &mut small).await.unwrap(), vec![1u8; 8]);
+
+        let mut large = futures::io::Cursor::new(vec![1u8; 9]);
+        let err = codec.read_response(&protocol, &mut large).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
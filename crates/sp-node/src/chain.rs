@@ -0,0 +1,41 @@
+//! On-disk copy of the node's blockchain.
+//!
+//! Stored with bincode in the node's data directory: loaded by
+//! [`Node::new`](crate::Node::new) and written by
+//! [`Node::shutdown`](crate::Node::shutdown).  For a human-readable form use
+//! [`Blockchain::to_json`].
+
+use std::{fs, io, path::Path};
+
+use sp_blockchain::Blockchain;
+
+/// File name of the chain inside the node's data directory.
+pub const CHAIN_FILE: &str = "chain.bin";
+
+/// Read a stored chain.  Returns `Ok(None)` if there is no file yet, and an
+/// error if the file can't be decoded or doesn't hold a valid chain.
+pub fn load(path: &Path) -> io::Result<Option<Blockchain>> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let chain: Blockchain = bincode::deserialize(&bytes)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if let Err((at, reason)) = chain.verify_chain() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("stored block #{at} is invalid: {reason}"),
+        ));
+    }
+    Ok(Some(chain))
+}
+
+/// Overwrite the stored chain at `path`, creating its directory if needed.
+pub fn save(path: &Path, chain: &Blockchain) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let bytes = bincode::serialize(chain).map_err(io::Error::other)?;
+    fs::write(path, bytes)
+}
@@ -1,10 +1,14 @@
-use std::ops::RangeInclusive;
+use std::{net::SocketAddr, ops::RangeInclusive, path::PathBuf, time::Duration};
 
-use crate::mode::NodeMode;
+use libp2p::Multiaddr;
+use serde::{Deserialize, Serialize};
 use sp_sync::SyncStrategy;
+use sp_transaction::PayloadKey;
+
+use crate::mode::NodeMode;
 
 /// Controls which peer-discovery mechanism(s) the node uses.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum DiscoveryMode {
     /// Use only the Kademlia DHT for global peer discovery (default).
     KademliaDht,
@@ -23,6 +27,34 @@ impl Default for DiscoveryMode {
     }
 }
 
+/// Which discovered peers the node dials on its own.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AutoConnectPolicy {
+    /// Dial every peer as soon as it is discovered.
+    All,
+    /// Dial discovered peers only while the node has fewer than this many
+    /// connections, counting automatic dials still in progress.
+    UpTo(usize),
+    /// Never dial on discovery; connections are made explicitly (default).
+    #[default]
+    None,
+}
+
+/// The conventional data directory: `$XDG_DATA_HOME/spn`, falling back to
+/// `~/.local/share/spn`.
+pub fn default_data_dir() -> PathBuf {
+    std::env::var("XDG_DATA_HOME")
+        .ok()
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var("HOME")
+                .ok()
+                .map(|h| PathBuf::from(h).join(".local").join("share"))
+        })
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("spn")
+}
+
 /// Full configuration for a [`crate::Node`].
 #[derive(Debug, Clone)]
 pub struct NodeConfig {
@@ -33,10 +65,29 @@ pub struct NodeConfig {
     /// be acted on during discovery.  `None` (the default) accepts all ports.
     pub discovery_port_range: Option<RangeInclusive<u16>>,
 
+    /// Whether [`Node::dial`](crate::Node::dial) refuses addresses whose port
+    /// is outside `discovery_port_range`.  Manual dials are deliberate, so by
+    /// default (`false`) they go ahead and are only reported with
+    /// [`NodeEvent::DialOutsidePortRange`](crate::NodeEvent::DialOutsidePortRange).
+    pub strict_port_range: bool,
+
     /// Which peer-discovery mechanism(s) to use.
     pub discovery_mode: DiscoveryMode,
 
-    /// Full participant vs. gossip-only operation.
+    /// Whether newly discovered peers are dialled automatically.  Defaults
+    /// to [`AutoConnectPolicy::None`], so a node only dials when asked.
+    pub auto_connect: AutoConnectPolicy,
+
+    /// How often mDNS asks the local network for peers.  Defaults to five
+    /// minutes; shorten it where nodes come and go often.
+    pub mdns_query_interval: Duration,
+
+    /// How long an address learnt through mDNS stays valid without being
+    /// seen again.  Defaults to six minutes; once it lapses the address is
+    /// dropped from the discovered peers.
+    pub mdns_ttl: Duration,
+
+    /// Full participant, gossip-only relay or read-only observer.
     pub mode: NodeMode,
 
     /// Controls which blocks are synced from peers.
@@ -47,6 +98,123 @@ pub struct NodeConfig {
     /// The library itself does not initialise a tracing subscriber; this flag
     /// is a signal to the host binary.
     pub quiet: bool,
+
+    /// Assemble blocks without a network: transactions are accepted and
+    /// sealed as usual, but nothing is published on gossip and no
+    /// verifications are sent.  For exercising the transaction → block →
+    /// Merkle path in isolation.  Defaults to `false`.
+    pub dry_run: bool,
+
+    /// Seal transactions still pending at [`Node::shutdown`](crate::Node::shutdown)
+    /// into final blocks before the chain is saved, instead of dropping
+    /// them.  Observers have nothing to seal.  Defaults to `false`.
+    pub seal_on_shutdown: bool,
+
+    /// Close connections that have carried no protocol traffic for this long.
+    /// `None` (the default) keeps libp2p's swarm default.
+    pub idle_timeout: Option<Duration>,
+
+    /// Largest gossip message, in bytes, the node will send or accept.
+    /// Defaults to 1 MiB; anything larger is dropped before decoding.
+    pub max_message_size: usize,
+
+    /// Most transactions sealed into one block; the highest-priority ones
+    /// are taken first.  Defaults to 1 000.
+    pub max_block_transactions: usize,
+
+    /// Most blocks, genesis included, the node keeps.  Once the chain grows
+    /// past this, finalised blocks below the window are pruned (see
+    /// [`Blockchain::prune_before`](sp_blockchain::Blockchain::prune_before));
+    /// unfinalised ones are kept until they finalise.  `None` (the default)
+    /// keeps every block.
+    pub max_chain_blocks: Option<usize>,
+
+    /// Most transactions held in the mempool while waiting for a block.
+    /// Defaults to 10 000; beyond that the oldest by timestamp are evicted.
+    pub max_mempool: usize,
+
+    /// Pending transactions older than this are evicted.  Defaults to one
+    /// hour.
+    pub mempool_ttl: Duration,
+
+    /// How long to wait for a peer to answer a sync request before giving
+    /// up on it.  Defaults to 30 seconds.
+    pub request_timeout: Duration,
+
+    /// Largest sync response, in bytes, the node will read.  Defaults to
+    /// 16 MiB; a peer sending more has the request failed.
+    pub max_response_size: usize,
+
+    /// How often the node emits
+    /// [`NodeEvent::Heartbeat`](crate::NodeEvent::Heartbeat) while it runs.
+    /// Defaults to 5 seconds; zero is refused by [`Node::new`](crate::Node::new).
+    pub heartbeat_interval: Duration,
+
+    /// How many undelivered events the node's event channel holds before
+    /// coalescing and dropping high-volume ones (see
+    /// [`NodeEvent`](crate::NodeEvent)).  Defaults to 1 024.
+    pub event_capacity: usize,
+
+    /// SOCKS5 proxy (e.g. Tor on `127.0.0.1:9050`) that every outbound TCP
+    /// connection goes through.  The node then listens on loopback only, for
+    /// connections the proxy forwards, and announces just `external_addrs`.
+    /// `None` (the default) dials directly.  mDNS still answers on the local
+    /// network, so pair this with [`DiscoveryMode::KademliaDht`].
+    pub proxy: Option<SocketAddr>,
+
+    /// Serve the HTTP health endpoint (see [`crate::health`]) on this
+    /// address: `/live` once the node listens, `/ready` once it also has a
+    /// peer.  `None` (the default) serves nothing.
+    pub health_addr: Option<SocketAddr>,
+
+    /// Addresses the node is reachable at besides its listen addresses, such
+    /// as `/dns/<name>.onion/tcp/<port>` for a Tor hidden service.  They are
+    /// announced in the DHT and through Identify.  Defaults to none.
+    pub external_addrs: Vec<Multiaddr>,
+
+    /// Peers dialled when the node starts; those whose address ends in
+    /// `/p2p/<id>` also seed the Kademlia routing table.  Defaults to none.
+    pub bootstrap_peers: Vec<Multiaddr>,
+
+    /// Domain whose `_dnsaddr` TXT records (see [`crate::dnsaddr`]) list more
+    /// bootstrap peers, resolved at startup and added to `bootstrap_peers`.
+    /// If the lookup fails the node logs a warning and starts with the
+    /// configured peers alone.  `None` (the default) skips the lookup.
+    pub bootstrap_dns: Option<String>,
+
+    /// Which network the node belongs to.  The id is folded into the genesis
+    /// block, so nodes on different networks refuse each other's chains.
+    /// Defaults to [`sp_blockchain::DEFAULT_NETWORK_ID`].
+    pub network_id: String,
+
+    /// Application gossip topics to subscribe to besides the built-in ones.
+    /// Messages on them arrive as
+    /// [`NodeEvent::TopicMessage`](crate::NodeEvent::TopicMessage); send with
+    /// [`Node::publish`](crate::Node::publish).  Built-in topic names are
+    /// ignored here.
+    pub extra_topics: Vec<String>,
+
+    /// Agent name advertised to peers through Identify, so nodes can be told
+    /// apart in peer logs.  `None` (the default) advertises
+    /// `sp-node/<crate version>`.  The genesis hash is always appended.
+    pub agent_version: Option<String>,
+
+    /// Tag stored in [`Block::metadata`](sp_blockchain::Block::metadata) of
+    /// every block this node forms.  `None` (the default) leaves it empty.
+    pub block_metadata: Option<Vec<u8>>,
+
+    /// Key shared by the network's members for private payloads.  When set,
+    /// every transaction this node broadcasts has its payload encrypted
+    /// with it first; readers recover the plaintext with
+    /// [`Transaction::decrypt_payload`](sp_transaction::Transaction::decrypt_payload).
+    /// `None` (the default) gossips payloads in the clear.
+    pub payload_key: Option<PayloadKey>,
+
+    /// Directory for state that should survive a restart: the chain, the node
+    /// identity, verified blocks and the routing snapshot.  `None` (the
+    /// default) keeps everything in memory and uses a fresh identity.  The
+    /// binaries pass [`default_data_dir`] unless told otherwise.
+    pub data_dir: Option<PathBuf>,
 }
 
 impl Default for NodeConfig {
@@ -54,10 +222,37 @@ impl Default for NodeConfig {
         Self {
             port: 51025,
             discovery_port_range: None,
+            strict_port_range: false,
             discovery_mode: DiscoveryMode::default(),
+            auto_connect: AutoConnectPolicy::default(),
+            mdns_query_interval: Duration::from_secs(5 * 60),
+            mdns_ttl: Duration::from_secs(6 * 60),
             mode: NodeMode::default(),
             sync_strategy: SyncStrategy::default(),
             quiet: false,
+            dry_run: false,
+            seal_on_shutdown: false,
+            idle_timeout: None,
+            max_message_size: 1024 * 1024,
+            max_block_transactions: 1000,
+            max_chain_blocks: None,
+            max_mempool: 10_000,
+            mempool_ttl: Duration::from_secs(60 * 60),
+            request_timeout: Duration::from_secs(30),
+            max_response_size: 16 * 1024 * 1024,
+            heartbeat_interval: Duration::from_secs(5),
+            event_capacity: 1024,
+            proxy: None,
+            health_addr: None,
+            external_addrs: Vec::new(),
+            bootstrap_peers: Vec::new(),
+            bootstrap_dns: None,
+            network_id: sp_blockchain::DEFAULT_NETWORK_ID.to_string(),
+            extra_topics: Vec::new(),
+            agent_version: None,
+            block_metadata: None,
+            payload_key: None,
+            data_dir: None,
         }
     }
 }
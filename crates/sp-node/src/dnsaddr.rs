@@ -0,0 +1,236 @@
+//! Bootstrap peers published in DNS, in the style of libp2p's `_dnsaddr`.
+//!
+//! A network operator lists its bootstrap nodes as TXT records on
+//! `_dnsaddr.<domain>`, each reading `dnsaddr=<multiaddr>`.  Moving a
+//! bootstrap node then only means editing DNS, not reconfiguring every
+//! node.  [`SystemResolver`] asks the first `nameserver` in
+//! `/etc/resolv.conf` over UDP; tests substitute their own [`TxtResolver`].
+
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use libp2p::Multiaddr;
+use tokio::net::UdpSocket;
+use tracing::debug;
+
+/// Prefix of a bootstrap entry in a TXT record.
+const DNSADDR_PREFIX: &str = "dnsaddr=";
+
+/// How long to wait for the name server to answer.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Record type and class of an Internet TXT query.
+const TYPE_TXT: u16 = 16;
+const CLASS_IN: u16 = 1;
+
+/// Looks up the TXT records of a name.
+#[async_trait]
+pub trait TxtResolver {
+    /// Every TXT record on `name`, each with its strings joined.  A name
+    /// that doesn't exist has none.
+    async fn txt(&self, name: &str) -> io::Result<Vec<String>>;
+}
+
+/// The bootstrap addresses published on `_dnsaddr.<domain>`.  Records that
+/// aren't `dnsaddr=` entries, or don't hold a multiaddr, are skipped.
+pub async fn resolve_bootstrap(
+    resolver: &(impl TxtResolver + Sync),
+    domain: &str,
+) -> io::Result<Vec<Multiaddr>> {
+    let records = resolver.txt(&format!("_dnsaddr.{domain}")).await?;
+    Ok(records
+        .iter()
+        .filter_map(|record| {
+            let addr = record.strip_prefix(DNSADDR_PREFIX)?.parse().ok();
+            if addr.is_none() {
+                debug!("Skipping malformed dnsaddr record {record:?}");
+            }
+            addr
+        })
+        .collect())
+}
+
+/// Queries the system's name server directly over UDP.
+#[derive(Debug, Clone, Copy)]
+pub struct SystemResolver {
+    server: SocketAddr,
+}
+
+impl SystemResolver {
+    pub fn new(server: SocketAddr) -> Self {
+        Self { server }
+    }
+
+    /// The first `nameserver` in `/etc/resolv.conf`, or a resolver on
+    /// localhost if there is none.
+    pub fn from_system_conf() -> Self {
+        let server = std::fs::read_to_string("/etc/resolv.conf")
+            .ok()
+            .and_then(|conf| {
+                conf.lines().find_map(|line| {
+                    let mut words = line.split_whitespace();
+                    match (words.next(), words.next()) {
+                        (Some("nameserver"), Some(ip)) => ip.parse::<IpAddr>().ok(),
+                        _ => None,
+                    }
+                })
+            })
+            .unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST));
+        Self::new(SocketAddr::new(server, 53))
+    }
+}
+
+#[async_trait]
+impl TxtResolver for SystemResolver {
+    async fn txt(&self, name: &str) -> io::Result<Vec<String>> {
+        let local: SocketAddr = match self.server {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (std::net::Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(local).await?;
+        socket.connect(self.server).await?;
+        let id: u16 = rand::random();
+        socket.send(&encode_query(id, name)?).await?;
+
+        let mut buf = vec![0u8; 4096];
+        let len = tokio::time::timeout(QUERY_TIMEOUT, socket.recv(&mut buf))
+            .await
+            .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))??;
+        parse_txt_response(id, &buf[..len])
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// A recursive TXT query for `name` with transaction id `id`.
+fn encode_query(id: u16, name: &str) -> io::Result<Vec<u8>> {
+    let mut query = Vec::with_capacity(18 + name.len());
+    query.extend_from_slice(&id.to_be_bytes());
+    // Recursion desired; one question, no other records.
+    query.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "bad DNS name"));
+        }
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&TYPE_TXT.to_be_bytes());
+    query.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(query)
+}
+
+/// The TXT records in the answer to query `id`.
+fn parse_txt_response(id: u16, msg: &[u8]) -> io::Result<Vec<String>> {
+    let u16_at = |pos: usize| -> io::Result<u16> {
+        msg.get(pos..pos + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+            .ok_or_else(|| invalid("truncated DNS response"))
+    };
+    if u16_at(0)? != id {
+        return Err(invalid("DNS response to another query"));
+    }
+    let flags = u16_at(2)?;
+    if flags & 0x8000 == 0 {
+        return Err(invalid("DNS message is not a response"));
+    }
+    if flags & 0x0200 != 0 {
+        return Err(invalid("DNS response was truncated"));
+    }
+    match flags & 0x000f {
+        0 => {}
+        // No such name: nothing is published there.
+        3 => return Ok(Vec::new()),
+        rcode => return Err(io::Error::other(format!("DNS query failed with rcode {rcode}"))),
+    }
+
+    let mut pos = 12;
+    for _ in 0..u16_at(4)? {
+        pos = skip_name(msg, pos)? + 4;
+    }
+    let mut records = Vec::new();
+    for _ in 0..u16_at(6)? {
+        pos = skip_name(msg, pos)?;
+        let kind = u16_at(pos)?;
+        let len = u16_at(pos + 8)? as usize;
+        let data = msg.get(pos + 10..pos + 10 + len).ok_or_else(|| invalid("truncated record"))?;
+        pos += 10 + len;
+        if kind != TYPE_TXT {
+            continue;
+        }
+        let mut text = Vec::new();
+        let mut rest = data;
+        while let Some((&n, tail)) = rest.split_first() {
+            let chunk = tail.get(..n as usize).ok_or_else(|| invalid("truncated TXT string"))?;
+            text.extend_from_slice(chunk);
+            rest = &tail[n as usize..];
+        }
+        records.push(String::from_utf8_lossy(&text).into_owned());
+    }
+    Ok(records)
+}
+
+/// Position just past the (possibly compressed) name at `pos`.
+fn skip_name(msg: &[u8], mut pos: usize) -> io::Result<usize> {
+    loop {
+        let len = *msg.get(pos).ok_or_else(|| invalid("truncated DNS name"))?;
+        match len {
+            0 => return Ok(pos + 1),
+            // A pointer to a name elsewhere ends this one.
+            _ if len & 0xc0 == 0xc0 => return Ok(pos + 2),
+            _ => pos += 1 + len as usize,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A response to `query` answering with one TXT record per entry.
+    fn response(query: &[u8], records: &[&[&str]]) -> Vec<u8> {
+        let mut msg = query.to_vec();
+        msg[2] |= 0x80;
+        msg[6..8].copy_from_slice(&(records.len() as u16).to_be_bytes());
+        for strings in records {
+            // Name: pointer to the question's name at offset 12.
+            msg.extend_from_slice(&[0xc0, 12]);
+            msg.extend_from_slice(&TYPE_TXT.to_be_bytes());
+            msg.extend_from_slice(&CLASS_IN.to_be_bytes());
+            msg.extend_from_slice(&300u32.to_be_bytes());
+            let len: usize = strings.iter().map(|s| 1 + s.len()).sum();
+            msg.extend_from_slice(&(len as u16).to_be_bytes());
+            for s in *strings {
+                msg.push(s.len() as u8);
+                msg.extend_from_slice(s.as_bytes());
+            }
+        }
+        msg
+    }
+
+    #[test]
+    fn txt_answers_are_read_back() {
+        let query = encode_query(7, "_dnsaddr.example.org").unwrap();
+        assert_eq!(&query[12..22], b"\x08_dnsaddr\x07");
+
+        let msg = response(&query, &[&["dnsaddr=/ip4/1.2.3.4", "/tcp/4001"], &["v=spf1"]]);
+        assert_eq!(
+            parse_txt_response(7, &msg).unwrap(),
+            ["dnsaddr=/ip4/1.2.3.4/tcp/4001", "v=spf1"]
+        );
+        assert!(parse_txt_response(8, &msg).is_err());
+        assert!(parse_txt_response(7, &msg[..msg.len() - 3]).is_err());
+
+        let mut missing = response(&query, &[]);
+        missing[3] |= 3;
+        assert!(parse_txt_response(7, &missing).unwrap().is_empty());
+        assert!(encode_query(1, "bad..name").is_err());
+    }
+}
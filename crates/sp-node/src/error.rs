@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::protocol::WIRE_VERSION;
+
 #[derive(Debug, Error)]
 pub enum NodeError {
     #[error("transport error: {0}")]
@@ -8,18 +10,74 @@ pub enum NodeError {
     #[error("gossipsub error: {0}")]
     Gossipsub(String),
 
+    #[error("kademlia error: {0}")]
+    Kademlia(String),
+
     #[error("serialisation error: {0}")]
     Serialisation(String),
 
     #[error("blockchain error: {0}")]
     Blockchain(#[from] sp_blockchain::BlockchainError),
 
+    #[error("transaction error: {0}")]
+    Transaction(#[from] sp_transaction::TransactionError),
+
+    /// A transaction in a received block failed
+    /// [`Transaction::validate`](sp_transaction::Transaction::validate).
+    #[error("invalid transaction {id}: {source}")]
+    InvalidTransaction {
+        id: uuid::Uuid,
+        source: sp_transaction::TransactionError,
+    },
+
     #[error("sync error: {0}")]
     Sync(#[from] sp_sync::SyncError),
 
     #[error("io error: {0}")]
     Io(#[from] std::io::Error),
 
+    #[error("port of {0} is outside the discovery port range")]
+    PortOutsideRange(libp2p::Multiaddr),
+
+    #[error("no known address for peer {0}")]
+    NoKnownAddress(libp2p::PeerId),
+
+    #[error("no peers to broadcast to on {0}")]
+    NoPeers(String),
+
     #[error("no pending transactions to form a block")]
     NoPendingTransactions,
+
+    #[error("observer nodes do not {0}")]
+    Observer(&'static str),
+
+    /// A [`NodeConfig`](crate::NodeConfig) setting the node can't run with.
+    #[error("invalid configuration: {0}")]
+    InvalidConfig(&'static str),
+}
+
+/// Why [`await_event`](crate::event::await_event) gave up.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AwaitEventError {
+    #[error("no matching node event within {0:?}")]
+    Timeout(std::time::Duration),
+
+    /// The node was dropped before a matching event arrived.
+    #[error("node event channel closed")]
+    Closed,
+}
+
+/// Why bytes from a peer could not be decoded as a protocol message.
+#[derive(Debug, Error)]
+pub enum DecodeError {
+    #[error("empty message")]
+    Empty,
+
+    /// The peer encodes with a different wire format, most likely because it
+    /// runs an incompatible release.
+    #[error("incompatible message version {0} (expected {WIRE_VERSION})")]
+    IncompatibleVersion(u8),
+
+    #[error("malformed message: {0}")]
+    Malformed(#[from] bincode::Error),
 }
@@ -1,9 +1,35 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::Stream;
 use libp2p::{Multiaddr, PeerId};
-use sp_blockchain::Block;
+use sp_blockchain::{Block, BlockHeader};
+use sp_merkle::MerkleProof;
 use sp_transaction::Transaction;
+use tokio::sync::mpsc::{
+    self,
+    error::{SendError, TrySendError},
+};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::error::AwaitEventError;
 
-/// High-level events emitted by a running [`Node`] that callers (e.g. the
-/// TUI) can subscribe to via a channel.
+/// High-level events emitted by a running [`Node`](crate::Node) that callers
+/// (e.g. the TUI) can subscribe to via a channel.
+///
+/// The channel is bounded by `NodeConfig::event_capacity`.  When the
+/// consumer falls behind, events wait in a backlog of the same size that is
+/// drained as the channel frees up.  In the backlog a `PeerDiscovered` for a
+/// peer that is already waiting is merged into the waiting one, and once the
+/// backlog is full the oldest `PeerDiscovered`, `TransactionReceived` or
+/// `Heartbeat` is dropped to make room (the oldest event of any kind if there
+/// is none).
 #[derive(Debug, Clone)]
 pub enum NodeEvent {
     /// A new peer has connected.
@@ -12,24 +38,471 @@ pub enum NodeEvent {
     /// A peer has disconnected.
     PeerDisconnected(PeerId),
 
+    /// A connected peer reported its Identify agent string, e.g.
+    /// `sp-node/0.1.0 genesis/<hex>`.
+    PeerIdentified {
+        peer_id: PeerId,
+        agent_version: String,
+    },
+
+    /// A dial started with [`Node::dial`](crate::Node::dial) or
+    /// [`Node::dial_peer_id`](crate::Node::dial_peer_id) failed to connect.
+    /// `addr` is the dialled address when there was a single one.
+    DialFailed {
+        addr: Option<Multiaddr>,
+        peer_id: Option<PeerId>,
+        error: String,
+    },
+
+    /// [`Node::dial`](crate::Node::dial) was asked for an address whose port
+    /// is outside the discovery port range and dialled it anyway.
+    DialOutsidePortRange(Multiaddr),
+
+    /// The Kademlia bootstrap started by
+    /// [`Node::trigger_discovery`](crate::Node::trigger_discovery) finished.
+    /// `found` counts the peers first reported as [`NodeEvent::PeerDiscovered`]
+    /// while it ran.
+    DiscoveryCompleted { found: usize },
+
     /// A peer was discovered by mDNS or Kademlia but is not yet connected.
+    /// Only sent when something new was learned: `addrs` holds the addresses
+    /// not reported for this peer before.
     PeerDiscovered {
         peer_id: PeerId,
         addrs: Vec<Multiaddr>,
+        source: DiscoverySource,
     },
 
-    /// A new transaction has arrived via gossip.
-    TransactionReceived(Transaction),
+    /// Addresses of a discovered peer were dropped, because they kept failing
+    /// to dial or their mDNS announcement expired.  `addrs` is what is left;
+    /// when it is empty the peer is no longer listed.
+    PeerAddrsPruned {
+        peer_id: PeerId,
+        addrs: Vec<Multiaddr>,
+    },
+
+    /// Every address mDNS reported for a discovered peer expired, so it is no
+    /// longer listed; most likely it left the local network.
+    PeerExpired { peer_id: PeerId },
+
+    /// A peer's announced addresses were found in the DHT (see
+    /// [`Node::find_peer`](crate::Node::find_peer)).
+    PeerResolved {
+        peer_id: PeerId,
+        addrs: Vec<Multiaddr>,
+    },
+
+    /// A new transaction has arrived via gossip.  `origin` is the peer that
+    /// relayed it to us, which need not be its author.
+    TransactionReceived {
+        tx: Transaction,
+        origin: Option<PeerId>,
+    },
 
-    /// A new block has been broadcast by a peer.
-    BlockReceived(Block),
+    /// A gossiped transaction was dropped instead of entering the mempool,
+    /// e.g. because it was already seen or has expired.
+    TransactionRejected { id: Uuid, reason: String },
+
+    /// Pending transactions were dropped from the mempool for being too old
+    /// or to keep it within `max_mempool`.
+    TransactionsEvicted(Vec<Uuid>),
+
+    /// A new block has been broadcast by a peer.  `origin` is the peer that
+    /// relayed it to us, which need not be the one that formed it.
+    BlockReceived {
+        block: Box<Block>,
+        origin: Option<PeerId>,
+    },
+
+    /// A block from a peer was refused, whether gossiped or synced: its index
+    /// is out of range, it does not link to the tip, its merkle root does not
+    /// match its transactions, or (on a full node) one of them is invalid.
+    BlockRejected { block_index: u64, reason: String },
+
+    /// This node sealed pending transactions into a new block.
+    BlockFormed { block_index: u64, tx_count: usize },
+
+    /// A new verification of a block was recorded.  `verifications` is the
+    /// weight gathered so far in full verifications (gossip-mode verifiers
+    /// add nothing); the block is finalised once it reaches `threshold`.
+    BlockVerified {
+        block_index: u64,
+        verifications: usize,
+        threshold: usize,
+    },
 
     /// A block has been verified by enough peers and is now finalised.
-    BlockFinalised { block_index: u64 },
+    /// `tx_count` is how many transactions it carries.
+    BlockFinalised { block_index: u64, tx_count: usize },
+
+    /// Blocks fetched from a peer extended the local chain.
+    ChainSynced {
+        new_length: usize,
+        /// How many blocks this sync added to the local chain (always > 0).
+        blocks_applied: usize,
+    },
+
+    /// Finalised blocks up to and including `up_to_index` were pruned to keep
+    /// the chain within `max_chain_blocks`.
+    ChainPruned { up_to_index: u64 },
+
+    /// An asset requested with [`crate::Node::request_asset`] arrived and
+    /// matched its content hash.
+    AssetReceived { hash: [u8; 32], data: Vec<u8> },
+
+    /// A block header and inclusion proof requested with
+    /// [`crate::Node::request_block_proof`] arrived.  Check it with
+    /// `proof.verify(&header.merkle_root)`.
+    BlockProofReceived {
+        header: BlockHeader,
+        proof: MerkleProof,
+    },
+
+    /// `peer`'s pending transaction ids, requested with
+    /// [`crate::Node::request_mempool`].
+    MempoolReceived { peer: PeerId, ids: Vec<Uuid> },
+
+    /// A message arrived on one of the `NodeConfig::extra_topics`.
+    TopicMessage { topic: String, data: Vec<u8> },
 
-    /// The local chain has been replaced by a longer remote chain.
-    ChainSynced { new_length: usize },
+    /// A peer subscribed to or left a gossip topic; `peers` is how many
+    /// connected peers are now subscribed to it.  A topic with no peers is
+    /// why a broadcast on it reaches no one.
+    TopicPeersChanged { topic: String, peers: usize },
 
     /// The node is now listening on the given address.
     Listening(Multiaddr),
+
+    /// Sent every `NodeConfig::heartbeat_interval` while the node is being
+    /// driven, so a consumer can tell a quiet node from a stalled one.
+    Heartbeat { tip_index: u64, peer_count: usize },
+
+    /// Something a peer sent (or the node tried to do) was rejected.
+    Error(String),
+}
+
+/// Which mechanism reported a [`NodeEvent::PeerDiscovered`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DiscoverySource {
+    /// Multicast DNS on the local network.
+    Mdns,
+    /// The Kademlia routing table (default).
+    #[default]
+    Kademlia,
+}
+
+impl fmt::Display for DiscoverySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Mdns => "mDNS",
+            Self::Kademlia => "Kademlia",
+        })
+    }
+}
+
+impl NodeEvent {
+    /// A [`NodeEvent::PeerDiscovered`] from the default [`DiscoverySource`],
+    /// for code written before the event named its source.
+    pub fn peer_discovered(peer_id: PeerId, addrs: Vec<Multiaddr>) -> Self {
+        Self::PeerDiscovered { peer_id, addrs, source: DiscoverySource::default() }
+    }
+
+    /// Frequent, individually unimportant events that may be dropped when
+    /// the consumer can't keep up.
+    fn is_droppable(&self) -> bool {
+        matches!(
+            self,
+            Self::PeerDiscovered { .. } | Self::TransactionReceived { .. } | Self::Heartbeat { .. }
+        )
+    }
+}
+
+/// The receiver returned by [`Node::new`](crate::Node::new) as a
+/// [`Stream`] of [`NodeEvent`]s, for use with `futures::StreamExt`
+/// combinators.  The stream ends once the node is dropped.
+#[derive(Debug)]
+pub struct EventStream {
+    rx: mpsc::Receiver<NodeEvent>,
+}
+
+impl EventStream {
+    pub fn new(rx: mpsc::Receiver<NodeEvent>) -> Self {
+        Self { rx }
+    }
+
+    /// Unwrap the raw receiver.
+    pub fn into_inner(self) -> mpsc::Receiver<NodeEvent> {
+        self.rx
+    }
+}
+
+impl From<mpsc::Receiver<NodeEvent>> for EventStream {
+    fn from(rx: mpsc::Receiver<NodeEvent>) -> Self {
+        Self::new(rx)
+    }
+}
+
+impl Stream for EventStream {
+    type Item = NodeEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<NodeEvent>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+/// Wait for the first event from `rx` that satisfies `predicate`, skipping
+/// the rest, for at most `timeout`.
+///
+/// Nothing arrives unless the node is being driven, so run it in another
+/// task (or `select!` over [`Node::run`](crate::Node::run)) while waiting.
+pub async fn await_event(
+    rx: &mut mpsc::Receiver<NodeEvent>,
+    mut predicate: impl FnMut(&NodeEvent) -> bool,
+    timeout: Duration,
+) -> Result<NodeEvent, AwaitEventError> {
+    let wait = async {
+        while let Some(event) = rx.recv().await {
+            if predicate(&event) {
+                return Ok(event);
+            }
+        }
+        Err(AwaitEventError::Closed)
+    };
+    tokio::time::timeout(timeout, wait)
+        .await
+        .unwrap_or(Err(AwaitEventError::Timeout(timeout)))
+}
+
+/// Sending half of the node's event channel, applying the overflow policy
+/// described on [`NodeEvent`].
+pub(crate) struct EventSender {
+    tx: mpsc::Sender<NodeEvent>,
+    backlog: VecDeque<NodeEvent>,
+    capacity: usize,
+    dropped: u64,
+}
+
+impl EventSender {
+    /// A bounded event channel holding up to `capacity` events, plus as many
+    /// again in the sender's backlog.
+    pub(crate) fn channel(capacity: usize) -> (Self, mpsc::Receiver<NodeEvent>) {
+        let capacity = capacity.max(1);
+        let (tx, rx) = mpsc::channel(capacity);
+        let sender = Self {
+            tx,
+            backlog: VecDeque::new(),
+            capacity,
+            dropped: 0,
+        };
+        (sender, rx)
+    }
+
+    /// Queue `event` without waiting.  Fails only once the receiver is gone.
+    pub(crate) fn send(&mut self, event: NodeEvent) -> Result<(), SendError<()>> {
+        self.flush();
+        if self.tx.is_closed() {
+            self.backlog.clear();
+            return Err(SendError(()));
+        }
+        if self.backlog.is_empty() {
+            match self.tx.try_send(event) {
+                Ok(()) => return Ok(()),
+                Err(TrySendError::Full(event)) => self.enqueue(event),
+                Err(TrySendError::Closed(_)) => return Err(SendError(())),
+            }
+        } else {
+            self.enqueue(event);
+        }
+        Ok(())
+    }
+
+    /// True while events are waiting for room in the channel.
+    pub(crate) fn has_backlog(&self) -> bool {
+        !self.backlog.is_empty()
+    }
+
+    /// Wait for room in the channel and move the oldest waiting event into
+    /// it.  Meant for the node's event loop, guarded by
+    /// [`EventSender::has_backlog`].
+    pub(crate) async fn drain_one(&mut self) {
+        if let Ok(permit) = self.tx.reserve().await {
+            if let Some(event) = self.backlog.pop_front() {
+                permit.send(event);
+            }
+        } else {
+            self.backlog.clear();
+        }
+    }
+
+    /// Events dropped so far because the consumer fell behind.
+    pub(crate) fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Move waiting events into the channel while it has room.
+    fn flush(&mut self) {
+        while let Some(event) = self.backlog.pop_front() {
+            match self.tx.try_send(event) {
+                Ok(()) => {}
+                Err(TrySendError::Full(event)) => {
+                    self.backlog.push_front(event);
+                    break;
+                }
+                Err(TrySendError::Closed(_)) => self.backlog.clear(),
+            }
+        }
+    }
+
+    fn enqueue(&mut self, event: NodeEvent) {
+        if let NodeEvent::PeerDiscovered { peer_id, addrs, source } = &event {
+            let waiting = self.backlog.iter_mut().find_map(|queued| match queued {
+                NodeEvent::PeerDiscovered {
+                    peer_id: queued_id,
+                    addrs: queued_addrs,
+                    source: queued_source,
+                } if queued_id == peer_id && queued_source == source => Some(queued_addrs),
+                _ => None,
+            });
+            if let Some(queued_addrs) = waiting {
+                for addr in addrs {
+                    if !queued_addrs.contains(addr) {
+                        queued_addrs.push(addr.clone());
+                    }
+                }
+                return;
+            }
+        }
+
+        self.backlog.push_back(event);
+        if self.backlog.len() > self.capacity {
+            let victim = self
+                .backlog
+                .iter()
+                .position(NodeEvent::is_droppable)
+                .unwrap_or(0);
+            self.backlog.remove(victim);
+            self.dropped += 1;
+            warn!("Event consumer is behind; dropped {} events so far", self.dropped);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn discovered(peer_id: PeerId, port: u16) -> NodeEvent {
+        let addr = format!("/ip4/127.0.0.1/tcp/{port}").parse().unwrap();
+        NodeEvent::PeerDiscovered { peer_id, addrs: vec![addr], source: DiscoverySource::Mdns }
+    }
+
+    #[test]
+    fn peer_discovered_is_built_without_a_source() {
+        let peer = PeerId::random();
+        let NodeEvent::PeerDiscovered { peer_id, addrs, source } =
+            NodeEvent::peer_discovered(peer, Vec::new())
+        else {
+            panic!("not a discovery");
+        };
+        assert_eq!((peer_id, addrs, source), (peer, Vec::new(), DiscoverySource::Kademlia));
+    }
+
+    #[test]
+    fn full_channel_coalesces_and_drops_instead_of_growing() {
+        let (mut tx, mut rx) = EventSender::channel(2);
+        let chatty = PeerId::random();
+
+        tx.send(NodeEvent::Listening("/ip4/127.0.0.1/tcp/1".parse().unwrap())).unwrap();
+        tx.send(NodeEvent::BlockFinalised { block_index: 1, tx_count: 0 }).unwrap();
+        // The channel is full; repeated discoveries of one peer share a slot.
+        for port in 10..20 {
+            tx.send(discovered(chatty, port)).unwrap();
+        }
+        assert_eq!(tx.backlog.len(), 1);
+
+        // More distinct events than fit push out the droppable ones first.
+        tx.send(NodeEvent::BlockFinalised { block_index: 2, tx_count: 0 }).unwrap();
+        tx.send(discovered(PeerId::random(), 30)).unwrap();
+        tx.send(NodeEvent::BlockFinalised { block_index: 3, tx_count: 0 }).unwrap();
+        assert_eq!(tx.backlog.len(), 2);
+        assert_eq!(tx.dropped(), 2);
+
+        // Receiving makes room and the backlog drains in order.
+        let mut received = Vec::new();
+        while let Ok(ev) = rx.try_recv() {
+            received.push(ev);
+            tx.flush();
+        }
+        let finalised: Vec<u64> = received
+            .iter()
+            .filter_map(|ev| match ev {
+                NodeEvent::BlockFinalised { block_index, .. } => Some(*block_index),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(received.len(), 4);
+        assert_eq!(finalised, vec![1, 2, 3]);
+        assert!(!tx.has_backlog());
+    }
+
+    #[tokio::test]
+    async fn stream_adapter_yields_events_until_the_sender_is_gone() {
+        use futures::StreamExt;
+
+        let (mut tx, rx) = EventSender::channel(4);
+        tx.send(NodeEvent::Listening("/ip4/127.0.0.1/tcp/1".parse().unwrap())).unwrap();
+        tx.send(NodeEvent::BlockFinalised { block_index: 1, tx_count: 0 }).unwrap();
+        tx.send(NodeEvent::BlockFinalised { block_index: 2, tx_count: 0 }).unwrap();
+        drop(tx);
+
+        let finalised: Vec<u64> = EventStream::from(rx)
+            .filter_map(|ev| async move {
+                match ev {
+                    NodeEvent::BlockFinalised { block_index, .. } => Some(block_index),
+                    _ => None,
+                }
+            })
+            .collect()
+            .await;
+        assert_eq!(finalised, vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn await_event_skips_to_a_match_or_says_why_not() {
+        let is_block_2 =
+            |ev: &NodeEvent| matches!(ev, NodeEvent::BlockFinalised { block_index: 2, .. });
+        let wait = Duration::from_millis(50);
+        let (mut tx, mut rx) = EventSender::channel(4);
+        tx.send(NodeEvent::BlockFinalised { block_index: 1, tx_count: 0 }).unwrap();
+        tx.send(NodeEvent::BlockFinalised { block_index: 2, tx_count: 0 }).unwrap();
+
+        let found = await_event(&mut rx, is_block_2, wait).await.unwrap();
+        assert!(is_block_2(&found));
+        assert_eq!(
+            await_event(&mut rx, is_block_2, wait).await.unwrap_err(),
+            AwaitEventError::Timeout(wait)
+        );
+        drop(tx);
+        assert_eq!(
+            await_event(&mut rx, is_block_2, wait).await.unwrap_err(),
+            AwaitEventError::Closed
+        );
+    }
+
+    #[test]
+    fn coalesced_discovery_keeps_every_address() {
+        let (mut tx, mut rx) = EventSender::channel(1);
+        let peer = PeerId::random();
+        tx.send(NodeEvent::BlockFinalised { block_index: 1, tx_count: 0 }).unwrap();
+        tx.send(discovered(peer, 1)).unwrap();
+        tx.send(discovered(peer, 2)).unwrap();
+        tx.send(discovered(peer, 1)).unwrap();
+
+        rx.try_recv().unwrap();
+        tx.flush();
+        let Ok(NodeEvent::PeerDiscovered { addrs, .. }) = rx.try_recv() else {
+            panic!("expected the coalesced discovery");
+        };
+        assert_eq!(addrs.len(), 2);
+    }
 }
@@ -0,0 +1,173 @@
+//! Optional HTTP health endpoint for process supervisors such as systemd or
+//! Kubernetes, enabled with `NodeConfig::health_addr`.
+//!
+//! `GET /live` answers `200` once the node listens on an address.  `GET
+//! /ready` (also `/health` and `/`) answers `200` only once it has a peer as
+//! well.  Both answer `503` otherwise, with a small JSON body giving the
+//! figures either way.
+
+use std::{io, net::SocketAddr, time::Duration};
+
+use serde::Serialize;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::watch,
+};
+use tracing::{debug, warn};
+
+use crate::node::NodeStatusSnapshot;
+
+/// Longest request head read before answering.
+const MAX_REQUEST_HEAD: usize = 8 * 1024;
+
+/// How long a client may take to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What the health endpoint reports, taken from a [`NodeStatusSnapshot`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Health {
+    /// The node listens on at least one address.
+    pub listening: bool,
+    pub peers: usize,
+}
+
+impl Health {
+    pub fn from_status(status: &NodeStatusSnapshot) -> Self {
+        Self {
+            listening: !status.listen_addrs.is_empty(),
+            peers: status.connected_peers,
+        }
+    }
+
+    /// Up and reachable, whether or not anyone is connected.
+    pub fn is_live(&self) -> bool {
+        self.listening
+    }
+
+    /// Live, with at least one peer to exchange blocks with.
+    pub fn is_ready(&self) -> bool {
+        self.listening && self.peers > 0
+    }
+}
+
+/// Bind the endpoint on `addr`.
+pub(crate) async fn bind(addr: SocketAddr) -> io::Result<TcpListener> {
+    TcpListener::bind(addr).await
+}
+
+/// Answer requests on `listener` from the latest value in `health` until
+/// the node drops its sender.
+pub(crate) fn serve(listener: TcpListener, mut health: watch::Receiver<Health>) {
+    tokio::spawn(async move {
+        loop {
+            let stream = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        warn!("Health endpoint failed to accept: {e}");
+                        continue;
+                    }
+                },
+                changed = health.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    continue;
+                }
+            };
+            let current = *health.borrow();
+            tokio::spawn(async move {
+                if let Err(e) = respond(stream, current).await {
+                    debug!("Health request failed: {e}");
+                }
+            });
+        }
+    });
+}
+
+async fn respond(mut stream: TcpStream, health: Health) -> io::Result<()> {
+    let head = tokio::time::timeout(REQUEST_TIMEOUT, read_head(&mut stream))
+        .await
+        .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))??;
+    let (status, body) = match request_path(&head) {
+        Some(path) => response_for(path, health),
+        None => (400, String::new()),
+    };
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        _ => "Service Unavailable",
+    };
+    let response = format!(
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Read up to the blank line ending the request head.
+async fn read_head(stream: &mut TcpStream) -> io::Result<String> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") && head.len() < MAX_REQUEST_HEAD {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+    Ok(String::from_utf8_lossy(&head).into_owned())
+}
+
+/// The path of a `GET` request line.
+fn request_path(head: &str) -> Option<&str> {
+    let mut parts = head.lines().next()?.split_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some("GET"), Some(path)) => Some(path),
+        _ => None,
+    }
+}
+
+/// Status code and JSON body for `path`.
+fn response_for(path: &str, health: Health) -> (u16, String) {
+    let ok = match path {
+        "/live" => health.is_live(),
+        "/ready" | "/health" | "/" => health.is_ready(),
+        _ => return (404, String::new()),
+    };
+    #[derive(Serialize)]
+    struct Body {
+        live: bool,
+        ready: bool,
+        peers: usize,
+    }
+    let body = Body { live: health.is_live(), ready: health.is_ready(), peers: health.peers };
+    let body = serde_json::to_string(&body).unwrap_or_default();
+    (if ok { 200 } else { 503 }, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn readiness_needs_a_peer_and_liveness_does_not() {
+        let peerless = Health { listening: true, peers: 0 };
+        assert_eq!(response_for("/live", peerless).0, 200);
+        assert_eq!(response_for("/ready", peerless).0, 503);
+
+        let connected = Health { listening: true, peers: 2 };
+        let (status, body) = response_for("/health", connected);
+        assert_eq!(status, 200);
+        assert_eq!(body, r#"{"live":true,"ready":true,"peers":2}"#);
+
+        assert_eq!(response_for("/live", Health::default()).0, 503);
+        assert_eq!(response_for("/metrics", connected).0, 404);
+        assert_eq!(request_path("GET /ready HTTP/1.1\r\n\r\n"), Some("/ready"));
+        assert_eq!(request_path("POST /ready HTTP/1.1\r\n\r\n"), None);
+    }
+}
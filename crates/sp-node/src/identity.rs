@@ -0,0 +1,44 @@
+//! On-disk node identity.
+//!
+//! The keypair is generated on first start and read back afterwards, so a
+//! node keeps the same peer id across restarts and the records other peers
+//! hold about it stay valid.
+
+use std::{fs, io, path::Path};
+
+use libp2p::identity::Keypair;
+
+/// File name of the keypair inside the node's data directory.
+pub const IDENTITY_FILE: &str = "identity.key";
+
+/// Read the keypair at `path`, generating and saving a new ed25519 one if
+/// the file does not exist yet.  A file that exists but does not decode is
+/// an error rather than silently replaced.
+pub fn load_or_generate(path: &Path) -> io::Result<Keypair> {
+    match fs::read(path) {
+        Ok(bytes) => Keypair::from_protobuf_encoding(&bytes)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let keypair = Keypair::generate_ed25519();
+            save(path, &keypair)?;
+            Ok(keypair)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Write `keypair` to `path`, creating its directory if needed.  On Unix the
+/// file is only readable by its owner.
+pub fn save(path: &Path, keypair: &Keypair) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let bytes = keypair.to_protobuf_encoding().map_err(io::Error::other)?;
+    fs::write(path, bytes)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(())
+}
@@ -1,13 +1,22 @@
 pub mod behaviour;
+pub mod chain;
 pub mod config;
+pub mod dnsaddr;
 pub mod error;
 pub mod event;
+pub mod health;
+pub mod identity;
 pub mod mode;
 pub mod node;
 pub mod protocol;
+pub mod proxy;
+pub mod routing;
+pub mod throughput;
+pub mod verified;
 
-pub use config::{DiscoveryMode, NodeConfig};
-pub use error::NodeError;
-pub use event::NodeEvent;
+pub use config::{default_data_dir, AutoConnectPolicy, DiscoveryMode, NodeConfig};
+pub use error::{AwaitEventError, DecodeError, NodeError};
+pub use event::{await_event, DiscoverySource, EventStream, NodeEvent};
 pub use mode::NodeMode;
-pub use node::Node;
+pub use node::{ConnectedPeer, Node, NodeStatusSnapshot};
+pub use throughput::Throughput;
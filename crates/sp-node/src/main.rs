@@ -1,7 +1,14 @@
-use std::time::Duration;
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use clap::{Parser, ValueEnum};
-use sp_node::{DiscoveryMode, Node, NodeConfig, NodeMode};
+use clap::{Parser, Subcommand, ValueEnum};
+use sp_blockchain::Blockchain;
+use sp_node::{
+    chain, protocol::VersionInfo, AutoConnectPolicy, DiscoveryMode, Node, NodeConfig, NodeMode,
+};
 use sp_sync::SyncStrategy;
 use tracing::info;
 use tracing_subscriber::EnvFilter;
@@ -40,13 +47,155 @@ struct Cli {
     #[arg(long, env = "SPN_DISCOVERY_PORT_MAX")]
     discovery_port_max: Option<u16>,
 
+    /// Refuse manual dials outside the discovery port range instead of
+    /// warning about them.
+    #[arg(long, default_value_t = false, env = "SPN_STRICT_PORT_RANGE")]
+    strict_port_range: bool,
+
+    /// Which discovered peers to dial: `all`, `none`, or a connection count
+    /// to stop at.
+    #[arg(long, default_value = "none", value_parser = parse_auto_connect, env = "SPN_AUTO_CONNECT")]
+    auto_connect: AutoConnectPolicy,
+
     /// How often (in seconds) to re-run peer discovery. Default: 60.
     #[arg(long, default_value_t = 60, env = "SPN_DISCOVERY_INTERVAL")]
     discovery_interval: u64,
 
+    /// Seconds between mDNS queries on the local network.
+    #[arg(long, default_value_t = 300, env = "SPN_MDNS_QUERY_INTERVAL")]
+    mdns_query_interval: u64,
+
+    /// Seconds an mDNS-discovered address stays valid without being seen again.
+    #[arg(long, default_value_t = 360, env = "SPN_MDNS_TTL")]
+    mdns_ttl: u64,
+
+    /// Close connections idle for this many seconds. Omit for the libp2p default.
+    #[arg(long, env = "SPN_IDLE_TIMEOUT")]
+    idle_timeout: Option<u64>,
+
+    /// Largest gossip message in bytes to send or accept.
+    #[arg(long, default_value_t = 1024 * 1024, env = "SPN_MAX_MESSAGE_SIZE")]
+    max_message_size: usize,
+
+    /// Most transactions to seal into one block.
+    #[arg(long, default_value_t = 1000, env = "SPN_MAX_BLOCK_TRANSACTIONS")]
+    max_block_transactions: usize,
+
+    /// Most blocks to keep; older finalised ones are pruned [default: all].
+    #[arg(long, env = "SPN_MAX_CHAIN_BLOCKS")]
+    max_chain_blocks: Option<usize>,
+
+    /// Most pending transactions to hold before evicting the oldest.
+    #[arg(long, default_value_t = 10_000, env = "SPN_MAX_MEMPOOL")]
+    max_mempool: usize,
+
+    /// Seconds a pending transaction may wait for a block before eviction.
+    #[arg(long, default_value_t = 3600, env = "SPN_MEMPOOL_TTL")]
+    mempool_ttl: u64,
+
+    /// Seconds to wait for a peer to answer a sync request.
+    #[arg(long, default_value_t = 30, env = "SPN_REQUEST_TIMEOUT")]
+    request_timeout: u64,
+
+    /// Largest sync response in bytes to accept from a peer.
+    #[arg(long, default_value_t = 16 * 1024 * 1024, env = "SPN_MAX_RESPONSE_SIZE")]
+    max_response_size: usize,
+
+    /// Seconds between heartbeat events.
+    #[arg(
+        long,
+        default_value_t = 5,
+        value_parser = clap::value_parser!(u64).range(1..),
+        env = "SPN_HEARTBEAT_INTERVAL"
+    )]
+    heartbeat_interval: u64,
+
+    /// Undelivered events to hold before dropping high-volume ones.
+    #[arg(long, default_value_t = 1024, env = "SPN_EVENT_CAPACITY")]
+    event_capacity: usize,
+
+    /// SOCKS5 proxy to make every outbound connection through, e.g. Tor at
+    /// 127.0.0.1:9050.  The node then listens on loopback only.
+    #[arg(long, env = "SPN_PROXY")]
+    proxy: Option<SocketAddr>,
+
+    /// Serve HTTP liveness (/live) and readiness (/ready) checks on this
+    /// address, e.g. 127.0.0.1:8080.
+    #[arg(long, env = "SPN_HEALTH_ADDR")]
+    health_addr: Option<SocketAddr>,
+
+    /// Address to announce besides the listen addresses, such as an onion
+    /// address (repeatable).
+    #[arg(long = "external-addr", env = "SPN_EXTERNAL_ADDRS", value_delimiter = ',')]
+    external_addrs: Vec<libp2p::Multiaddr>,
+
+    /// Peer to dial on startup (repeatable).
+    #[arg(long = "bootstrap", env = "SPN_BOOTSTRAP_PEERS", value_delimiter = ',')]
+    bootstrap_peers: Vec<libp2p::Multiaddr>,
+
+    /// Domain whose _dnsaddr TXT records list more bootstrap peers.
+    #[arg(long, env = "SPN_BOOTSTRAP_DNS")]
+    bootstrap_dns: Option<String>,
+
+    /// Network to join; nodes on different networks have different genesis
+    /// blocks and refuse each other.
+    #[arg(long, default_value = sp_blockchain::DEFAULT_NETWORK_ID, env = "SPN_NETWORK_ID")]
+    network_id: String,
+
+    /// Extra gossip topic to subscribe to and relay (repeatable).
+    #[arg(long = "extra-topic", env = "SPN_EXTRA_TOPICS", value_delimiter = ',')]
+    extra_topics: Vec<String>,
+
+    /// Agent name advertised to peers [default: sp-node/<version>].
+    #[arg(long, env = "SPN_AGENT_VERSION")]
+    agent_version: Option<String>,
+
+    /// Tag stored in every block this node forms, e.g. a region or operator.
+    #[arg(long, env = "SPN_BLOCK_METADATA")]
+    block_metadata: Option<String>,
+
+    /// Network key (64 hex characters) to encrypt broadcast payloads with.
+    #[arg(long, env = "SPN_PAYLOAD_KEY", hide_env_values = true)]
+    payload_key: Option<sp_transaction::PayloadKey>,
+
+    /// Directory for the chain, identity and other persistent state
+    /// [default: $XDG_DATA_HOME/spn].
+    #[arg(long, global = true, env = "SPN_DATA_DIR")]
+    data_dir: Option<PathBuf>,
+
     /// Suppress log output to stderr (run silently).
     #[arg(short, long, default_value_t = false, env = "SPN_QUIET")]
     quiet: bool,
+
+    /// Form blocks locally without publishing anything to the network.
+    #[arg(long, default_value_t = false, env = "SPN_DRY_RUN")]
+    dry_run: bool,
+
+    /// Seal pending transactions into a final block when the node stops.
+    #[arg(long, default_value_t = false, env = "SPN_SEAL_ON_SHUTDOWN")]
+    seal_on_shutdown: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Maintenance commands.  Without one, the node runs.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the stored chain as JSON.
+    DumpChain {
+        /// Write the JSON to this file instead of stdout.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Replace the stored chain with one exported by `dump-chain`.
+    ImportChain {
+        /// JSON file to import.
+        input: PathBuf,
+    },
+    /// Print the protocol version, network id and genesis hash peers must
+    /// share with this node.
+    VersionInfo,
 }
 
 #[derive(ValueEnum, Debug, Clone)]
@@ -55,6 +204,8 @@ enum CliMode {
     Full,
     /// Gossip-only: relays messages without storing assets or verifying blocks.
     Gossip,
+    /// Read-only: syncs and serves the chain but never originates anything.
+    Observer,
 }
 
 #[derive(ValueEnum, Debug, Clone)]
@@ -76,9 +227,25 @@ enum CliSync {
     All,
 }
 
+fn parse_auto_connect(s: &str) -> Result<AutoConnectPolicy, String> {
+    match s {
+        "all" => Ok(AutoConnectPolicy::All),
+        "none" => Ok(AutoConnectPolicy::None),
+        n => n
+            .parse()
+            .map(AutoConnectPolicy::UpTo)
+            .map_err(|_| format!("expected `all`, `none` or a number, got `{n}`")),
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
+    let data_dir = cli.data_dir.unwrap_or_else(sp_node::default_data_dir);
+
+    if let Some(command) = cli.command {
+        return run_command(command, &data_dir, &cli.network_id);
+    }
 
     let log_filter = if cli.quiet {
         EnvFilter::new("off")
@@ -99,6 +266,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         mode: match cli.mode {
             CliMode::Full => NodeMode::Full,
             CliMode::Gossip => NodeMode::Gossip,
+            CliMode::Observer => NodeMode::Observer,
         },
         discovery_mode: match cli.discovery {
             CliDiscovery::Kademlia => DiscoveryMode::KademliaDht,
@@ -109,8 +277,35 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             CliSync::OnDemand => SyncStrategy::OnDemand,
             CliSync::All => SyncStrategy::OnDemand,
         },
+        auto_connect: cli.auto_connect,
+        mdns_query_interval: Duration::from_secs(cli.mdns_query_interval),
+        mdns_ttl: Duration::from_secs(cli.mdns_ttl),
         discovery_port_range,
+        strict_port_range: cli.strict_port_range,
         quiet: cli.quiet,
+        dry_run: cli.dry_run,
+        seal_on_shutdown: cli.seal_on_shutdown,
+        idle_timeout: cli.idle_timeout.map(Duration::from_secs),
+        max_message_size: cli.max_message_size,
+        max_block_transactions: cli.max_block_transactions,
+        max_chain_blocks: cli.max_chain_blocks,
+        max_mempool: cli.max_mempool,
+        mempool_ttl: Duration::from_secs(cli.mempool_ttl),
+        request_timeout: Duration::from_secs(cli.request_timeout),
+        max_response_size: cli.max_response_size,
+        heartbeat_interval: Duration::from_secs(cli.heartbeat_interval),
+        event_capacity: cli.event_capacity,
+        proxy: cli.proxy,
+        health_addr: cli.health_addr,
+        external_addrs: cli.external_addrs,
+        bootstrap_peers: cli.bootstrap_peers,
+        bootstrap_dns: cli.bootstrap_dns,
+        network_id: cli.network_id,
+        extra_topics: cli.extra_topics,
+        agent_version: cli.agent_version,
+        block_metadata: cli.block_metadata.map(String::into_bytes),
+        payload_key: cli.payload_key,
+        data_dir: Some(data_dir),
     };
 
     let discovery_interval = Duration::from_secs(cli.discovery_interval);
@@ -134,7 +329,38 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     });
 
     // Auto-discover on startup and repeat every `discovery_interval`.
-    node.run_with_periodic_discovery(discovery_interval).await;
+    tokio::select! {
+        _ = node.run_with_periodic_discovery(discovery_interval) => {}
+        _ = tokio::signal::ctrl_c() => info!("Shutting down"),
+    }
+    node.shutdown().await?;
 
     Ok(())
 }
+
+/// Run a maintenance subcommand against the chain stored in `data_dir`.
+fn run_command(
+    command: Command,
+    data_dir: &Path,
+    network_id: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let chain_path = data_dir.join(chain::CHAIN_FILE);
+
+    match command {
+        Command::DumpChain { output } => {
+            let blockchain = chain::load(&chain_path)?.unwrap_or_default();
+            let json = blockchain.to_json()?;
+            match output {
+                Some(path) => std::fs::write(path, json)?,
+                None => println!("{json}"),
+            }
+        }
+        Command::ImportChain { input } => {
+            let blockchain = Blockchain::from_json(&std::fs::read_to_string(input)?)?;
+            chain::save(&chain_path, &blockchain)?;
+            println!("Imported {} blocks into {}", blockchain.len(), chain_path.display());
+        }
+        Command::VersionInfo => println!("{}", VersionInfo::for_network(network_id)),
+    }
+    Ok(())
+}
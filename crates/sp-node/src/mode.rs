@@ -1,5 +1,8 @@
+use serde::{Deserialize, Serialize};
+use sp_blockchain::FULL_VERIFICATION_WEIGHT;
+
 /// Controls how much work a node does on behalf of the network.
-#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
 pub enum NodeMode {
     /// Full participant: validates blocks, stores assets, contributes
     /// verifications so that blocks can be finalised.
@@ -10,4 +13,21 @@ pub enum NodeMode {
     /// does not store assets and does not send verification messages.
     /// Useful for lightweight mobile/IoT deployments.
     Gossip,
+
+    /// Read-only: receives, validates, stores and serves the chain like a
+    /// full node, but never originates transactions, verifications or
+    /// blocks.
+    Observer,
+}
+
+impl NodeMode {
+    /// How much a verification from a node in this mode counts toward
+    /// finalising a block.  Gossip nodes don't validate blocks, so their
+    /// verifications carry no weight.
+    pub fn verification_weight(&self) -> u32 {
+        match self {
+            NodeMode::Full => FULL_VERIFICATION_WEIGHT,
+            NodeMode::Gossip | NodeMode::Observer => 0,
+        }
+    }
 }
@@ -1,34 +1,113 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    net::SocketAddr,
+};
 
 use futures::StreamExt;
 use libp2p::{
-    gossipsub::IdentTopic,
-    request_response::Message as RrMessage,
-    swarm::SwarmEvent,
+    gossipsub::{IdentTopic, TopicHash},
+    kad::{self, GetRecordOk, PeerRecord, QueryId, QueryResult, Quorum, Record},
+    request_response::{
+        self, InboundFailure, Message as RrMessage, OutboundFailure, OutboundRequestId,
+    },
+    swarm::{
+        dial_opts::{DialOpts, PeerCondition},
+        ConnectionId, DialError, SwarmEvent,
+    },
     Multiaddr, PeerId, Swarm,
 };
-use sp_blockchain::Blockchain;
-use sp_sync::SyncManager;
-use sp_transaction::Transaction;
-use tokio::sync::mpsc;
+use sp_blockchain::{
+    Block, Blockchain, BlockchainError, FULL_VERIFICATION_WEIGHT, MIN_VERIFICATIONS,
+};
+use sp_merkle::{content_hash, AssetStore, MerkleProof};
+use sp_sync::{SyncManager, SyncStrategy};
+use sp_transaction::{PayloadKey, Transaction, TransactionType};
+use uuid::Uuid;
+use tokio::sync::{mpsc, watch};
 use tracing::{debug, info, warn};
 
 use crate::{
     behaviour::{build_behaviour, SpBehaviour, SpBehaviourEvent},
-    config::{DiscoveryMode, NodeConfig},
-    error::NodeError,
-    event::NodeEvent,
+    config::{AutoConnectPolicy, DiscoveryMode, NodeConfig},
+    dnsaddr::{self, SystemResolver, TxtResolver},
+    error::{DecodeError, NodeError},
+    event::{DiscoverySource, EventSender, NodeEvent},
+    health::{self, Health},
     mode::NodeMode,
+    chain, identity, routing, verified,
     protocol::{
-        decode_gossip, decode_request, encode_gossip, encode_response, GossipMessage, SyncRequest,
-        SyncResponse, TOPIC_BLOCK, TOPIC_TX, TOPIC_VERIFY,
+        decode_addresses, decode_gossip, decode_request, encode_addresses, encode_gossip,
+        encode_response, genesis_from_agent, peer_record_key, sign_verification,
+        verification_signer, GossipMessage, SyncRequest, SyncResponse, BUILTIN_TOPICS,
+        TOPIC_BLOCK, TOPIC_TX, TOPIC_VERIFY, WIRE_VERSION,
     },
+    proxy::Socks5Transport,
+    throughput::Throughput,
 };
 
 /// Maximum number of pending transactions before they are automatically batched
 /// into a new block.
 const BLOCK_BATCH_SIZE: usize = 10;
 
+/// How far ahead of the local tip a gossiped block may be before it is treated
+/// as bogus rather than buffered as an orphan.
+const MAX_ORPHAN_DISTANCE: u64 = 64;
+
+/// How often the node re-announces its listen addresses in the DHT.
+const ANNOUNCE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(300);
+
+/// Failed dials to one address of a discovered peer before the address is
+/// dropped as stale.
+const MAX_ADDR_FAILURES: u32 = 3;
+
+/// How long after auto-dialling a peer discovery leaves it alone, so each
+/// periodic scan only dials peers it has not tried lately.
+const AUTO_DIAL_COOLDOWN: std::time::Duration = std::time::Duration::from_secs(600);
+
+/// How long [`Node::shutdown`] keeps the swarm running so blocks sealed on
+/// shutdown reach the connected peers.
+const SHUTDOWN_FLUSH: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// The transport a node's swarm runs over.
+enum Wire {
+    /// TCP on `NodeConfig::port`.
+    Tcp,
+    /// libp2p's in-process memory transport.
+    #[cfg(test)]
+    Memory,
+}
+
+/// A connected peer: its known addresses, when the current connection was
+/// established and the agent string it reported through Identify.
+#[derive(Debug, Clone)]
+pub struct ConnectedPeer {
+    pub addrs: Vec<Multiaddr>,
+    pub since: std::time::Instant,
+    pub agent_version: Option<String>,
+}
+
+/// The node's state at one instant, as returned by [`Node::status`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeStatusSnapshot {
+    pub peer_id: PeerId,
+    pub listen_addrs: Vec<Multiaddr>,
+    pub connected_peers: usize,
+    /// Discovered peers, whether or not they are connected too.
+    pub discovered_peers: usize,
+    pub chain_length: usize,
+    pub tip_hash: [u8; 32],
+    /// Index of the highest finalised block, if any is.
+    pub last_finalised: Option<u64>,
+    pub pending_txs: usize,
+    pub mode: NodeMode,
+    pub sync_strategy: SyncStrategy,
+    pub discovery_mode: DiscoveryMode,
+    pub network_id: String,
+    pub genesis_hash: [u8; 32],
+    /// Transactions finalised since the node started, by type.
+    pub finalised_by_type: HashMap<TransactionType, u64>,
+}
+
 /// The Social Production P2P node.
 ///
 /// Wraps a libp2p [`Swarm`] and exposes a simple async API for:
@@ -39,75 +118,334 @@ const BLOCK_BATCH_SIZE: usize = 10;
 /// - Peer management (connect, disconnect, list discovered/connected)
 pub struct Node {
     swarm: Swarm<SpBehaviour>,
+    /// Signs this node's block verifications.
+    keypair: libp2p::identity::Keypair,
     local_peer_id: PeerId,
     mode: NodeMode,
+    /// The network this node belongs to; see `NodeConfig::network_id`.
+    network_id: String,
     blockchain: Blockchain,
+    /// Ids of the transactions in the blocks `blockchain` holds, so a replay
+    /// is spotted without scanning the chain.
+    chain_tx_ids: HashSet<Uuid>,
     sync_manager: SyncManager,
+    /// Outstanding chain-tip and block requests, so a failed one can be
+    /// retried with another peer.
+    sync_requests: HashSet<OutboundRequestId>,
+    /// Connected gossip-only peers, which answer chain requests with
+    /// [`SyncResponse::NotServing`]; retries skip them.
+    not_serving: HashSet<PeerId>,
     pending_transactions: Vec<Transaction>,
-    event_tx: mpsc::UnboundedSender<NodeEvent>,
+    /// Most transactions the mempool holds before evicting the oldest.
+    max_mempool: usize,
+    /// Pending transactions older than this are dropped.
+    mempool_ttl: std::time::Duration,
+    /// Most transactions sealed into one block.
+    max_block_transactions: usize,
+    /// Most blocks kept before pruning; see `NodeConfig::max_chain_blocks`.
+    max_chain_blocks: Option<usize>,
+    /// Tag for the blocks this node forms; see `NodeConfig::block_metadata`.
+    block_metadata: Option<Vec<u8>>,
+    /// Encrypts the payloads this node broadcasts; see
+    /// `NodeConfig::payload_key`.
+    payload_key: Option<PayloadKey>,
+    /// Skip every gossip publish and verification; see `NodeConfig::dry_run`.
+    dry_run: bool,
+    /// Seal pending transactions in [`Node::shutdown`]; see
+    /// `NodeConfig::seal_on_shutdown`.
+    seal_on_shutdown: bool,
+    /// Blocks received ahead of the local tip, keyed by index, waiting for
+    /// their predecessors to arrive.
+    orphan_blocks: BTreeMap<u64, (Block, Option<PeerId>)>,
+    /// Content-addressed transaction payloads.  Gossip-only nodes leave this
+    /// empty unless the application stores something explicitly.
+    assets: AssetStore,
+    /// Bounded event channel; see [`NodeEvent`] for the overflow policy.
+    event_tx: EventSender,
     /// Peers found via discovery but not yet connected.
     discovered_peers: HashMap<PeerId, Vec<Multiaddr>>,
-    /// Currently connected peers and their known addresses.
-    connected_peers_map: HashMap<PeerId, Vec<Multiaddr>>,
+    /// Currently connected peers, their known addresses and connection time.
+    connected_peers_map: HashMap<PeerId, ConnectedPeer>,
+    /// Whether outbound connections go through a proxy, in which case only
+    /// external addresses are announced.
+    proxied: bool,
+    /// Latest figures for the health endpoint, if `NodeConfig::health_addr`
+    /// is set, and the address it was bound to.
+    health: Option<(watch::Sender<Health>, SocketAddr)>,
     /// Controls which discovery events to act on.
     discovery_mode: DiscoveryMode,
+    /// Which discovered peers to dial without being asked.
+    auto_connect: AutoConnectPolicy,
+    /// Dials started on discovery that have not connected or failed yet.
+    auto_dials: HashSet<ConnectionId>,
+    /// When each peer was last auto-dialled, kept for [`AUTO_DIAL_COOLDOWN`].
+    last_auto_dial: HashMap<PeerId, std::time::Instant>,
     /// Optional port range filter applied to discovered peer addresses.
     discovery_port_range: Option<std::ops::RangeInclusive<u16>>,
-    /// The port this node is listening on (retained for future use).
-    #[allow(dead_code)]
-    port: u16,
+    /// The filter set by the config or [`Node::set_discovery_port_range`].
+    /// A scan with a range of its own overrides it until the next scan.
+    default_port_range: Option<std::ops::RangeInclusive<u16>>,
+    /// Refuse manual dials outside `discovery_port_range` instead of warning.
+    strict_port_range: bool,
+    /// Peers the user disconnected on purpose.  Discovery ignores them until
+    /// they are dialled again, so a kicked peer isn't straight back.
+    kicked_peers: HashSet<PeerId>,
+    /// Dials started by [`Node::dial`] (with the address) or
+    /// [`Node::dial_peer_id`], so their failures can be reported.
+    user_dials: HashMap<ConnectionId, Option<Multiaddr>>,
+    /// Consecutive failed dials per peer address; forgotten when the peer
+    /// connects or the address is dropped, as it is on reaching
+    /// [`MAX_ADDR_FAILURES`].
+    addr_failures: HashMap<(PeerId, Multiaddr), u32>,
+    /// Outstanding DHT address lookups started by [`Node::find_peer`].
+    pending_lookups: HashMap<QueryId, PeerId>,
+    /// The bootstrap started by the last [`Node::trigger_discovery`], while
+    /// it runs, and the peers discovered since it started.
+    discovery_scan: Option<(QueryId, HashSet<PeerId>)>,
+    /// Fires every [`ANNOUNCE_INTERVAL`] to refresh our address record.
+    announce_timer: tokio::time::Interval,
+    /// Fires every `NodeConfig::heartbeat_interval` to send a heartbeat.
+    heartbeat_timer: tokio::time::Interval,
+    /// Hashes of blocks this node has already verified; persisted in
+    /// `data_dir` so a restart can't verify them again.
+    verified_blocks: BTreeSet<[u8; 32]>,
+    /// Where persistent state lives, if anywhere.
+    data_dir: Option<std::path::PathBuf>,
+    /// Application topics from `NodeConfig::extra_topics`, by hash.
+    extra_topics: HashMap<TopicHash, String>,
+    /// Connected peers subscribed to each gossip topic.
+    topic_peers: HashMap<TopicHash, HashSet<PeerId>>,
+    /// Transactions in blocks that finalised, for the throughput figures.
+    finalised_txs: Throughput,
+    /// Transactions in blocks that finalised, by type.
+    finalised_by_type: HashMap<TransactionType, u64>,
+    /// Idle-connection timeout applied to the swarm, if configured.
+    idle_timeout: Option<std::time::Duration>,
+    /// How long a sync request may go unanswered.
+    request_timeout: std::time::Duration,
+    /// The TCP port actually bound, known once the first listen address
+    /// is reported.  Differs from `NodeConfig::port` when that is 0.
+    listen_port: Option<u16>,
 }
 
 impl Node {
     /// Create and configure a new node from a [`NodeConfig`].
     ///
     /// Returns the node together with a receiver for [`NodeEvent`]s that the
-    /// calling application can process independently.
+    /// calling application can process independently (wrap it in an
+    /// [`EventStream`](crate::EventStream) to use it as a `Stream`).
     pub async fn new(
         config: NodeConfig,
-    ) -> Result<(Self, mpsc::UnboundedReceiver<NodeEvent>), NodeError> {
-        let keypair = libp2p::identity::Keypair::generate_ed25519();
+    ) -> Result<(Self, mpsc::Receiver<NodeEvent>), NodeError> {
+        Self::with_wire(config, Wire::Tcp).await
+    }
+
+    /// Like [`Node::new`], but over libp2p's in-process memory transport
+    /// instead of TCP, listening on a fresh `/memory/<n>` address.  Nodes
+    /// built this way can dial each other without binding any port.
+    #[cfg(test)]
+    pub(crate) async fn new_in_memory(
+        config: NodeConfig,
+    ) -> Result<(Self, mpsc::Receiver<NodeEvent>), NodeError> {
+        Self::with_wire(config, Wire::Memory).await
+    }
+
+    async fn with_wire(
+        config: NodeConfig,
+        wire: Wire,
+    ) -> Result<(Self, mpsc::Receiver<NodeEvent>), NodeError> {
+        if config.heartbeat_interval.is_zero() {
+            return Err(NodeError::InvalidConfig("heartbeat_interval must not be zero"));
+        }
+        let keypair = match &config.data_dir {
+            Some(dir) => identity::load_or_generate(&dir.join(identity::IDENTITY_FILE))?,
+            None => libp2p::identity::Keypair::generate_ed25519(),
+        };
         let local_peer_id = keypair.public().to_peer_id();
 
         info!("Local peer id: {local_peer_id}");
 
-        let listen_addr: Multiaddr = format!("/ip4/0.0.0.0/tcp/{}", config.port)
-            .parse()
-            .map_err(|e: libp2p::multiaddr::Error| NodeError::Transport(e.to_string()))?;
+        let listen_addr: Multiaddr = match wire {
+            // Behind a proxy only the proxy should be able to reach us.
+            Wire::Tcp if config.proxy.is_some() => format!("/ip4/127.0.0.1/tcp/{}", config.port),
+            Wire::Tcp => format!("/ip4/0.0.0.0/tcp/{}", config.port),
+            #[cfg(test)]
+            Wire::Memory => "/memory/0".to_string(),
+        }
+        .parse()
+        .map_err(|e: libp2p::multiaddr::Error| NodeError::Transport(e.to_string()))?;
+
+        let fresh = Blockchain::new_with_network(&config.network_id);
+        let blockchain = match &config.data_dir {
+            Some(dir) => chain::load(&dir.join(chain::CHAIN_FILE))?.unwrap_or(fresh.clone()),
+            None => fresh.clone(),
+        };
+        let genesis_hash = blockchain.genesis_hash();
+        if genesis_hash != fresh.genesis_hash() {
+            return Err(NodeError::Blockchain(BlockchainError::InvalidChain(format!(
+                "stored chain does not belong to network {:?}",
+                config.network_id
+            ))));
+        }
 
-        let swarm = libp2p::SwarmBuilder::with_existing_identity(keypair.clone())
-            .with_tokio()
-            .with_tcp(
-                libp2p::tcp::Config::default(),
-                libp2p::noise::Config::new,
-                libp2p::yamux::Config::default,
-            )
-            .map_err(|e| NodeError::Transport(e.to_string()))?
-            .with_behaviour(|_| build_behaviour(&keypair))
-            .map_err(|e| NodeError::Transport(e.to_string()))?
-            .build();
+        let builder = libp2p::SwarmBuilder::with_existing_identity(keypair.clone()).with_tokio();
+        let behaviour = |_: &libp2p::identity::Keypair| {
+            build_behaviour(&keypair, &config, &genesis_hash)
+        };
+        let swarm_config = |cfg: libp2p::swarm::Config| match config.idle_timeout {
+            Some(timeout) => cfg.with_idle_connection_timeout(timeout),
+            None => cfg,
+        };
+        let mut swarm = match (wire, config.proxy) {
+            (Wire::Tcp, Some(proxy)) => {
+                use libp2p::{core::upgrade::Version, Transport as _};
+                let noise = libp2p::noise::Config::new(&keypair)
+                    .map_err(|e| NodeError::Transport(e.to_string()))?;
+                builder
+                    .with_other_transport(|_| {
+                        let listener = libp2p::tcp::tokio::Transport::new(libp2p::tcp::Config::default());
+                        Socks5Transport::new(proxy)
+                            .or_transport(listener)
+                            .upgrade(Version::V1)
+                            .authenticate(noise)
+                            .multiplex(libp2p::yamux::Config::default())
+                    })
+                    .map_err(|e| NodeError::Transport(e.to_string()))?
+                    .with_behaviour(behaviour)
+                    .map_err(|e| NodeError::Transport(e.to_string()))?
+                    .with_swarm_config(swarm_config)
+                    .build()
+            }
+            (Wire::Tcp, None) => builder
+                .with_tcp(
+                    libp2p::tcp::Config::default(),
+                    libp2p::noise::Config::new,
+                    libp2p::yamux::Config::default,
+                )
+                .map_err(|e| NodeError::Transport(e.to_string()))?
+                .with_dns()
+                .map_err(|e| NodeError::Transport(e.to_string()))?
+                .with_behaviour(behaviour)
+                .map_err(|e| NodeError::Transport(e.to_string()))?
+                .with_swarm_config(swarm_config)
+                .build(),
+            #[cfg(test)]
+            (Wire::Memory, _) => {
+                use libp2p::{
+                    core::{transport::MemoryTransport, upgrade::Version},
+                    Transport as _,
+                };
+                let noise = libp2p::noise::Config::new(&keypair)
+                    .map_err(|e| NodeError::Transport(e.to_string()))?;
+                builder
+                    .with_other_transport(|_| {
+                        MemoryTransport::default()
+                            .upgrade(Version::V1)
+                            .authenticate(noise)
+                            .multiplex(libp2p::yamux::Config::default())
+                    })
+                    .map_err(|e| NodeError::Transport(e.to_string()))?
+                    .with_behaviour(behaviour)
+                    .map_err(|e| NodeError::Transport(e.to_string()))?
+                    .with_swarm_config(swarm_config)
+                    .build()
+            }
+        };
+
+        for addr in &config.external_addrs {
+            swarm.add_external_address(addr.clone());
+        }
+
+        let (event_tx, event_rx) = EventSender::channel(config.event_capacity);
 
-        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        let mut announce_timer = tokio::time::interval(ANNOUNCE_INTERVAL);
+        announce_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        let mut heartbeat_timer = tokio::time::interval(config.heartbeat_interval);
+        heartbeat_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        let health = match config.health_addr {
+            Some(addr) => {
+                let listener = health::bind(addr).await?;
+                let bound = listener.local_addr()?;
+                let (health_tx, health_rx) = watch::channel(Health::default());
+                health::serve(listener, health_rx);
+                info!("Health endpoint on http://{bound}");
+                Some((health_tx, bound))
+            }
+            None => None,
+        };
 
         let mut node = Self {
             swarm,
+            keypair,
             local_peer_id,
             mode: config.mode,
-            blockchain: Blockchain::new(),
+            network_id: config.network_id,
+            chain_tx_ids: blockchain.iter_transactions().map(|(_, tx)| tx.id).collect(),
+            blockchain,
             sync_manager: SyncManager::new(config.sync_strategy),
+            sync_requests: HashSet::new(),
+            not_serving: HashSet::new(),
             pending_transactions: Vec::new(),
+            max_mempool: config.max_mempool,
+            mempool_ttl: config.mempool_ttl,
+            max_block_transactions: config.max_block_transactions,
+            max_chain_blocks: config.max_chain_blocks,
+            block_metadata: config.block_metadata,
+            payload_key: config.payload_key,
+            dry_run: config.dry_run,
+            seal_on_shutdown: config.seal_on_shutdown,
+            orphan_blocks: BTreeMap::new(),
+            assets: AssetStore::new(),
             event_tx,
             discovered_peers: HashMap::new(),
             connected_peers_map: HashMap::new(),
+            proxied: config.proxy.is_some(),
+            health,
             discovery_mode: config.discovery_mode,
-            discovery_port_range: config.discovery_port_range,
-            port: config.port,
+            auto_connect: config.auto_connect,
+            auto_dials: HashSet::new(),
+            last_auto_dial: HashMap::new(),
+            discovery_port_range: config.discovery_port_range.clone(),
+            default_port_range: config.discovery_port_range,
+            strict_port_range: config.strict_port_range,
+            kicked_peers: HashSet::new(),
+            user_dials: HashMap::new(),
+            addr_failures: HashMap::new(),
+            pending_lookups: HashMap::new(),
+            discovery_scan: None,
+            announce_timer,
+            heartbeat_timer,
+            extra_topics: config
+                .extra_topics
+                .iter()
+                .filter(|topic| !BUILTIN_TOPICS.contains(&topic.as_str()))
+                .map(|topic| (IdentTopic::new(topic.as_str()).hash(), topic.clone()))
+                .collect(),
+            topic_peers: HashMap::new(),
+            finalised_txs: Throughput::default(),
+            finalised_by_type: HashMap::new(),
+            verified_blocks: config
+                .data_dir
+                .as_ref()
+                .map(|dir| verified::load(&dir.join(verified::VERIFIED_FILE)))
+                .unwrap_or_default(),
+            data_dir: config.data_dir,
+            idle_timeout: config.idle_timeout,
+            request_timeout: config.request_timeout,
+            listen_port: None,
         };
 
+        node.load_routing_snapshot();
+
         node.swarm
             .listen_on(listen_addr)
             .map_err(|e| NodeError::Transport(e.to_string()))?;
 
+        let resolver = SystemResolver::from_system_conf();
+        node.bootstrap(config.bootstrap_peers, config.bootstrap_dns.as_deref(), &resolver)
+            .await;
+
         Ok((node, event_rx))
     }
 
@@ -116,19 +454,160 @@ impl Node {
         self.local_peer_id
     }
 
+    /// Where the health endpoint is served, if `NodeConfig::health_addr` is
+    /// set.  With port 0 this is the port the OS assigned.
+    pub fn health_addr(&self) -> Option<SocketAddr> {
+        self.health.as_ref().map(|(_, addr)| *addr)
+    }
+
+    /// The TCP port the node is listening on, once the swarm has bound it.
+    /// With `NodeConfig::port` set to 0 this is the port the OS assigned.
+    pub fn listen_port(&self) -> Option<u16> {
+        self.listen_port
+    }
+
+    /// The idle-connection timeout the swarm was built with, if any.
+    /// Connections closed this way surface as [`NodeEvent::PeerDisconnected`].
+    pub fn idle_timeout(&self) -> Option<std::time::Duration> {
+        self.idle_timeout
+    }
+
+    /// How long the node waits for a peer to answer a sync request.  Requests
+    /// that run out surface as [`NodeEvent::Error`].
+    pub fn request_timeout(&self) -> std::time::Duration {
+        self.request_timeout
+    }
+
+    /// How many events were dropped because the event receiver fell behind.
+    pub fn dropped_events(&self) -> u64 {
+        self.event_tx.dropped()
+    }
+
     /// Provide read access to the local blockchain.
     pub fn blockchain(&self) -> &Blockchain {
         &self.blockchain
     }
 
+    /// Provide read access to the local asset store.
+    pub fn assets(&self) -> &AssetStore {
+        &self.assets
+    }
+
+    /// Store an asset locally so it can be served to peers.  Returns the
+    /// content hash under which it is stored.
+    pub fn store_asset(&mut self, data: Vec<u8>) -> [u8; 32] {
+        self.assets.insert(data)
+    }
+
+    /// Ask `peer` for the asset with the given content hash.  The payload
+    /// arrives later as [`NodeEvent::AssetReceived`].
+    pub fn request_asset(&mut self, peer: PeerId, hash: [u8; 32]) -> Result<(), NodeError> {
+        let bytes = crate::protocol::encode_request(&SyncRequest::Asset { hash })
+            .map_err(|e| NodeError::Serialisation(e.to_string()))?;
+        self.swarm
+            .behaviour_mut()
+            .request_response
+            .send_request(&peer, bytes);
+        Ok(())
+    }
+
+    /// Ask `peer` for the header of block `index` and a Merkle proof that
+    /// `tx_id` is included in it.  The result arrives later as
+    /// [`NodeEvent::BlockProofReceived`].
+    pub fn request_block_proof(
+        &mut self,
+        peer: PeerId,
+        index: u64,
+        tx_id: Uuid,
+    ) -> Result<(), NodeError> {
+        let bytes = crate::protocol::encode_request(&SyncRequest::BlockWithProof { index, tx_id })
+            .map_err(|e| NodeError::Serialisation(e.to_string()))?;
+        self.swarm
+            .behaviour_mut()
+            .request_response
+            .send_request(&peer, bytes);
+        Ok(())
+    }
+
+    /// Ask `peer` which transactions it holds pending.  The ids arrive later
+    /// as [`NodeEvent::MempoolReceived`].
+    pub fn request_mempool(&mut self, peer: PeerId) -> Result<(), NodeError> {
+        let bytes = crate::protocol::encode_request(&SyncRequest::Mempool)
+            .map_err(|e| NodeError::Serialisation(e.to_string()))?;
+        self.swarm
+            .behaviour_mut()
+            .request_response
+            .send_request(&peer, bytes);
+        Ok(())
+    }
+
     /// Return a snapshot of currently connected peers and their known addresses.
     pub fn connected_peers(&self) -> Vec<(PeerId, Vec<Multiaddr>)> {
         self.connected_peers_map
             .iter()
-            .map(|(pid, addrs)| (*pid, addrs.clone()))
+            .map(|(pid, peer)| (*pid, peer.addrs.clone()))
             .collect()
     }
 
+    /// Transactions in every block finalised since the node started.
+    pub fn finalised_transactions(&self) -> u64 {
+        self.finalised_txs.total()
+    }
+
+    /// Transactions in every block finalised since the node started, counted
+    /// per [`TransactionType`].
+    pub fn finalised_by_type(&self) -> &HashMap<TransactionType, u64> {
+        &self.finalised_by_type
+    }
+
+    /// Transactions finalised per minute, averaged over the last
+    /// [`THROUGHPUT_WINDOW`](crate::throughput::THROUGHPUT_WINDOW); it decays
+    /// to zero when nothing finalises.
+    pub fn finalised_per_minute(&self) -> f64 {
+        self.finalised_txs.per_minute(std::time::Instant::now())
+    }
+
+    /// How many connected peers are subscribed to gossip `topic`.
+    pub fn topic_peers(&self, topic: &str) -> usize {
+        self.topic_peers
+            .get(&IdentTopic::new(topic).hash())
+            .map_or(0, HashSet::len)
+    }
+
+    /// The Identify agent string `peer_id` reported, if it is connected and
+    /// has identified itself.
+    pub fn peer_agent_version(&self, peer_id: &PeerId) -> Option<&str> {
+        self.connected_peers_map.get(peer_id)?.agent_version.as_deref()
+    }
+
+    /// When the connection to `peer_id` was established, if it is connected.
+    /// A peer that disconnects and reconnects starts again from the new
+    /// connection.
+    pub fn connected_since(&self, peer_id: &PeerId) -> Option<std::time::Instant> {
+        self.connected_peers_map.get(peer_id).map(|peer| peer.since)
+    }
+
+    /// Everything a status display needs, read in one go so the figures
+    /// agree with each other.
+    pub fn status(&self) -> NodeStatusSnapshot {
+        NodeStatusSnapshot {
+            peer_id: self.local_peer_id,
+            listen_addrs: self.swarm.listeners().cloned().collect(),
+            connected_peers: self.connected_peers_map.len(),
+            discovered_peers: self.discovered_peers.len(),
+            chain_length: self.blockchain.len(),
+            tip_hash: self.blockchain.tip().hash(),
+            last_finalised: self.blockchain.last_finalised_index(),
+            pending_txs: self.pending_transactions.len(),
+            mode: self.mode.clone(),
+            sync_strategy: self.sync_manager.strategy().clone(),
+            discovery_mode: self.discovery_mode.clone(),
+            network_id: self.network_id.clone(),
+            genesis_hash: self.blockchain.genesis_hash(),
+            finalised_by_type: self.finalised_by_type.clone(),
+        }
+    }
+
     /// Return a snapshot of discovered-but-not-yet-connected peers.
     pub fn discovered_peers(&self) -> Vec<(PeerId, Vec<Multiaddr>)> {
         self.discovered_peers
@@ -138,69 +617,298 @@ impl Node {
     }
 
     /// Dial a remote peer by multiaddr.
+    ///
+    /// Errors that show up only once the connection attempt runs (e.g. the
+    /// address is unreachable) are reported as [`NodeEvent::DialFailed`].
+    ///
+    /// An address whose port is outside the discovery port range is dialled
+    /// with a [`NodeEvent::DialOutsidePortRange`] warning, or refused with
+    /// [`NodeError::PortOutsideRange`] under `strict_port_range`.
     pub fn dial(&mut self, addr: Multiaddr) -> Result<(), NodeError> {
-        self.swarm
-            .dial(addr)
-            .map_err(|e| NodeError::Transport(e.to_string()))
+        let outside = match (&self.discovery_port_range, addr_port(&addr)) {
+            (Some(range), Some(port)) => !range.contains(&port),
+            _ => false,
+        };
+        if outside {
+            if self.strict_port_range {
+                return Err(NodeError::PortOutsideRange(addr));
+            }
+            warn!(%addr, "Dialling outside the discovery port range");
+            let _ = self.event_tx.send(NodeEvent::DialOutsidePortRange(addr.clone()));
+        }
+        let opts = DialOpts::unknown_peer_id().address(addr.clone()).build();
+        self.user_dials.insert(opts.connection_id(), Some(addr));
+        self.dial_tracked(opts)
     }
 
-    /// Disconnect from a connected peer.
+    /// Dial `peers` plus whatever `dns` publishes in `_dnsaddr` records.
+    /// Addresses naming a peer id also seed Kademlia.  A failed lookup is
+    /// logged and the configured peers are dialled regardless.
+    async fn bootstrap(
+        &mut self,
+        mut peers: Vec<Multiaddr>,
+        dns: Option<&str>,
+        resolver: &(impl TxtResolver + Sync),
+    ) {
+        if let Some(domain) = dns {
+            match dnsaddr::resolve_bootstrap(resolver, domain).await {
+                Ok(found) => {
+                    info!("Found {} bootstrap peer(s) under _dnsaddr.{domain}", found.len());
+                    for addr in found {
+                        if !peers.contains(&addr) {
+                            peers.push(addr);
+                        }
+                    }
+                }
+                Err(e) => warn!("Could not resolve bootstrap peers from {domain}: {e}"),
+            }
+        }
+        for addr in peers {
+            if let Some(libp2p::multiaddr::Protocol::P2p(peer_id)) = addr.iter().last() {
+                self.swarm.behaviour_mut().kademlia.add_address(&peer_id, addr.clone());
+            }
+            if let Err(e) = self.dial(addr.clone()) {
+                warn!("Could not dial bootstrap peer {addr}: {e}");
+            }
+        }
+    }
+
+    /// Dial a peer by id using the addresses already known for it, either
+    /// from discovery or from the Kademlia routing table.
+    ///
+    /// Returns [`NodeError::NoKnownAddress`] if neither has an address; use
+    /// [`Node::find_peer`] to look the peer up in the DHT instead.
+    pub fn dial_peer_id(&mut self, peer_id: PeerId) -> Result<(), NodeError> {
+        let mut addrs = self
+            .discovered_peers
+            .get(&peer_id)
+            .cloned()
+            .unwrap_or_default();
+        for bucket in self.swarm.behaviour_mut().kademlia.kbuckets() {
+            for entry in bucket.iter() {
+                if *entry.node.key.preimage() == peer_id {
+                    for addr in entry.node.value.iter() {
+                        if !addrs.contains(addr) {
+                            addrs.push(addr.clone());
+                        }
+                    }
+                }
+            }
+        }
+        if addrs.is_empty() {
+            return Err(NodeError::NoKnownAddress(peer_id));
+        }
+
+        let opts = DialOpts::peer_id(peer_id).addresses(addrs).build();
+        self.user_dials.insert(opts.connection_id(), None);
+        self.dial_tracked(opts)
+    }
+
+    /// Start a dial already recorded in `user_dials`, forgetting it again if
+    /// the swarm refuses straight away.
+    fn dial_tracked(&mut self, opts: DialOpts) -> Result<(), NodeError> {
+        let id = opts.connection_id();
+        self.swarm.dial(opts).map_err(|e| {
+            self.user_dials.remove(&id);
+            NodeError::Transport(e.to_string())
+        })
+    }
+
+    /// Disconnect from a connected peer.  Discovery ignores it afterwards
+    /// until it is dialled again.
     pub fn disconnect(&mut self, peer_id: PeerId) -> Result<(), NodeError> {
         self.swarm
             .disconnect_peer_id(peer_id)
-            .map_err(|_| NodeError::Transport(format!("peer {peer_id} not connected")))
+            .map_err(|_| NodeError::Transport(format!("peer {peer_id} not connected")))?;
+        self.kicked_peers.insert(peer_id);
+        Ok(())
     }
 
     /// Trigger an active discovery scan using both mDNS and Kademlia.
     ///
     /// `port_range` — when `Some((start, end))` only peer addresses whose port
-    /// falls in that range are accepted.  When `None` the port filter is cleared
-    /// so peers on any port are accepted (the "search on the node's own network"
-    /// default from the PLAN).
+    /// falls in that range are accepted, until the next scan.  When `None` the
+    /// filter goes back to the one set by [`Node::set_discovery_port_range`],
+    /// and with none set peers on any port are accepted (the "search on the
+    /// node's own network" default from the PLAN).
     ///
     /// Kademlia `bootstrap()` refreshes the routing table and triggers
     /// `RoutingUpdated` events → `PeerDiscovered` events to the TUI.
     /// mDNS runs continuously in the background and surfaces results as soon as
     /// `discovery_mode` allows them through.
+    ///
+    /// [`NodeEvent::DiscoveryCompleted`] follows once the bootstrap finishes,
+    /// straight away if there is nobody to bootstrap from.  A new scan
+    /// supersedes one still running.
     pub fn trigger_discovery(&mut self, port_range: Option<(u16, u16)>) {
-        self.discovery_port_range = port_range.map(|(start, end)| start..=end);
+        self.discovery_port_range = port_range
+            .map(|(start, end)| start..=end)
+            .or_else(|| self.default_port_range.clone());
         // Ensure both mDNS and Kademlia results flow through.
         self.discovery_mode = DiscoveryMode::Both;
-        let _ = self.swarm.behaviour_mut().kademlia.bootstrap();
+        match self.swarm.behaviour_mut().kademlia.bootstrap() {
+            Ok(query) => self.discovery_scan = Some((query, HashSet::new())),
+            Err(_) => {
+                self.discovery_scan = None;
+                let _ = self.event_tx.send(NodeEvent::DiscoveryCompleted { found: 0 });
+            }
+        }
+    }
+
+    /// Only accept discovered peer addresses whose port is in `port_range`,
+    /// or any port for `None`, from now on.  Unlike a range passed to
+    /// [`Node::trigger_discovery`] this holds for every later scan.
+    pub fn set_discovery_port_range(&mut self, port_range: Option<(u16, u16)>) {
+        self.default_port_range = port_range.map(|(start, end)| start..=end);
+        self.discovery_port_range = self.default_port_range.clone();
+    }
+
+    /// The port range discovered peer addresses are currently filtered by.
+    pub fn discovery_port_range(&self) -> Option<std::ops::RangeInclusive<u16>> {
+        self.discovery_port_range.clone()
+    }
+
+    /// Publish this node's listen addresses in the Kademlia DHT, keyed by its
+    /// peer id, so that other nodes can reach it knowing only the peer id.
+    ///
+    /// Called automatically whenever a new listen address comes up and every
+    /// few minutes from the event loop.  Behind a proxy only the external
+    /// addresses are announced, so the node's own address never leaks.
+    pub fn announce_addresses(&mut self) -> Result<(), NodeError> {
+        let listeners = self.swarm.listeners().filter(|_| !self.proxied);
+        let addrs: Vec<Multiaddr> =
+            listeners.chain(self.swarm.external_addresses()).cloned().collect();
+        if addrs.is_empty() {
+            return Ok(());
+        }
+
+        let value =
+            encode_addresses(&addrs).map_err(|e| NodeError::Serialisation(e.to_string()))?;
+        let mut record = Record::new(peer_record_key(&self.local_peer_id), value);
+        record.publisher = Some(self.local_peer_id);
+        self.swarm
+            .behaviour_mut()
+            .kademlia
+            .put_record(record, Quorum::One)
+            .map_err(|e| NodeError::Kademlia(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Look up the addresses `peer_id` announced in the DHT and dial it.
+    ///
+    /// Emits [`NodeEvent::PeerResolved`] when a record is found, or
+    /// [`NodeEvent::Error`] if the lookup finishes without one.
+    pub fn find_peer(&mut self, peer_id: PeerId) {
+        let query = self
+            .swarm
+            .behaviour_mut()
+            .kademlia
+            .get_record(peer_record_key(&peer_id));
+        self.pending_lookups.insert(query, peer_id);
     }
 
     /// Broadcast a transaction to all connected peers via gossipsub.
+    ///
+    /// The transaction enters the local mempool only once it has been
+    /// published.  With no peer subscribed to the transaction topic this
+    /// fails with [`NodeError::NoPeers`] and nothing is kept, so the caller
+    /// can retry once peers arrive.  Observer nodes refuse with
+    /// [`NodeError::Observer`].  With a `payload_key` configured, the
+    /// payload is encrypted before it leaves the node unless it already is,
+    /// and encrypting re-derives a content-derived `tx.id`, so the id kept
+    /// in the mempool may differ from the caller's.  A signature covers the
+    /// ciphertext, so seal a transaction with [`Node::seal_payload`] before
+    /// signing it; a signed transaction that is not sealed fails (see
+    /// [`Transaction::encrypt_payload`]).
     pub fn broadcast_transaction(&mut self, tx: Transaction) -> Result<(), NodeError> {
+        self.ensure_not_observer("broadcast transactions")?;
+        let tx = self.seal_payload(tx)?;
         let msg = GossipMessage::Transaction(tx.clone());
         let bytes =
             encode_gossip(&msg).map_err(|e| NodeError::Serialisation(e.to_string()))?;
 
-        self.swarm
-            .behaviour_mut()
-            .gossipsub
-            .publish(IdentTopic::new(TOPIC_TX), bytes)
-            .map_err(|e| NodeError::Gossipsub(e.to_string()))?;
+        self.publish_gossip(TOPIC_TX, bytes)?;
 
+        self.store_payload(&tx);
         self.pending_transactions.push(tx);
+        self.prune_mempool();
+        self.maybe_form_block()?;
+
+        Ok(())
+    }
+
+    /// Broadcast several transactions in a single gossip message.  Peers
+    /// unpack the batch into their mempool as if each had arrived alone.
+    /// An empty batch is a no-op; without peers nothing is kept, as with
+    /// [`Node::broadcast_transaction`].
+    pub fn broadcast_transactions(&mut self, txs: Vec<Transaction>) -> Result<(), NodeError> {
+        self.ensure_not_observer("broadcast transactions")?;
+        if txs.is_empty() {
+            return Ok(());
+        }
+        let txs = txs
+            .into_iter()
+            .map(|tx| self.seal_payload(tx))
+            .collect::<Result<Vec<_>, _>>()?;
+        let msg = GossipMessage::TransactionBatch(txs.clone());
+        let bytes =
+            encode_gossip(&msg).map_err(|e| NodeError::Serialisation(e.to_string()))?;
+
+        self.publish_gossip(TOPIC_TX, bytes)?;
+
+        for tx in txs {
+            self.store_payload(&tx);
+            self.pending_transactions.push(tx);
+        }
+        self.prune_mempool();
         self.maybe_form_block()?;
 
         Ok(())
     }
 
+    /// Encrypt the payload of an outgoing transaction if the network has a
+    /// payload key and it is not sealed under that key already.  To send a
+    /// signed transaction, seal it with this first, then sign and broadcast
+    /// the result.
+    pub fn seal_payload(&self, mut tx: Transaction) -> Result<Transaction, NodeError> {
+        if let Some(key) = &self.payload_key {
+            if tx.decrypt_payload(key).is_err() {
+                tx.encrypt_payload(key)?;
+            }
+        }
+        Ok(tx)
+    }
+
     /// Seal pending transactions into a block and broadcast it.
+    ///
+    /// Transactions go in by descending priority, then oldest first; at most
+    /// `max_block_transactions` are taken and the rest stay pending.
     pub fn form_block(&mut self) -> Result<(), NodeError> {
+        self.ensure_not_observer("form blocks")?;
         if self.pending_transactions.is_empty() {
             return Err(NodeError::NoPendingTransactions);
         }
 
-        let txs = std::mem::take(&mut self.pending_transactions);
-        let block = self.blockchain.add_block(txs)?;
+        self.pending_transactions.sort_by(|a, b| {
+            b.priority.cmp(&a.priority).then(a.timestamp.cmp(&b.timestamp))
+        });
+        let take = self.pending_transactions.len().min(self.max_block_transactions);
+        let txs: Vec<Transaction> = self.pending_transactions.drain(..take).collect();
+        let block = self.blockchain.add_block_with_metadata(txs, self.block_metadata.clone())?;
+        self.chain_tx_ids.extend(block.transactions.iter().map(|tx| tx.id));
         let block_index = block.index;
+        let tx_count = block.transactions.len();
         let block_clone = block.clone();
 
         info!("Formed block #{block_index}");
+        let _ = self.event_tx.send(NodeEvent::BlockFormed { block_index, tx_count });
+        self.prune_chain();
+
+        if self.dry_run {
+            return Ok(());
+        }
 
-        let msg = GossipMessage::Block(block_clone);
+        let msg = GossipMessage::Block(Box::new(block_clone));
         if let Ok(bytes) = encode_gossip(&msg) {
             let _ = self
                 .swarm
@@ -216,13 +924,34 @@ impl Node {
         Ok(())
     }
 
-    /// Send a block verification for `block_index` to all peers.
+    /// Send a block verification for `block_index` to all peers.  A dry-run
+    /// node neither records nor sends one.
     pub fn send_verification(&mut self, block_index: u64) -> Result<(), NodeError> {
+        self.ensure_not_observer("send verifications")?;
+        if self.dry_run {
+            debug!("Dry run: not verifying block #{block_index}");
+            return Ok(());
+        }
+        let hash = self
+            .blockchain
+            .get_block(block_index)
+            .map(Block::hash)
+            .ok_or(BlockchainError::BlockNotFound(block_index))?;
+        if self.verified_blocks.contains(&hash) {
+            debug!("Block #{block_index} already verified, not verifying again");
+            return Ok(());
+        }
+
+        // Record locally first so a failed publish can't lead to a second
+        // verification of the same block later.
         let peer_id_str = self.local_peer_id.to_string();
-        let msg = GossipMessage::BlockVerification {
-            block_index,
-            peer_id: peer_id_str.clone(),
-        };
+        self.record_verification(block_index, peer_id_str, self.mode.verification_weight())?;
+        self.verified_blocks.insert(hash);
+        if let Some(dir) = &self.data_dir {
+            verified::append(&dir.join(verified::VERIFIED_FILE), &hash)?;
+        }
+
+        let msg = sign_verification(&self.keypair, block_index, hash, self.mode.clone())?;
         let bytes =
             encode_gossip(&msg).map_err(|e| NodeError::Serialisation(e.to_string()))?;
 
@@ -232,22 +961,119 @@ impl Node {
             .publish(IdentTopic::new(TOPIC_VERIFY), bytes)
             .map_err(|e| NodeError::Gossipsub(e.to_string()))?;
 
-        let finalised = self.blockchain.verify_block(block_index, peer_id_str)?;
+        Ok(())
+    }
 
-        if finalised {
-            let _ = self
-                .event_tx
-                .send(NodeEvent::BlockFinalised { block_index });
+    /// Publish `bytes` on the application topic `topic`.  Subscribers that
+    /// listed it in `NodeConfig::extra_topics` receive a
+    /// [`NodeEvent::TopicMessage`].  The built-in topics are reserved, and
+    /// with no subscriber this fails with [`NodeError::NoPeers`].
+    pub fn publish(&mut self, topic: &str, bytes: Vec<u8>) -> Result<(), NodeError> {
+        if BUILTIN_TOPICS.contains(&topic) {
+            return Err(NodeError::Gossipsub(format!("topic {topic} is reserved")));
         }
+        self.publish_gossip(topic, bytes)
+    }
 
-        Ok(())
+    /// Cancel any discovery scan and dials still in flight, then persist
+    /// state that should survive a restart.  Call once the event loop has
+    /// stopped; a node without a `data_dir` has nothing to save.  From then
+    /// on the node no longer dials discovered peers by itself.
+    ///
+    /// With `NodeConfig::seal_on_shutdown`, pending transactions are first
+    /// sealed into blocks so the saved chain keeps them.  While peers are
+    /// connected the swarm then runs a little longer so those blocks are
+    /// gossiped to them rather than staying local.
+    pub async fn shutdown(&mut self) -> Result<(), NodeError> {
+        self.cancel_discovery();
+        if self.seal_on_shutdown && self.mode != NodeMode::Observer {
+            let tip = self.blockchain.tip_index();
+            self.seal_pending();
+            let sealed = self.blockchain.tip_index() != tip;
+            if sealed && !self.dry_run && self.swarm.connected_peers().next().is_some() {
+                let _ = tokio::time::timeout(SHUTDOWN_FLUSH, async {
+                    loop {
+                        self.step().await;
+                    }
+                })
+                .await;
+            }
+        }
+        if let Some(dir) = &self.data_dir {
+            chain::save(&dir.join(chain::CHAIN_FILE), &self.blockchain)?;
+        }
+        self.save_routing_snapshot()
+    }
+
+    /// Form blocks until the mempool is empty.  A failure to publish or verify
+    /// doesn't matter this late, but one that leaves the mempool untouched
+    /// stops the loop.
+    fn seal_pending(&mut self) {
+        while !self.pending_transactions.is_empty() {
+            let before = self.pending_transactions.len();
+            if let Err(e) = self.form_block() {
+                warn!("Sealing pending transactions on shutdown: {e}");
+            }
+            if self.pending_transactions.len() == before {
+                break;
+            }
+        }
+    }
+
+    /// Finish the running discovery scan, stop auto-dialling and abort the
+    /// pending dials to every peer discovery knows about.
+    fn cancel_discovery(&mut self) {
+        let kademlia = &mut self.swarm.behaviour_mut().kademlia;
+        if let Some((query, _)) = self.discovery_scan.take() {
+            if let Some(mut query) = kademlia.query_mut(&query) {
+                query.finish();
+            }
+        }
+        let mut peers: HashSet<PeerId> = self.discovered_peers.keys().copied().collect();
+        peers.extend(self.last_auto_dial.keys().copied());
+        for bucket in kademlia.kbuckets() {
+            peers.extend(bucket.iter().map(|entry| *entry.node.key.preimage()));
+        }
+
+        self.auto_connect = AutoConnectPolicy::None;
+        self.auto_dials.clear();
+        for peer in peers {
+            if !self.swarm.is_connected(&peer) {
+                // Aborts the peer's pending connections.
+                let _ = self.swarm.disconnect_peer_id(peer);
+            }
+        }
     }
 
     /// Run the node event loop.  This future runs until cancelled.
     pub async fn run(&mut self) {
         loop {
-            let event = self.swarm.select_next_some().await;
-            self.handle_swarm_event(event).await;
+            self.step().await;
+        }
+    }
+
+    /// Wait for and handle one unit of node work: a swarm event, an address
+    /// re-announcement, or delivering a backlogged [`NodeEvent`].
+    ///
+    /// [`Node::run`] is a loop over this; a host with its own event loop can
+    /// call it from a `select!` instead.  Dropping the future early loses
+    /// nothing, since work is only taken up when it can be finished in the
+    /// same poll.
+    pub async fn step(&mut self) {
+        tokio::select! {
+            event = self.swarm.select_next_some() => {
+                self.handle_swarm_event(event).await;
+            }
+            _ = self.announce_timer.tick() => {
+                let _ = self.announce_addresses();
+            }
+            _ = self.heartbeat_timer.tick() => {
+                let _ = self.event_tx.send(NodeEvent::Heartbeat {
+                    tip_index: self.blockchain.tip_index(),
+                    peer_count: self.connected_peers_map.len(),
+                });
+            }
+            _ = self.event_tx.drain_one(), if self.event_tx.has_backlog() => {}
         }
     }
 
@@ -270,18 +1096,132 @@ impl Node {
 
         loop {
             tokio::select! {
-                event = self.swarm.select_next_some() => {
-                    self.handle_swarm_event(event).await;
-                }
                 _ = ticker.tick() => {
                     self.trigger_discovery(None);
                 }
+                _ = self.step() => {}
             }
         }
     }
 
     // ── Internal helpers ─────────────────────────────────────────────────────
 
+    /// Publish on `topic`, failing with [`NodeError::NoPeers`] rather than
+    /// libp2p's own error when no connected peer is subscribed to it.
+    /// A dry-run node publishes nothing and always succeeds.
+    fn publish_gossip(&mut self, topic: &str, bytes: Vec<u8>) -> Result<(), NodeError> {
+        if self.dry_run {
+            debug!("Dry run: not publishing {} bytes on {topic}", bytes.len());
+            return Ok(());
+        }
+        if self.topic_peers(topic) == 0 {
+            return Err(NodeError::NoPeers(topic.to_string()));
+        }
+        self.swarm
+            .behaviour_mut()
+            .gossipsub
+            .publish(IdentTopic::new(topic), bytes)
+            .map_err(|e| NodeError::Gossipsub(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Record `peer_id` joining or leaving `topic`, reporting the new count
+    /// if it changed.
+    fn update_topic_peer(&mut self, topic: TopicHash, peer_id: PeerId, subscribed: bool) {
+        let peers = self.topic_peers.entry(topic.clone()).or_default();
+        let changed = if subscribed {
+            peers.insert(peer_id)
+        } else {
+            peers.remove(&peer_id)
+        };
+        if changed {
+            let _ = self.event_tx.send(NodeEvent::TopicPeersChanged {
+                topic: topic.to_string(),
+                peers: peers.len(),
+            });
+        }
+    }
+
+    /// Dial a newly discovered peer if the auto-connect policy allows it and
+    /// it is neither connected nor auto-dialled within [`AUTO_DIAL_COOLDOWN`].
+    fn auto_dial(&mut self, peer_id: PeerId, addrs: Vec<Multiaddr>) {
+        let now = std::time::Instant::now();
+        self.last_auto_dial.retain(|_, at| now.duration_since(*at) < AUTO_DIAL_COOLDOWN);
+        if self.connected_peers_map.contains_key(&peer_id)
+            || self.last_auto_dial.contains_key(&peer_id)
+        {
+            return;
+        }
+        let allowed = match self.auto_connect {
+            AutoConnectPolicy::All => true,
+            AutoConnectPolicy::UpTo(max) => {
+                self.connected_peers_map.len() + self.auto_dials.len() < max
+            }
+            AutoConnectPolicy::None => false,
+        };
+        if !allowed {
+            return;
+        }
+        let opts = DialOpts::peer_id(peer_id)
+            .addresses(addrs)
+            .condition(PeerCondition::DisconnectedAndNotDialing)
+            .build();
+        let connection_id = opts.connection_id();
+        match self.swarm.dial(opts) {
+            Ok(()) => {
+                self.auto_dials.insert(connection_id);
+                self.last_auto_dial.insert(peer_id, now);
+            }
+            Err(e) => debug!("Not auto-dialling {peer_id}: {e}"),
+        }
+    }
+
+    /// Count a failed dial against each of `addrs` for `peer_id`, dropping
+    /// from the discovered list any that have now failed
+    /// [`MAX_ADDR_FAILURES`] times in a row.
+    fn record_dial_failures(&mut self, peer_id: PeerId, addrs: Vec<Multiaddr>) {
+        let mut stale = Vec::new();
+        for addr in addrs {
+            let failures = self.addr_failures.entry((peer_id, addr.clone())).or_insert(0);
+            *failures += 1;
+            if *failures >= MAX_ADDR_FAILURES {
+                stale.push(addr);
+            }
+        }
+        if let Some(addrs) = self.drop_discovered_addrs(peer_id, &stale) {
+            let _ = self.event_tx.send(NodeEvent::PeerAddrsPruned { peer_id, addrs });
+        }
+    }
+
+    /// Forget `stale` addresses of a discovered peer, and the peer once none
+    /// are left.  Returns the remaining addresses if any were dropped.
+    fn drop_discovered_addrs(
+        &mut self,
+        peer_id: PeerId,
+        stale: &[Multiaddr],
+    ) -> Option<Vec<Multiaddr>> {
+        if stale.is_empty() {
+            return None;
+        }
+        for addr in stale {
+            debug!("Dropping stale address {addr} for {peer_id}");
+            self.swarm.behaviour_mut().kademlia.remove_address(&peer_id, addr);
+            self.addr_failures.remove(&(peer_id, addr.clone()));
+        }
+        let known = self.discovered_peers.get_mut(&peer_id)?;
+        let before = known.len();
+        known.retain(|addr| !stale.contains(addr));
+        if known.len() == before {
+            return None;
+        }
+        let addrs = known.clone();
+        if addrs.is_empty() {
+            self.discovered_peers.remove(&peer_id);
+            self.addr_failures.retain(|(peer, _), _| *peer != peer_id);
+        }
+        Some(addrs)
+    }
+
     /// Filter peer addresses by the configured discovery port range.
     fn filter_addrs(&self, addrs: Vec<Multiaddr>) -> Vec<Multiaddr> {
         match &self.discovery_port_range {
@@ -297,38 +1237,146 @@ impl Node {
         }
     }
 
+    /// Drop any of our own listen addresses from `addrs`, so discovery never
+    /// points us back at ourselves.
+    fn without_own_addrs(&self, mut addrs: Vec<Multiaddr>) -> Vec<Multiaddr> {
+        let own: HashSet<&Multiaddr> = self.swarm.listeners().collect();
+        addrs.retain(|addr| !own.contains(addr));
+        addrs
+    }
+
     async fn handle_swarm_event(&mut self, event: SwarmEvent<SpBehaviourEvent>) {
+        let affects_health = matches!(
+            event,
+            SwarmEvent::NewListenAddr { .. }
+                | SwarmEvent::ExpiredListenAddr { .. }
+                | SwarmEvent::ConnectionEstablished { .. }
+                | SwarmEvent::ConnectionClosed { .. }
+        );
+        self.dispatch_swarm_event(event).await;
+        if affects_health {
+            self.refresh_health();
+        }
+    }
+
+    /// Publish fresh figures to the health endpoint, if it is served.
+    fn refresh_health(&self) {
+        if let Some((health_tx, _)) = &self.health {
+            health_tx.send_replace(Health::from_status(&self.status()));
+        }
+    }
+
+    /// The per-event work of [`Node::handle_swarm_event`].
+    async fn dispatch_swarm_event(&mut self, event: SwarmEvent<SpBehaviourEvent>) {
         match event {
             SwarmEvent::NewListenAddr { address, .. } => {
                 info!("Listening on {address}");
+                if self.listen_port.is_none() {
+                    self.listen_port = addr_port(&address);
+                }
                 let _ = self.event_tx.send(NodeEvent::Listening(address));
+                if let Err(e) = self.announce_addresses() {
+                    warn!("Failed to announce addresses: {e}");
+                }
             }
 
-            SwarmEvent::ConnectionEstablished { peer_id, endpoint, .. } => {
+            SwarmEvent::ConnectionEstablished { peer_id, connection_id, endpoint, .. } => {
                 info!("Connected to {peer_id}");
+                self.user_dials.remove(&connection_id);
+                self.auto_dials.remove(&connection_id);
+                self.kicked_peers.remove(&peer_id);
                 let addr = endpoint.get_remote_address().clone();
-                // Move from discovered → connected.
-                self.discovered_peers.remove(&peer_id);
-                self.connected_peers_map
-                    .entry(peer_id)
-                    .or_default()
-                    .push(addr);
+                self.addr_failures.retain(|(peer, _), _| *peer != peer_id);
+                // Move from discovered → connected, keeping every address the
+                // peer is known by under the one entry.
+                let known = self.discovered_peers.remove(&peer_id).unwrap_or_default();
+                let peer = self.connected_peers_map.entry(peer_id).or_insert_with(|| {
+                    ConnectedPeer {
+                        addrs: Vec::new(),
+                        since: std::time::Instant::now(),
+                        agent_version: None,
+                    }
+                });
+                merge_addrs(&mut peer.addrs, std::iter::once(addr).chain(known));
                 let _ = self.event_tx.send(NodeEvent::PeerConnected(peer_id));
                 self.request_chain_tip(peer_id);
             }
 
-            SwarmEvent::ConnectionClosed { peer_id, .. } => {
-                debug!("Disconnected from {peer_id}");
-                self.connected_peers_map.remove(&peer_id);
-                let _ = self.event_tx.send(NodeEvent::PeerDisconnected(peer_id));
+            SwarmEvent::OutgoingConnectionError { connection_id, peer_id, error } => {
+                self.auto_dials.remove(&connection_id);
+                if let (Some(peer_id), DialError::Transport(attempts)) = (peer_id, &error) {
+                    let failed: Vec<Multiaddr> =
+                        attempts.iter().map(|(addr, _)| addr.clone()).collect();
+                    self.record_dial_failures(peer_id, failed);
+                }
+                if let Some(addr) = self.user_dials.remove(&connection_id) {
+                    warn!("Dial failed: {error}");
+                    let _ = self.event_tx.send(NodeEvent::DialFailed {
+                        addr,
+                        peer_id,
+                        error: error.to_string(),
+                    });
+                }
             }
 
-            SwarmEvent::Behaviour(SpBehaviourEvent::Gossipsub(
-                libp2p::gossipsub::Event::Message { message, .. },
-            )) => {
-                self.handle_gossip_message(&message.data).await;
+            SwarmEvent::ConnectionClosed { peer_id, num_established, .. } => {
+                // Discovery can race an explicit dial and leave two
+                // connections; the peer is only gone once the last one is.
+                if num_established > 0 {
+                    debug!("Closed one of several connections to {peer_id}");
+                    return;
+                }
+                debug!("Disconnected from {peer_id}");
+                self.not_serving.remove(&peer_id);
+                let peer = self.connected_peers_map.remove(&peer_id);
+                // A dropped peer goes back to discovered so it can be redialled;
+                // one the user kicked stays out.
+                match peer {
+                    Some(peer) if !self.kicked_peers.contains(&peer_id) => {
+                        let known = self.discovered_peers.entry(peer_id).or_default();
+                        merge_addrs(known, peer.addrs);
+                    }
+                    _ => {}
+                }
+                let topics: Vec<TopicHash> = self
+                    .topic_peers
+                    .iter()
+                    .filter(|(_, peers)| peers.contains(&peer_id))
+                    .map(|(topic, _)| topic.clone())
+                    .collect();
+                for topic in topics {
+                    self.update_topic_peer(topic, peer_id, false);
+                }
+                let _ = self.event_tx.send(NodeEvent::PeerDisconnected(peer_id));
             }
 
+            SwarmEvent::Behaviour(SpBehaviourEvent::Gossipsub(
+                libp2p::gossipsub::Event::Subscribed { peer_id, topic },
+            )) => self.update_topic_peer(topic, peer_id, true),
+
+            SwarmEvent::Behaviour(SpBehaviourEvent::Gossipsub(
+                libp2p::gossipsub::Event::Unsubscribed { peer_id, topic },
+            )) => self.update_topic_peer(topic, peer_id, false),
+
+            SwarmEvent::Behaviour(SpBehaviourEvent::Gossipsub(
+                libp2p::gossipsub::Event::Message { propagation_source, message, .. },
+            )) => match self.extra_topics.get(&message.topic) {
+                Some(topic) => {
+                    let _ = self.event_tx.send(NodeEvent::TopicMessage {
+                        topic: topic.clone(),
+                        data: message.data,
+                    });
+                }
+                None => {
+                    self.handle_gossip_message(
+                        Some(propagation_source),
+                        message.source,
+                        &message.data,
+                    )
+                    .await
+                }
+            },
+
             SwarmEvent::Behaviour(SpBehaviourEvent::Mdns(
                 libp2p::mdns::Event::Discovered(peers),
             )) => {
@@ -341,7 +1389,14 @@ impl Node {
                     by_peer.entry(peer_id).or_default().push(addr);
                 }
                 for (peer_id, addrs) in by_peer {
-                    if self.connected_peers_map.contains_key(&peer_id) {
+                    if peer_id == self.local_peer_id
+                        || self.connected_peers_map.contains_key(&peer_id)
+                        || self.kicked_peers.contains(&peer_id)
+                    {
+                        continue;
+                    }
+                    let addrs = self.without_own_addrs(addrs);
+                    if addrs.is_empty() {
                         continue;
                     }
                     let filtered = self.filter_addrs(addrs.clone());
@@ -357,15 +1412,27 @@ impl Node {
                             .add_address(&peer_id, addr.clone());
                     }
                     let entry = self.discovered_peers.entry(peer_id).or_default();
-                    for addr in &kept {
-                        if !entry.contains(addr) {
-                            entry.push(addr.clone());
-                        }
+                    let added = merge_addrs(entry, kept.iter().cloned());
+                    self.auto_dial(peer_id, kept);
+                    // mDNS repeats itself; only news is worth an event.
+                    if !added.is_empty() {
+                        self.report_discovered(peer_id, added, DiscoverySource::Mdns);
                     }
-                    let _ = self.event_tx.send(NodeEvent::PeerDiscovered {
-                        peer_id,
-                        addrs: kept,
-                    });
+                }
+            }
+
+            SwarmEvent::Behaviour(SpBehaviourEvent::Mdns(libp2p::mdns::Event::Expired(peers))) => {
+                let mut by_peer: HashMap<PeerId, Vec<Multiaddr>> = HashMap::new();
+                for (peer_id, addr) in peers {
+                    by_peer.entry(peer_id).or_default().push(addr);
+                }
+                for (peer_id, addrs) in by_peer {
+                    let event = match self.drop_discovered_addrs(peer_id, &addrs) {
+                        None => continue,
+                        Some(addrs) if addrs.is_empty() => NodeEvent::PeerExpired { peer_id },
+                        Some(addrs) => NodeEvent::PeerAddrsPruned { peer_id, addrs },
+                    };
+                    let _ = self.event_tx.send(event);
                 }
             }
 
@@ -376,10 +1443,16 @@ impl Node {
                 if self.discovery_mode == DiscoveryMode::Mdns {
                     return;
                 }
-                if self.connected_peers_map.contains_key(&peer) {
+                if peer == self.local_peer_id
+                    || self.connected_peers_map.contains_key(&peer)
+                    || self.kicked_peers.contains(&peer)
+                {
+                    return;
+                }
+                let addrs = self.without_own_addrs(addresses.into_vec());
+                if addrs.is_empty() {
                     return;
                 }
-                let addrs: Vec<Multiaddr> = addresses.into_vec();
                 let filtered = self.filter_addrs(addrs.clone());
                 let kept = if filtered.is_empty() && self.discovery_port_range.is_some() {
                     return;
@@ -389,23 +1462,69 @@ impl Node {
                     filtered
                 };
                 let entry = self.discovered_peers.entry(peer).or_default();
-                for addr in &kept {
-                    if !entry.contains(addr) {
-                        entry.push(addr.clone());
+                let added = merge_addrs(entry, kept.iter().cloned());
+                self.auto_dial(peer, kept);
+                if !added.is_empty() {
+                    self.report_discovered(peer, added, DiscoverySource::Kademlia);
+                }
+            }
+
+            SwarmEvent::Behaviour(SpBehaviourEvent::Kademlia(
+                kad::Event::OutboundQueryProgressed {
+                    id,
+                    result: QueryResult::GetRecord(result),
+                    ..
+                },
+            )) => {
+                self.handle_peer_lookup(id, result);
+            }
+
+            SwarmEvent::Behaviour(SpBehaviourEvent::Kademlia(
+                kad::Event::OutboundQueryProgressed {
+                    id,
+                    result: QueryResult::Bootstrap(_),
+                    step,
+                    ..
+                },
+            )) => {
+                let ours = matches!(&self.discovery_scan, Some((query, _)) if *query == id);
+                if ours && step.last {
+                    if let Some((_, found)) = self.discovery_scan.take() {
+                        let found = found.len();
+                        let _ = self.event_tx.send(NodeEvent::DiscoveryCompleted { found });
                     }
                 }
-                let _ = self.event_tx.send(NodeEvent::PeerDiscovered {
-                    peer_id: peer,
-                    addrs: kept,
-                });
             }
 
             // When a peer sends us its Identify info, register its listen
             // addresses in the Kademlia routing table.  Without this step,
             // kademlia.bootstrap() has an empty table and can't reach anyone.
+            // Peers advertising a different genesis are on another chain and
+            // get disconnected instead.
             SwarmEvent::Behaviour(SpBehaviourEvent::Identify(
                 libp2p::identify::Event::Received { peer_id, info, .. },
             )) => {
+                let ours = self.blockchain.genesis_hash();
+                if let Some(theirs) = genesis_from_agent(&info.agent_version) {
+                    if theirs != ours {
+                        warn!("Disconnecting {peer_id}: different genesis block");
+                        let _ = self.event_tx.send(NodeEvent::Error(format!(
+                            "peer {peer_id} has a different genesis block ({} vs ours {})",
+                            hex::encode(theirs),
+                            hex::encode(ours)
+                        )));
+                        self.kicked_peers.insert(peer_id);
+                        let _ = self.swarm.disconnect_peer_id(peer_id);
+                        return;
+                    }
+                }
+                if let Some(peer) = self.connected_peers_map.get_mut(&peer_id) {
+                    peer.agent_version = Some(info.agent_version.clone());
+                }
+                let _ = self.event_tx.send(NodeEvent::PeerIdentified {
+                    peer_id,
+                    agent_version: info.agent_version,
+                });
                 for addr in info.listen_addrs {
                     self.swarm
                         .behaviour_mut()
@@ -428,74 +1547,156 @@ impl Node {
             }
 
             SwarmEvent::Behaviour(SpBehaviourEvent::RequestResponse(
-                libp2p::request_response::Event::Message { peer, message, .. },
+                request_response::Event::Message { peer, message, .. },
             )) => {
                 self.handle_request_response(peer, message).await;
             }
 
+            SwarmEvent::Behaviour(SpBehaviourEvent::RequestResponse(
+                request_response::Event::OutboundFailure { peer, request_id, error, .. },
+            )) => {
+                self.handle_outbound_failure(peer, request_id, error);
+            }
+
+            SwarmEvent::Behaviour(SpBehaviourEvent::RequestResponse(
+                request_response::Event::InboundFailure { peer, error, .. },
+            )) => {
+                self.handle_inbound_failure(peer, error);
+            }
+
             _ => {}
         }
     }
 
-    async fn handle_gossip_message(&mut self, data: &[u8]) {
+    /// Handle a gossip message relayed by `origin` and published by `source`
+    /// (each if known).
+    async fn handle_gossip_message(
+        &mut self,
+        origin: Option<PeerId>,
+        source: Option<PeerId>,
+        data: &[u8],
+    ) {
         match decode_gossip(data) {
             Ok(GossipMessage::Transaction(tx)) => {
-                debug!("Received transaction {}", tx.id);
-                let _ = self.event_tx.send(NodeEvent::TransactionReceived(tx.clone()));
-                self.pending_transactions.push(tx);
+                self.receive_transaction(tx, origin);
+                self.prune_mempool();
+                let _ = self.maybe_form_block();
+            }
+
+            Ok(GossipMessage::TransactionBatch(txs)) => {
+                debug!("Received batch of {} transactions", txs.len());
+                for tx in txs {
+                    self.receive_transaction(tx, origin);
+                }
+                self.prune_mempool();
                 let _ = self.maybe_form_block();
             }
 
             Ok(GossipMessage::Block(block)) => {
-                let block_index = block.index;
-                debug!("Received block #{block_index}");
-                let _ = self.event_tx.send(NodeEvent::BlockReceived(block));
+                debug!("Received block #{}", block.index);
+                self.handle_block(*block, origin);
+            }
 
-                if self.mode == NodeMode::Full {
-                    let _ = self.send_verification(block_index);
+            Ok(GossipMessage::BlockVerification {
+                block_index,
+                block_hash,
+                peer_id,
+                mode,
+                public_key,
+                signature,
+            }) => {
+                let signer = verification_signer(
+                    block_index,
+                    &block_hash,
+                    &peer_id,
+                    &mode,
+                    &public_key,
+                    &signature,
+                );
+                // The signer must also be whoever published the message; one
+                // with no known publisher can't be checked, so it is dropped.
+                let authentic = match (signer, source) {
+                    (Some(signer), Some(source)) => signer == source,
+                    (Some(_), None) | (None, _) => false,
+                };
+                if !authentic {
+                    warn!("Dropping verification of block #{block_index} claimed by {peer_id}");
+                    let _ = self.event_tx.send(NodeEvent::Error(format!(
+                        "dropped unauthenticated verification of block #{block_index} \
+                         claimed by {peer_id}"
+                    )));
+                    return;
+                }
+                let ours = self.blockchain.get_block(block_index).map(Block::hash);
+                if ours.is_some_and(|hash| hash != block_hash) {
+                    debug!("Ignoring {peer_id}'s verification of a different block #{block_index}");
+                    return;
+                }
+                let weight = mode.verification_weight();
+                if let Err(e) = self.record_verification(block_index, peer_id, weight) {
+                    warn!("verify_block error: {e}");
                 }
             }
 
-            Ok(GossipMessage::BlockVerification { block_index, peer_id }) => {
-                match self.blockchain.verify_block(block_index, peer_id) {
-                    Ok(true) => {
-                        info!("Block #{block_index} finalised");
-                        let _ = self
-                            .event_tx
-                            .send(NodeEvent::BlockFinalised { block_index });
-                    }
-                    Ok(false) => {}
-                    Err(e) => warn!("verify_block error: {e}"),
-                }
+            Err(e) => self.report_undecodable("gossip message", source, &e),
+        }
+    }
+
+    /// Admit a gossiped transaction to the mempool, or report why not with
+    /// [`NodeEvent::TransactionRejected`].
+    fn receive_transaction(&mut self, tx: Transaction, origin: Option<PeerId>) {
+        match self.rejection_reason(&tx) {
+            Some(reason) => {
+                debug!("Rejected transaction {}: {reason}", tx.id);
+                let _ = self.event_tx.send(NodeEvent::TransactionRejected { id: tx.id, reason });
             }
+            None => self.accept_transaction(tx, origin),
+        }
+    }
 
-            Err(e) => warn!("Failed to decode gossip message: {e}"),
+    /// Why a gossiped transaction can't be accepted, if it can't.  A full
+    /// node refuses one that fails [`Transaction::validate`], since any block
+    /// sealing it would be rejected by other full nodes.  A replay of one
+    /// already pending or sealed would be counted twice; one older than
+    /// `mempool_ttl` would only be evicted again.
+    fn rejection_reason(&self, tx: &Transaction) -> Option<String> {
+        if self.mode == NodeMode::Full {
+            if let Err(e) = tx.validate() {
+                return Some(format!("invalid: {e}"));
+            }
+        }
+        if self.pending_transactions.iter().any(|p| p.id == tx.id) {
+            return Some("replayed: already pending".to_string());
         }
+        if self.chain_tx_ids.contains(&tx.id) {
+            return Some("replayed: already in the chain".to_string());
+        }
+        if tx.timestamp < self.mempool_cutoff() {
+            return Some("expired".to_string());
+        }
+        None
+    }
+
+    /// Add a gossiped transaction to the mempool.
+    fn accept_transaction(&mut self, tx: Transaction, origin: Option<PeerId>) {
+        debug!("Received transaction {}", tx.id);
+        let _ = self.event_tx.send(NodeEvent::TransactionReceived { tx: tx.clone(), origin });
+        self.store_payload(&tx);
+        self.pending_transactions.push(tx);
     }
 
     async fn handle_request_response(
         &mut self,
-        _peer: PeerId,
+        peer: PeerId,
         message: RrMessage<Vec<u8>, Vec<u8>>,
     ) {
         match message {
             RrMessage::Request { request, channel, .. } => {
-                let response = match decode_request(&request) {
-                    Ok(SyncRequest::ChainTip) => {
-                        let tip = self.blockchain.tip().index;
-                        encode_response(&SyncResponse::ChainTip { tip_index: tip })
-                    }
-                    Ok(SyncRequest::BlocksFrom { from_index }) => {
-                        let blocks = self.blockchain.blocks_from(from_index).to_vec();
-                        encode_response(&SyncResponse::Blocks(blocks))
-                    }
-                    Err(e) => {
-                        warn!("Failed to decode sync request: {e}");
-                        return;
-                    }
+                let Some(response) = self.respond_to_request(peer, &request) else {
+                    return;
                 };
 
-                if let Ok(bytes) = response {
+                if let Ok(bytes) = encode_response(&response) {
                     let _ = self
                         .swarm
                         .behaviour_mut()
@@ -504,59 +1705,614 @@ impl Node {
                 }
             }
 
-            RrMessage::Response { response, .. } => {
-                self.apply_sync_response(&response).await;
+            RrMessage::Response { request_id, response } => {
+                self.sync_requests.remove(&request_id);
+                self.apply_sync_response(peer, &response).await;
+            }
+        }
+    }
+
+    /// Decode and answer a raw sync request from `peer`.  One in another wire
+    /// version gets [`SyncResponse::VersionMismatch`] so the requester learns
+    /// why; anything else undecodable goes unanswered.
+    fn respond_to_request(&mut self, peer: PeerId, bytes: &[u8]) -> Option<SyncResponse> {
+        match decode_request(bytes) {
+            Ok(request) => Some(self.answer_sync_request(peer, request)),
+            Err(e) => {
+                self.report_undecodable("sync request", Some(peer), &e);
+                matches!(e, DecodeError::IncompatibleVersion(_))
+                    .then_some(SyncResponse::VersionMismatch { supported: WIRE_VERSION })
+            }
+        }
+    }
+
+    /// What to tell `peer` in reply to `request`.  A gossip-only node's chain
+    /// is just what it relayed, so it refuses anything answered from it.
+    fn answer_sync_request(&self, peer: PeerId, request: SyncRequest) -> SyncResponse {
+        match request {
+            SyncRequest::ChainTip
+            | SyncRequest::BlocksFrom { .. }
+            | SyncRequest::BlockWithProof { .. }
+                if self.mode == NodeMode::Gossip =>
+            {
+                SyncResponse::NotServing
+            }
+            SyncRequest::ChainTip => SyncResponse::ChainTip {
+                tip_index: self.blockchain.tip_index(),
+            },
+            SyncRequest::BlocksFrom { from_index } => match self.blocks_to_serve(from_index) {
+                Ok(blocks) => SyncResponse::Blocks(blocks),
+                Err(e) => {
+                    debug!("{peer} asked for blocks we don't hold: {e}");
+                    SyncResponse::NotFound
+                }
+            },
+            SyncRequest::Asset { hash } => {
+                let data = self.assets.get(&hash).map(<[u8]>::to_vec);
+                SyncResponse::Asset { hash, data }
+            }
+            SyncRequest::BlockWithProof { index, tx_id } => {
+                let found = self
+                    .blockchain
+                    .get_block(index)
+                    .and_then(|block| Some((block.header(), block.proof(tx_id).ok()?)));
+                match found {
+                    Some((header, proof)) => SyncResponse::BlockWithProof {
+                        header,
+                        proof: proof.to_bytes(),
+                    },
+                    None => SyncResponse::NotFound,
+                }
+            }
+            SyncRequest::Mempool => SyncResponse::Mempool(
+                self.pending_transactions.iter().map(|tx| tx.id).collect(),
+            ),
+        }
+    }
+
+    /// The blocks from `from_index` onward that our sync strategy lets us
+    /// share.  An unusable strategy serves nothing rather than everything;
+    /// a `from_index` beyond the block after our tip, or one we have pruned,
+    /// is an error.
+    fn blocks_to_serve(&self, from_index: u64) -> Result<Vec<Block>, BlockchainError> {
+        let blocks = self.blockchain.blocks_from_checked(from_index)?;
+        match self.sync_manager.blocks_to_serve(blocks) {
+            Ok(blocks) => Ok(blocks.into_iter().cloned().collect()),
+            Err(e) => {
+                warn!("Not serving blocks: {e}");
+                Ok(Vec::new())
+            }
+        }
+    }
+
+    /// Record `peer_id`'s verification of `block_index`, reporting the
+    /// block's progress if it was new and its finalisation when first
+    /// reached.
+    fn record_verification(
+        &mut self,
+        block_index: u64,
+        peer_id: String,
+        weight: u32,
+    ) -> Result<(), BlockchainError> {
+        let state = |chain: &Blockchain| {
+            chain
+                .get_block(block_index)
+                .map_or((0, false), |block| (block.verifications.len(), block.is_finalised()))
+        };
+        let (verifiers, was_finalised) = state(&self.blockchain);
+        let finalised = self.blockchain.verify_block_weighted(block_index, peer_id, weight)?;
+
+        if state(&self.blockchain).0 != verifiers {
+            let weight = self.blockchain.get_block(block_index).map_or(0, |b| b.verified_weight);
+            let _ = self.event_tx.send(NodeEvent::BlockVerified {
+                block_index,
+                verifications: (weight / FULL_VERIFICATION_WEIGHT) as usize,
+                threshold: MIN_VERIFICATIONS,
+            });
+        }
+        if finalised && !was_finalised {
+            info!("Block #{block_index} finalised");
+            let txs = self.blockchain.get_block(block_index).map_or(&[][..], |b| &b.transactions);
+            for tx in txs {
+                *self.finalised_by_type.entry(tx.kind.clone()).or_default() += 1;
+            }
+            let tx_count = txs.len();
+            self.finalised_txs.record(std::time::Instant::now(), tx_count);
+            let _ = self.event_tx.send(NodeEvent::BlockFinalised { block_index, tx_count });
+            self.prune_chain();
+        }
+        Ok(())
+    }
+
+    /// Report a request of ours that `peer` did not answer.  A failed sync
+    /// request is retried with another connected peer, if there is one.
+    fn handle_outbound_failure(
+        &mut self,
+        peer: PeerId,
+        request_id: OutboundRequestId,
+        error: OutboundFailure,
+    ) {
+        warn!("Request to {peer} failed: {error}");
+        let _ = self
+            .event_tx
+            .send(NodeEvent::Error(format!("request to {peer} failed: {error}")));
+
+        if self.sync_requests.remove(&request_id) {
+            let other = self
+                .connected_peers_map
+                .keys()
+                .find(|p| **p != peer && !self.not_serving.contains(p))
+                .copied();
+            if let Some(other) = other {
+                debug!("Retrying sync with {other}");
+                self.request_chain_tip(other);
             }
         }
     }
 
-    async fn apply_sync_response(&mut self, data: &[u8]) {
+    /// Report a request from `peer` that we could not answer.
+    fn handle_inbound_failure(&mut self, peer: PeerId, error: InboundFailure) {
+        warn!("Request from {peer} failed: {error}");
+        let _ = self
+            .event_tx
+            .send(NodeEvent::Error(format!("request from {peer} failed: {error}")));
+    }
+
+    async fn apply_sync_response(&mut self, peer: PeerId, data: &[u8]) {
         match crate::protocol::decode_response(data) {
             Ok(SyncResponse::ChainTip { tip_index }) => {
-                let local_tip = self.blockchain.tip().index;
-                if tip_index > local_tip {
+                let local_tip = self.blockchain.tip_index();
+                let from_index = self.sync_manager.first_index_to_request(&self.blockchain);
+                if let Some(from_index) = from_index.filter(|&from| tip_index >= from) {
                     debug!("Peer tip ({tip_index}) > local ({local_tip}), requesting blocks");
-                    let peer = self.swarm.connected_peers().next().copied();
-                    if let Some(peer) = peer {
-                        if let Ok(bytes) = crate::protocol::encode_request(
-                            &SyncRequest::BlocksFrom { from_index: local_tip + 1 },
-                        ) {
-                            self.swarm
-                                .behaviour_mut()
-                                .request_response
-                                .send_request(&peer, bytes);
-                        }
+                    if let Ok(bytes) =
+                        crate::protocol::encode_request(&SyncRequest::BlocksFrom { from_index })
+                    {
+                        let id = self
+                            .swarm
+                            .behaviour_mut()
+                            .request_response
+                            .send_request(&peer, bytes);
+                        self.sync_requests.insert(id);
                     }
                 }
             }
 
             Ok(SyncResponse::Blocks(remote_blocks)) => {
-                let remote_chain = Blockchain::new();
-                for block in remote_blocks {
-                    if block.index > 0 {
-                        let _ = self.sync_manager.record_download(&block);
+                let before = self.blockchain.len();
+                // Blocks we asked for are wanted as they come; any other
+                // strategy picks (and counts) the ones it keeps.
+                let on_demand = *self.sync_manager.strategy() == SyncStrategy::OnDemand;
+                let blocks = if on_demand {
+                    remote_blocks
+                } else {
+                    match self.sync_manager.blocks_to_sync(&self.blockchain, &remote_blocks) {
+                        Ok(blocks) => blocks.into_iter().cloned().collect(),
+                        Err(e) => {
+                            warn!("Not syncing from {peer}: {e}");
+                            Vec::new()
+                        }
+                    }
+                };
+                for block in blocks {
+                    if block.index <= self.blockchain.tip_index() {
+                        continue;
+                    }
+                    let block_index = block.index;
+                    if let Err(e) = self.check_transactions(&block) {
+                        warn!("Stopping sync from {peer}: {e}");
+                        self.reject_block(block_index, e.to_string());
+                        break;
+                    }
+                    match self.blockchain.append_block(without_verifications(block)) {
+                        Ok(block) => {
+                            self.chain_tx_ids.extend(block.transactions.iter().map(|tx| tx.id));
+                            if on_demand {
+                                self.sync_manager.record_download(block);
+                            }
+                        }
+                        Err(e) => {
+                            warn!("Stopping sync from {peer}: {e}");
+                            self.reject_block(block_index, e.to_string());
+                            break;
+                        }
+                    }
+                }
+                self.connect_orphans();
+                self.prune_chain();
+
+                let new_length = self.blockchain.len();
+                let blocks_applied = new_length - before;
+                if blocks_applied > 0 {
+                    info!("Chain synced to length {new_length} (+{blocks_applied})");
+                    let _ = self.event_tx.send(NodeEvent::ChainSynced {
+                        new_length,
+                        blocks_applied,
+                    });
+                }
+            }
+
+            Ok(SyncResponse::Asset { hash, data: Some(data) }) => {
+                if content_hash(&data) == hash {
+                    if self.mode != NodeMode::Gossip {
+                        self.assets.insert(data.clone());
+                    }
+                    let _ = self.event_tx.send(NodeEvent::AssetReceived { hash, data });
+                } else {
+                    warn!("Asset payload does not match its hash, discarding");
+                }
+            }
+
+            Ok(SyncResponse::Asset { data: None, .. }) => {
+                debug!("Peer does not hold the requested asset");
+            }
+
+            Ok(SyncResponse::BlockWithProof { header, proof }) => {
+                match MerkleProof::from_bytes(&proof) {
+                    Ok(proof) => {
+                        let _ = self
+                            .event_tx
+                            .send(NodeEvent::BlockProofReceived { header, proof });
+                    }
+                    Err(e) => warn!("Failed to decode block proof: {e}"),
+                }
+            }
+
+            Ok(SyncResponse::NotFound) => {
+                // A pruned peer answers this way when asked for blocks it has
+                // dropped, which leaves our chain stuck short of its tip.
+                warn!("{peer} does not hold the requested block or transaction");
+                let _ = self.event_tx.send(NodeEvent::Error(format!(
+                    "{peer} does not hold the requested block or transaction"
+                )));
+            }
+
+            Ok(SyncResponse::Mempool(ids)) => {
+                let _ = self.event_tx.send(NodeEvent::MempoolReceived { peer, ids });
+            }
+
+            Ok(SyncResponse::NotServing) => {
+                // Every connected peer is asked for its tip on connect, so a
+                // full node among them is already being synced from.
+                debug!("{peer} is gossip-only and does not serve the chain");
+                self.not_serving.insert(peer);
+            }
+
+            Ok(SyncResponse::VersionMismatch { supported }) => {
+                warn!("{peer} speaks sync wire version {supported}, not {WIRE_VERSION}");
+                let _ = self.event_tx.send(NodeEvent::Error(format!(
+                    "{peer} speaks sync wire version {supported}, not {WIRE_VERSION}"
+                )));
+            }
+
+            Err(e) => self.report_undecodable("sync response", Some(peer), &e),
+        }
+    }
+
+    /// Log a message that could not be decoded.  A version mismatch also
+    /// surfaces as [`NodeEvent::Error`], since it means the peer runs an
+    /// incompatible release rather than that one message was garbled.
+    fn report_undecodable(&mut self, what: &str, peer: Option<PeerId>, e: &DecodeError) {
+        let from = peer.map(|p| format!(" from {p}")).unwrap_or_default();
+        warn!("Failed to decode {what}{from}: {e}");
+        if let DecodeError::IncompatibleVersion(_) = e {
+            let _ = self.event_tx.send(NodeEvent::Error(format!("{what}{from}: {e}")));
+        }
+    }
+
+    /// Place a gossiped block relative to the local tip: connect it if it is
+    /// the next block, buffer it as an orphan if it is ahead, drop it if it is
+    /// a duplicate of something already on the chain.
+    fn handle_block(&mut self, block: Block, origin: Option<PeerId>) {
+        let tip = self.blockchain.tip_index();
+
+        if block.index == 0 || block.index > tip.saturating_add(MAX_ORPHAN_DISTANCE) {
+            warn!("Rejecting block with bogus index #{} (tip #{tip})", block.index);
+            self.reject_block(block.index, format!("index out of range (local tip is #{tip})"));
+            return;
+        }
+
+        if block.index <= tip {
+            debug!("Dropping duplicate block #{}", block.index);
+            return;
+        }
+
+        if block.index > tip + 1 {
+            debug!("Buffering orphan block #{}", block.index);
+            self.orphan_blocks.insert(block.index, (block, origin));
+            return;
+        }
+
+        let block_index = block.index;
+        if let Err(e) = self.connect_block(block, origin) {
+            warn!("{e}");
+            self.reject_block(block_index, e.to_string());
+            return;
+        }
+        self.connect_orphans();
+    }
+
+    /// Tell the consumer a block from a peer was refused, and why.
+    fn reject_block(&mut self, block_index: u64, reason: String) {
+        let _ = self.event_tx.send(NodeEvent::BlockRejected { block_index, reason });
+    }
+
+    /// Connect any buffered orphans that now follow the tip, then discard the
+    /// ones at or below it since they can no longer connect.
+    fn connect_orphans(&mut self) {
+        while let Some((block, origin)) = self
+            .blockchain
+            .tip_index()
+            .checked_add(1)
+            .and_then(|next| self.orphan_blocks.remove(&next))
+        {
+            let block_index = block.index;
+            if let Err(e) = self.connect_block(block, origin) {
+                warn!("{e}");
+                self.reject_block(block_index, e.to_string());
+                break;
+            }
+        }
+        self.orphan_blocks = match self.blockchain.tip_index().checked_add(1) {
+            Some(next) => self.orphan_blocks.split_off(&next),
+            None => BTreeMap::new(),
+        };
+    }
+
+    /// Append a block relayed by `origin` that extends the tip and announce it.
+    fn connect_block(&mut self, block: Block, origin: Option<PeerId>) -> Result<(), NodeError> {
+        let block_index = block.index;
+        self.check_transactions(&block)?;
+        let block = without_verifications(block);
+        self.blockchain.append_block(block.clone())?;
+        self.chain_tx_ids.extend(block.transactions.iter().map(|tx| tx.id));
+        let _ = self.event_tx.send(NodeEvent::BlockReceived { block: Box::new(block), origin });
+        self.prune_chain();
+
+        if self.mode == NodeMode::Full {
+            let _ = self.send_verification(block_index);
+        }
+        Ok(())
+    }
+
+    /// Full nodes refuse a block from a peer if any transaction in it fails
+    /// [`Transaction::validate`]; other modes relay and store it as is.
+    fn check_transactions(&self, block: &Block) -> Result<(), NodeError> {
+        if self.mode != NodeMode::Full {
+            return Ok(());
+        }
+        block.transactions.iter().try_for_each(|tx| {
+            tx.validate()
+                .map_err(|source| NodeError::InvalidTransaction { id: tx.id, source })
+        })
+    }
+
+    /// Prune finalised blocks that have fallen out of the `max_chain_blocks`
+    /// window, announcing how far the chain was pruned.
+    fn prune_chain(&mut self) {
+        let Some(cap) = self.max_chain_blocks else { return };
+        if self.blockchain.blocks().len() <= cap {
+            return;
+        }
+        // Keep genesis plus the newest `cap - 1` blocks.
+        let keep = cap.saturating_sub(1).max(1) as u64;
+        let below = self.blockchain.tip_index().saturating_sub(keep) + 1;
+        let candidates: Vec<(u64, Uuid)> = self
+            .blockchain
+            .blocks_range(1, below)
+            .iter()
+            .flat_map(|b| b.transactions.iter().map(move |tx| (b.index, tx.id)))
+            .collect();
+        let hashes: Vec<(u64, [u8; 32])> =
+            self.blockchain.blocks_range(1, below).iter().map(|b| (b.index, b.hash())).collect();
+        if let Some(up_to_index) = self.blockchain.prune_before(below) {
+            for (index, id) in candidates {
+                if index <= up_to_index {
+                    self.chain_tx_ids.remove(&id);
+                }
+            }
+            // A pruned block can't come back to be verified again.
+            let before = self.verified_blocks.len();
+            for (index, hash) in hashes {
+                if index <= up_to_index {
+                    self.verified_blocks.remove(&hash);
+                }
+            }
+            if self.verified_blocks.len() < before {
+                if let Some(dir) = &self.data_dir {
+                    let path = dir.join(verified::VERIFIED_FILE);
+                    if let Err(e) = verified::save(&path, &self.verified_blocks) {
+                        warn!("Could not rewrite the verified-block record: {e}");
+                    }
+                }
+            }
+            debug!("Pruned the chain up to block #{up_to_index}");
+            let _ = self.event_tx.send(NodeEvent::ChainPruned { up_to_index });
+        }
+    }
+
+    /// Re-seed Kademlia (and the discovered set) from the routing snapshot in
+    /// `data_dir`, if there is one.
+    fn load_routing_snapshot(&mut self) {
+        let Some(dir) = &self.data_dir else { return };
+        let peers = routing::load(&dir.join(routing::ROUTING_FILE));
+        if !peers.is_empty() {
+            info!("Loaded {} peer(s) from routing snapshot", peers.len());
+        }
+        for (peer_id, addrs) in peers {
+            if peer_id == self.local_peer_id {
+                continue;
+            }
+            for addr in &addrs {
+                self.swarm
+                    .behaviour_mut()
+                    .kademlia
+                    .add_address(&peer_id, addr.clone());
+            }
+            merge_addrs(self.discovered_peers.entry(peer_id).or_default(), addrs);
+        }
+    }
+
+    /// Write every peer in the Kademlia routing table plus any discovered
+    /// peers to the routing snapshot in `data_dir`.
+    fn save_routing_snapshot(&mut self) -> Result<(), NodeError> {
+        let Some(dir) = self.data_dir.clone() else { return Ok(()) };
+
+        let mut peers = self.discovered_peers.clone();
+        for bucket in self.swarm.behaviour_mut().kademlia.kbuckets() {
+            for entry in bucket.iter() {
+                let known = peers.entry(*entry.node.key.preimage()).or_default();
+                for addr in entry.node.value.iter() {
+                    if !known.contains(addr) {
+                        known.push(addr.clone());
                     }
                 }
-                if self.blockchain.sync_from(&remote_chain) {
-                    let new_length = self.blockchain.len();
-                    info!("Chain synced to length {new_length}");
-                    let _ = self.event_tx.send(NodeEvent::ChainSynced { new_length });
+            }
+        }
+        routing::save(&dir.join(routing::ROUTING_FILE), &peers)?;
+        Ok(())
+    }
+
+    /// Tell the consumer about addresses newly learned for `peer_id`, and
+    /// count the peer towards a running discovery scan.
+    fn report_discovered(
+        &mut self,
+        peer_id: PeerId,
+        addrs: Vec<Multiaddr>,
+        source: DiscoverySource,
+    ) {
+        if let Some((_, found)) = &mut self.discovery_scan {
+            found.insert(peer_id);
+        }
+        let _ = self.event_tx.send(NodeEvent::PeerDiscovered { peer_id, addrs, source });
+    }
+
+    /// Resolve a [`Node::find_peer`] query: dial the first record found, or
+    /// report failure once the query ends without one.
+    fn handle_peer_lookup(&mut self, id: QueryId, result: kad::GetRecordResult) {
+        let record = match result {
+            Ok(GetRecordOk::FoundRecord(PeerRecord { record, .. })) => record,
+            Ok(GetRecordOk::FinishedWithNoAdditionalRecord { .. }) | Err(_) => {
+                if let Some(peer_id) = self.pending_lookups.remove(&id) {
+                    let _ = self
+                        .event_tx
+                        .send(NodeEvent::Error(format!("no address record found for {peer_id}")));
                 }
+                return;
+            }
+        };
+
+        let Some(peer_id) = self.pending_lookups.remove(&id) else {
+            return;
+        };
+        if let Some(mut query) = self.swarm.behaviour_mut().kademlia.query_mut(&id) {
+            query.finish();
+        }
+
+        let addrs = match decode_addresses(&record.value) {
+            Ok(addrs) => addrs,
+            Err(e) => {
+                warn!("Malformed address record for {peer_id}: {e}");
+                return;
             }
+        };
+        let _ = self.event_tx.send(NodeEvent::PeerResolved {
+            peer_id,
+            addrs: addrs.clone(),
+        });
 
-            Err(e) => warn!("Failed to decode sync response: {e}"),
+        if peer_id != self.local_peer_id && !self.connected_peers_map.contains_key(&peer_id) {
+            for addr in &addrs {
+                self.swarm
+                    .behaviour_mut()
+                    .kademlia
+                    .add_address(&peer_id, addr.clone());
+            }
+            let opts = DialOpts::peer_id(peer_id).addresses(addrs).build();
+            if let Err(e) = self.swarm.dial(opts) {
+                warn!("Failed to dial resolved peer {peer_id}: {e}");
+            }
         }
     }
 
     fn request_chain_tip(&mut self, peer: PeerId) {
         if let Ok(bytes) = crate::protocol::encode_request(&SyncRequest::ChainTip) {
-            self.swarm
+            let id = self
+                .swarm
                 .behaviour_mut()
                 .request_response
                 .send_request(&peer, bytes);
+            self.sync_requests.insert(id);
+        }
+    }
+
+    /// Keep a copy of a transaction's payload in the asset store unless this
+    /// is a gossip-only node.
+    fn store_payload(&mut self, tx: &Transaction) {
+        if self.mode != NodeMode::Gossip && !tx.payload.is_empty() {
+            self.assets.insert(tx.payload.clone());
         }
     }
 
+    /// Unix time before which a transaction has outlived `mempool_ttl`.
+    fn mempool_cutoff(&self) -> i64 {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as i64);
+        now.saturating_sub(self.mempool_ttl.as_secs() as i64)
+    }
+
+    /// Drop pending transactions older than the mempool TTL, then the oldest
+    /// by timestamp until the mempool is back within its cap.  Emits
+    /// [`NodeEvent::TransactionsEvicted`] when anything was dropped.
+    fn prune_mempool(&mut self) {
+        let cutoff = self.mempool_cutoff();
+
+        let mut evicted = Vec::new();
+        self.pending_transactions.retain(|tx| {
+            let keep = tx.timestamp >= cutoff;
+            if !keep {
+                evicted.push(tx.id);
+            }
+            keep
+        });
+
+        let excess = self.pending_transactions.len().saturating_sub(self.max_mempool);
+        if excess > 0 {
+            // Oldest first; ties keep arrival order.
+            let mut by_age: Vec<(i64, usize)> = self
+                .pending_transactions
+                .iter()
+                .enumerate()
+                .map(|(i, tx)| (tx.timestamp, i))
+                .collect();
+            by_age.sort_unstable();
+            let dropped: HashSet<usize> = by_age[..excess].iter().map(|(_, i)| *i).collect();
+
+            let mut i = 0;
+            self.pending_transactions.retain(|tx| {
+                let keep = !dropped.contains(&i);
+                i += 1;
+                if !keep {
+                    evicted.push(tx.id);
+                }
+                keep
+            });
+        }
+
+        if !evicted.is_empty() {
+            warn!("Evicted {} transactions from the mempool", evicted.len());
+            let _ = self.event_tx.send(NodeEvent::TransactionsEvicted(evicted));
+        }
+    }
+
+    fn ensure_not_observer(&self, action: &'static str) -> Result<(), NodeError> {
+        if self.mode == NodeMode::Observer {
+            return Err(NodeError::Observer(action));
+        }
+        Ok(())
+    }
+
     fn maybe_form_block(&mut self) -> Result<(), NodeError> {
         if self.pending_transactions.len() >= BLOCK_BATCH_SIZE {
             self.form_block()?;
@@ -565,6 +2321,30 @@ impl Node {
     }
 }
 
+/// Append each of `addrs` not already in `known`, returning the ones added.
+fn merge_addrs(
+    known: &mut Vec<Multiaddr>,
+    addrs: impl IntoIterator<Item = Multiaddr>,
+) -> Vec<Multiaddr> {
+    let mut added = Vec::new();
+    for addr in addrs {
+        if !known.contains(&addr) {
+            known.push(addr.clone());
+            added.push(addr);
+        }
+    }
+    added
+}
+
+/// `block` as received from a peer, minus the verifications it came with.
+/// Only verifications this node receives itself count towards finality, or
+/// a peer could ship a block already finalised.
+fn without_verifications(mut block: Block) -> Block {
+    block.verifications.clear();
+    block.verified_weight = 0;
+    block
+}
+
 /// Extract the TCP/UDP port from a multiaddr, if present.
 fn addr_port(addr: &Multiaddr) -> Option<u16> {
     use libp2p::multiaddr::Protocol;
@@ -576,3 +2356,2004 @@ fn addr_port(addr: &Multiaddr) -> Option<u16> {
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p::multiaddr::Protocol;
+    use sp_transaction::TransactionType;
+
+    /// A node on an OS-assigned port.  Tests dial explicitly; dialling
+    /// whatever mDNS finds on the host would make them racy.
+    async fn test_node() -> (Node, mpsc::Receiver<NodeEvent>) {
+        let config = NodeConfig {
+            port: 0,
+            auto_connect: AutoConnectPolicy::None,
+            ..NodeConfig::default()
+        };
+        Node::new(config).await.unwrap()
+    }
+
+    /// A chain sharing `node`'s genesis, extended by `n` blocks.
+    fn extended_chain(node: &Node, n: usize) -> Blockchain {
+        let mut chain = node.blockchain().clone();
+        for _ in 0..n {
+            let tx = Transaction::new(TransactionType::PostCreated, vec![]);
+            chain.add_block(vec![tx]).unwrap();
+        }
+        chain
+    }
+
+    /// Indices of the blocks received, and how many were rejected.
+    fn block_events(rx: &mut mpsc::Receiver<NodeEvent>) -> (Vec<u64>, usize) {
+        let (mut received, mut rejected) = (Vec::new(), 0);
+        while let Ok(ev) = rx.try_recv() {
+            match ev {
+                NodeEvent::BlockReceived { block, .. } => received.push(block.index),
+                NodeEvent::BlockRejected { .. } => rejected += 1,
+                _ => {}
+            }
+        }
+        (received, rejected)
+    }
+
+    fn kademlia_addresses(node: &mut Node, peer_id: PeerId) -> Vec<Multiaddr> {
+        let mut found = Vec::new();
+        for bucket in node.swarm.behaviour_mut().kademlia.kbuckets() {
+            for entry in bucket.iter() {
+                if *entry.node.key.preimage() == peer_id {
+                    found.extend(entry.node.value.iter().cloned());
+                }
+            }
+        }
+        found
+    }
+
+    fn temp_data_dir() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("sp-node-test-{}", Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn routing_snapshot_reseeds_kademlia() {
+        let dir = temp_data_dir();
+        let peer = PeerId::random();
+        let addr: Multiaddr = "/ip4/10.0.0.7/tcp/51025".parse().unwrap();
+        routing::save(
+            &dir.join(routing::ROUTING_FILE),
+            &HashMap::from([(peer, vec![addr.clone()])]),
+        )
+        .unwrap();
+
+        let config = NodeConfig {
+            port: 0,
+            data_dir: Some(dir.clone()),
+            ..NodeConfig::default()
+        };
+        let (mut node, _rx) = Node::new(config).await.unwrap();
+
+        // Kademlia stores addresses with the peer id appended.
+        let expected = addr.with_p2p(peer).unwrap();
+        assert_eq!(kademlia_addresses(&mut node, peer), vec![expected]);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn block_metadata_is_stored_and_keeps_the_chain_valid() {
+        let dir = temp_data_dir();
+        let config = NodeConfig {
+            port: 0,
+            block_metadata: Some(b"eu-west/alice".to_vec()),
+            ..NodeConfig::default()
+        };
+        let (mut node, _rx) = Node::new(config).await.unwrap();
+        node.pending_transactions
+            .push(Transaction::new(TransactionType::PostCreated, vec![]));
+        // Forms the block, then finds nobody to send the verification to.
+        let _ = node.form_block();
+
+        let path = dir.join(chain::CHAIN_FILE);
+        chain::save(&path, node.blockchain()).unwrap();
+        let stored = chain::load(&path).unwrap().unwrap();
+        assert!(stored.is_valid());
+        assert_eq!(stored.tip().metadata.as_deref(), Some(&b"eu-west/alice"[..]));
+        assert_eq!(stored.tip().hash(), node.blockchain().tip().hash());
+        assert_eq!(stored.get_block(0).unwrap().metadata, None);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn shutdown_during_discovery_aborts_pending_dials() {
+        // Accepts connections and never answers, so a dial to it stays
+        // pending until its handshake times out.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                held.push(socket);
+            }
+        });
+
+        let (mut node, _rx) = test_node().await;
+        node.discovery_mode = DiscoveryMode::Both;
+        node.auto_connect = AutoConnectPolicy::All;
+        let silent = format!("/ip4/127.0.0.1/tcp/{port}").parse().unwrap();
+        let mdns = libp2p::mdns::Event::Discovered(vec![(PeerId::random(), silent)]);
+        node.handle_swarm_event(SwarmEvent::Behaviour(SpBehaviourEvent::Mdns(mdns))).await;
+        node.trigger_discovery(None);
+        let pending = |node: &Node| node.swarm.network_info().connection_counters().num_pending();
+        assert_eq!(pending(&node), 1);
+
+        node.shutdown().await.unwrap();
+        assert!(node.discovery_scan.is_none());
+        assert!(node.auto_dials.is_empty());
+        tokio::time::timeout(std::time::Duration::from_secs(2), async {
+            while pending(&node) > 0 {
+                node.step().await;
+            }
+        })
+        .await
+        .expect("dial still pending after shutdown");
+    }
+
+    #[tokio::test]
+    async fn status_snapshot_matches_the_node() {
+        let (mut node, _rx) = test_node().await;
+        let addr = loopback_addr(&mut node).await;
+        node.pending_transactions
+            .push(Transaction::new(TransactionType::PostCreated, vec![]));
+        let _ = node.form_block();
+        for _ in 0..2 {
+            node.pending_transactions
+                .push(Transaction::new(TransactionType::PostCreated, vec![]));
+        }
+        node.discovery_mode = DiscoveryMode::Both;
+        let found = vec![(PeerId::random(), "/ip4/10.0.0.1/tcp/4001".parse().unwrap())];
+        let mdns = libp2p::mdns::Event::Discovered(found);
+        node.handle_swarm_event(SwarmEvent::Behaviour(SpBehaviourEvent::Mdns(mdns))).await;
+
+        let status = node.status();
+        assert_eq!(status.peer_id, node.peer_id());
+        assert!(status.listen_addrs.contains(&addr));
+        assert_eq!((status.connected_peers, status.discovered_peers), (0, 1));
+        assert_eq!(status.chain_length, 2);
+        assert_eq!(status.tip_hash, node.blockchain().tip().hash());
+        assert_eq!(status.last_finalised, None);
+        assert_eq!(status.pending_txs, 2);
+        assert_eq!(status.mode, NodeMode::Full);
+        assert_eq!(status.sync_strategy, SyncStrategy::OnDemand);
+        assert_eq!(status.discovery_mode, DiscoveryMode::Both);
+        assert_eq!(status.network_id, sp_blockchain::DEFAULT_NETWORK_ID);
+        assert_eq!(status.genesis_hash, node.blockchain().genesis_hash());
+    }
+
+    #[tokio::test]
+    async fn node_keeps_its_files_under_the_data_dir() {
+        let dir = temp_data_dir();
+        let config = NodeConfig {
+            port: 0,
+            data_dir: Some(dir.clone()),
+            ..NodeConfig::default()
+        };
+        let (mut node, _rx) = Node::new(config.clone()).await.unwrap();
+        let peer_id = node.peer_id();
+        node.pending_transactions
+            .push(Transaction::new(TransactionType::PostCreated, vec![]));
+        let _ = node.form_block();
+        node.shutdown().await.unwrap();
+
+        for file in [
+            identity::IDENTITY_FILE,
+            chain::CHAIN_FILE,
+            routing::ROUTING_FILE,
+            verified::VERIFIED_FILE,
+        ] {
+            assert!(dir.join(file).is_file(), "{file} missing");
+        }
+
+        let (restarted, _rx) = Node::new(config).await.unwrap();
+        assert_eq!(restarted.peer_id(), peer_id);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn chain_is_restored_after_shutdown() {
+        let dir = temp_data_dir();
+        let config = NodeConfig {
+            port: 0,
+            data_dir: Some(dir.clone()),
+            ..NodeConfig::default()
+        };
+        let (mut node, _rx) = Node::new(config.clone()).await.unwrap();
+        node.pending_transactions
+            .push(Transaction::new(TransactionType::PostCreated, vec![]));
+        let _ = node.form_block();
+        let tip = node.blockchain().tip().hash();
+        node.shutdown().await.unwrap();
+
+        let (restarted, _rx) = Node::new(config).await.unwrap();
+        assert_eq!(restarted.blockchain().len(), 2);
+        assert_eq!(restarted.blockchain().tip().hash(), tip);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn shutdown_writes_routing_snapshot() {
+        let dir = temp_data_dir();
+        let config = NodeConfig {
+            port: 0,
+            data_dir: Some(dir.clone()),
+            ..NodeConfig::default()
+        };
+        let (mut node, _rx) = Node::new(config).await.unwrap();
+        let peer = PeerId::random();
+        let addr: Multiaddr = "/ip4/10.0.0.8/tcp/51025".parse().unwrap();
+        node.swarm.behaviour_mut().kademlia.add_address(&peer, addr.clone());
+
+        node.shutdown().await.unwrap();
+
+        let saved = routing::load(&dir.join(routing::ROUTING_FILE));
+        assert_eq!(saved.get(&peer), Some(&vec![addr.with_p2p(peer).unwrap()]));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn shutdown_seals_pending_transactions_when_asked() {
+        let dir = temp_data_dir();
+        let config = NodeConfig {
+            port: 0,
+            seal_on_shutdown: true,
+            data_dir: Some(dir.clone()),
+            ..NodeConfig::default()
+        };
+        let (mut node, _rx) = Node::new(config).await.unwrap();
+        let txs: Vec<Transaction> = (0..3)
+            .map(|_| Transaction::new(TransactionType::PostCreated, vec![]))
+            .collect();
+        node.pending_transactions.extend(txs.iter().cloned());
+
+        node.shutdown().await.unwrap();
+
+        assert!(node.pending_transactions.is_empty());
+        let stored = chain::load(&dir.join(chain::CHAIN_FILE)).unwrap().unwrap();
+        assert_eq!(stored.len(), 2);
+        let sealed: HashSet<Uuid> = stored.blocks()[1].transactions.iter().map(|t| t.id).collect();
+        assert_eq!(sealed, txs.iter().map(|t| t.id).collect());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn shutdown_leaves_pending_transactions_by_default() {
+        let (mut node, _rx) = test_node().await;
+        node.pending_transactions
+            .push(Transaction::new(TransactionType::PostCreated, vec![]));
+
+        node.shutdown().await.unwrap();
+
+        assert_eq!(node.blockchain().len(), 1);
+        assert_eq!(node.pending_transactions.len(), 1);
+    }
+
+    /// Answers every TXT lookup with `records`, or fails if there are none.
+    struct FixedTxt(Option<Vec<String>>);
+
+    #[async_trait::async_trait]
+    impl TxtResolver for FixedTxt {
+        async fn txt(&self, name: &str) -> std::io::Result<Vec<String>> {
+            assert_eq!(name, "_dnsaddr.bootstrap.example.org");
+            self.0.clone().ok_or_else(|| std::io::Error::other("no name server"))
+        }
+    }
+
+    #[tokio::test]
+    async fn dnsaddr_records_join_the_bootstrap_dials() {
+        let configured: Multiaddr = "/ip4/10.0.0.1/tcp/51025".parse().unwrap();
+        let peer = PeerId::random();
+        let published: Multiaddr = format!("/ip4/10.0.0.2/tcp/51025/p2p/{peer}").parse().unwrap();
+        let resolver = FixedTxt(Some(vec![
+            format!("dnsaddr={published}"),
+            "dnsaddr=not-a-multiaddr".to_string(),
+            "v=spf1 -all".to_string(),
+        ]));
+        let (mut node, _rx) = test_node().await;
+
+        node.bootstrap(vec![configured.clone()], Some("bootstrap.example.org"), &resolver)
+            .await;
+
+        let dialled: HashSet<Multiaddr> = node.user_dials.values().flatten().cloned().collect();
+        assert_eq!(dialled, HashSet::from([configured.clone(), published]));
+        let known = node.swarm.behaviour_mut().kademlia.kbuckets().any(|bucket| {
+            bucket.iter().any(|entry| *entry.node.key.preimage() == peer)
+        });
+        assert!(known, "published peer should seed Kademlia");
+
+        // A failed lookup still dials the configured peers.
+        let (mut node, _rx) = test_node().await;
+        node.bootstrap(vec![configured.clone()], Some("bootstrap.example.org"), &FixedTxt(None))
+            .await;
+        let dialled: Vec<&Multiaddr> = node.user_dials.values().flatten().collect();
+        assert_eq!(dialled, vec![&configured]);
+    }
+
+    #[tokio::test]
+    async fn block_is_verified_only_once() {
+        let (mut node, _rx) = test_node().await;
+        node.pending_transactions
+            .push(Transaction::new(TransactionType::PostCreated, vec![]));
+        // No peers to publish to, but the local verification still counts.
+        let _ = node.form_block();
+        let _ = node.send_verification(1);
+        let _ = node.send_verification(1);
+
+        let block = node.blockchain().get_block(1).unwrap();
+        assert_eq!(block.verifications.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn verified_blocks_survive_a_restart() {
+        let dir = temp_data_dir();
+        let config = NodeConfig {
+            port: 0,
+            data_dir: Some(dir.clone()),
+            ..NodeConfig::default()
+        };
+
+        let (mut first, _rx) = Node::new(config.clone()).await.unwrap();
+        first.pending_transactions
+            .push(Transaction::new(TransactionType::PostCreated, vec![]));
+        let _ = first.form_block();
+        let mut block = first.blockchain().get_block(1).unwrap().clone();
+
+        // A restarted node over the same data directory must not verify the
+        // block a second time.
+        let (mut second, _rx) = Node::new(config).await.unwrap();
+        block.prev_hash = second.blockchain().tip().hash();
+        block.verifications.clear();
+        block.verified_weight = 0;
+        second.blockchain.append_block(block).unwrap();
+        let _ = second.send_verification(1);
+
+        assert!(second.blockchain().get_block(1).unwrap().verifications.is_empty());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn a_replaced_block_at_a_verified_index_is_verified() {
+        let (mut node, _rx) = test_node().await;
+        node.pending_transactions
+            .push(Transaction::new(TransactionType::PostCreated, vec![]));
+        let _ = node.form_block();
+        assert_eq!(node.blockchain().get_block(1).unwrap().verifications.len(), 1);
+
+        // A reorg puts a different block at index 1; it hasn't been verified.
+        let mut other = Blockchain::new();
+        other.add_block(vec![Transaction::new(TransactionType::VoteCast, vec![])]).unwrap();
+        node.blockchain = other;
+        let _ = node.send_verification(1);
+        let _ = node.send_verification(1);
+
+        assert_eq!(node.blockchain().get_block(1).unwrap().verifications.len(), 1);
+        assert_eq!(node.verified_blocks.len(), 2);
+    }
+
+    fn pending_dials(node: &Node) -> u32 {
+        node.swarm
+            .network_info()
+            .connection_counters()
+            .num_pending_outgoing()
+    }
+
+    #[tokio::test]
+    async fn dial_peer_id_uses_discovered_address() {
+        let (mut node, _rx) = test_node().await;
+        let peer = PeerId::random();
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/1".parse().unwrap();
+        node.discovered_peers.insert(peer, vec![addr]);
+
+        node.dial_peer_id(peer).unwrap();
+        assert_eq!(pending_dials(&node), 1);
+    }
+
+    #[tokio::test]
+    async fn dial_peer_id_uses_kademlia_address() {
+        let (mut node, _rx) = test_node().await;
+        let peer = PeerId::random();
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/1".parse().unwrap();
+        node.swarm.behaviour_mut().kademlia.add_address(&peer, addr);
+
+        node.dial_peer_id(peer).unwrap();
+        assert_eq!(pending_dials(&node), 1);
+    }
+
+    #[tokio::test]
+    async fn dial_peer_id_without_address_fails() {
+        let (mut node, _rx) = test_node().await;
+        let err = node.dial_peer_id(PeerId::random()).unwrap_err();
+        assert!(matches!(err, NodeError::NoKnownAddress(_)));
+        assert_eq!(pending_dials(&node), 0);
+    }
+
+    #[tokio::test]
+    async fn gossip_from_another_wire_version_is_reported() {
+        let (mut node, mut rx) = test_node().await;
+        let tx = Transaction::new(TransactionType::PostCreated, vec![]);
+        let mut bytes = encode_gossip(&GossipMessage::Transaction(tx)).unwrap();
+        bytes[0] = WIRE_VERSION + 1;
+
+        node.handle_gossip_message(None, None, &bytes).await;
+
+        match rx.try_recv() {
+            Ok(NodeEvent::Error(msg)) => assert!(msg.contains("incompatible message version")),
+            other => panic!("expected a version error, got {other:?}"),
+        }
+        assert!(node.pending_transactions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn sync_request_in_the_same_version_is_answered() {
+        let (mut node, _rx) = test_node().await;
+        let request = crate::protocol::encode_request(&SyncRequest::ChainTip).unwrap();
+
+        let response = node.respond_to_request(PeerId::random(), &request);
+        assert!(matches!(response, Some(SyncResponse::ChainTip { tip_index: 0 })));
+    }
+
+    #[tokio::test]
+    async fn sync_request_in_another_version_gets_a_mismatch_reply() {
+        let (mut server, _rx) = test_node().await;
+        let (mut client, mut rx) = test_node().await;
+        let mut request = crate::protocol::encode_request(&SyncRequest::ChainTip).unwrap();
+        request[0] = WIRE_VERSION + 1;
+
+        let response = server.respond_to_request(client.peer_id(), &request).unwrap();
+        assert!(matches!(
+            response,
+            SyncResponse::VersionMismatch { supported } if supported == WIRE_VERSION
+        ));
+
+        let bytes = encode_response(&response).unwrap();
+        client.apply_sync_response(server.peer_id(), &bytes).await;
+        match rx.try_recv() {
+            Ok(NodeEvent::Error(msg)) => assert!(msg.contains("sync wire version")),
+            other => panic!("expected a version error, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn transaction_batch_is_unpacked_into_the_mempool() {
+        let (mut node, mut rx) = test_node().await;
+        let txs: Vec<Transaction> = (0..3)
+            .map(|i| Transaction::new(TransactionType::PostCreated, vec![i]))
+            .collect();
+        let msg = GossipMessage::TransactionBatch(txs.clone());
+
+        node.handle_gossip_message(None, None, &encode_gossip(&msg).unwrap()).await;
+
+        let ids = |txs: &[Transaction]| txs.iter().map(|tx| tx.id).collect::<Vec<_>>();
+        assert_eq!(ids(&node.pending_transactions), ids(&txs));
+        let mut received = 0;
+        while let Ok(ev) = rx.try_recv() {
+            if matches!(ev, NodeEvent::TransactionReceived { .. }) {
+                received += 1;
+            }
+        }
+        assert_eq!(received, 3);
+    }
+
+    #[tokio::test]
+    async fn private_payloads_travel_encrypted() {
+        let key = PayloadKey::new([7; 32]);
+        let config = NodeConfig {
+            port: 0,
+            payload_key: Some(key.clone()),
+            ..NodeConfig::default()
+        };
+        let (sender, _) = Node::new(config).await.unwrap();
+        let (mut relay, mut rx) = test_node().await;
+
+        let plaintext = br#"{"bio":"private"}"#.to_vec();
+        let tx = Transaction::new(TransactionType::UserEdited, plaintext.clone());
+        let sealed = sender.seal_payload(tx.clone()).unwrap();
+        let msg = GossipMessage::Transaction(sealed.clone());
+        relay.handle_gossip_message(None, None, &encode_gossip(&msg).unwrap()).await;
+
+        let received = std::iter::from_fn(|| rx.try_recv().ok())
+            .find_map(|ev| match ev {
+                NodeEvent::TransactionReceived { tx, .. } => Some(tx),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(received.id, tx.id);
+        assert_ne!(received.payload, plaintext);
+        assert_eq!(received.hash().unwrap(), sealed.hash().unwrap());
+        assert_eq!(received.decrypt_payload(&key).unwrap(), plaintext);
+        assert!(received.decrypt_payload(&PayloadKey::new([8; 32])).is_err());
+    }
+
+    #[tokio::test]
+    async fn sealed_then_signed_transactions_can_be_broadcast() {
+        let key = PayloadKey::new([7; 32]);
+        let config = NodeConfig {
+            port: 0,
+            dry_run: true,
+            payload_key: Some(key.clone()),
+            ..NodeConfig::default()
+        };
+        let (mut node, _rx) = Node::new(config).await.unwrap();
+
+        let plaintext = b"signed and private".to_vec();
+        let mut unsealed = Transaction::new(TransactionType::PostCreated, plaintext.clone());
+        unsealed.sign(vec![1; sp_transaction::SIGNATURE_LEN]);
+        assert!(node.broadcast_transaction(unsealed).is_err());
+
+        let tx = Transaction::new(TransactionType::PostCreated, plaintext.clone());
+        let mut sealed = node.seal_payload(tx).unwrap();
+        sealed.sign(vec![1; sp_transaction::SIGNATURE_LEN]);
+        node.broadcast_transaction(sealed.clone()).unwrap();
+        assert_eq!(node.pending_transactions, vec![sealed.clone()]);
+        assert_eq!(sealed.decrypt_payload(&key).unwrap(), plaintext);
+    }
+
+    #[tokio::test]
+    async fn dropped_gossip_transactions_are_reported_with_a_reason() {
+        let config = NodeConfig {
+            port: 0,
+            mempool_ttl: std::time::Duration::from_secs(60),
+            max_block_transactions: 1,
+            ..NodeConfig::default()
+        };
+        let (mut node, mut rx) = Node::new(config).await.unwrap();
+        let sealed = Transaction::new(TransactionType::PostCreated, b"sealed".to_vec());
+        node.accept_transaction(sealed.clone(), None);
+        let _ = node.form_block();
+        assert_eq!(node.blockchain().len(), 2);
+        let pending = Transaction::new(TransactionType::PostCreated, b"pending".to_vec());
+        node.accept_transaction(pending.clone(), None);
+        while rx.try_recv().is_ok() {}
+
+        let stale = tx_aged(120);
+        let mut malformed = Transaction::new(TransactionType::PostCreated, b"bad".to_vec());
+        malformed.sign(vec![1; 3]);
+        for tx in [sealed.clone(), pending.clone(), stale.clone(), malformed.clone()] {
+            let msg = GossipMessage::Transaction(tx);
+            node.handle_gossip_message(None, None, &encode_gossip(&msg).unwrap()).await;
+        }
+
+        let mut rejected = Vec::new();
+        while let Ok(ev) = rx.try_recv() {
+            if let NodeEvent::TransactionRejected { id, reason } = ev {
+                rejected.push((id, reason));
+            }
+        }
+        assert_eq!(
+            rejected,
+            vec![
+                (sealed.id, "replayed: already in the chain".to_string()),
+                (pending.id, "replayed: already pending".to_string()),
+                (stale.id, "expired".to_string()),
+                (malformed.id, "invalid: invalid signature".to_string()),
+            ]
+        );
+        assert_eq!(node.pending_transactions, vec![pending]);
+    }
+
+    fn evicted_events(rx: &mut mpsc::Receiver<NodeEvent>) -> Vec<Uuid> {
+        let mut evicted = Vec::new();
+        while let Ok(ev) = rx.try_recv() {
+            if let NodeEvent::TransactionsEvicted(ids) = ev {
+                evicted.extend(ids);
+            }
+        }
+        evicted
+    }
+
+    fn tx_aged(secs: i64) -> Transaction {
+        let mut tx = Transaction::new(TransactionType::PostCreated, vec![]);
+        tx.timestamp -= secs;
+        tx
+    }
+
+    #[tokio::test]
+    async fn mempool_over_capacity_evicts_the_oldest() {
+        let config = NodeConfig { port: 0, max_mempool: 3, ..NodeConfig::default() };
+        let (mut node, mut rx) = Node::new(config).await.unwrap();
+        let txs = [tx_aged(20), tx_aged(40), tx_aged(10), tx_aged(30)];
+        for tx in &txs {
+            node.accept_transaction(tx.clone(), None);
+        }
+
+        node.prune_mempool();
+
+        assert_eq!(evicted_events(&mut rx), vec![txs[1].id]);
+        let kept: Vec<Uuid> = node.pending_transactions.iter().map(|tx| tx.id).collect();
+        assert_eq!(kept, vec![txs[0].id, txs[2].id, txs[3].id]);
+    }
+
+    #[tokio::test]
+    async fn expired_transactions_leave_the_mempool() {
+        let config = NodeConfig {
+            port: 0,
+            mempool_ttl: std::time::Duration::from_secs(60),
+            ..NodeConfig::default()
+        };
+        let (mut node, mut rx) = Node::new(config).await.unwrap();
+        let (stale, fresh) = (tx_aged(120), tx_aged(5));
+        node.accept_transaction(stale.clone(), None);
+        node.accept_transaction(fresh.clone(), None);
+
+        node.prune_mempool();
+
+        assert_eq!(evicted_events(&mut rx), vec![stale.id]);
+        assert_eq!(node.pending_transactions, vec![fresh]);
+    }
+
+    #[tokio::test]
+    async fn capped_block_takes_highest_priority_first() {
+        let config = NodeConfig { port: 0, max_block_transactions: 2, ..NodeConfig::default() };
+        let (mut node, _rx) = Node::new(config).await.unwrap();
+        let txs: Vec<Transaction> = [1, 5, 3]
+            .into_iter()
+            .map(|p| {
+                Transaction::builder(TransactionType::PostCreated)
+                    .priority(p)
+                    .build()
+                    .unwrap()
+            })
+            .collect();
+        node.pending_transactions.extend(txs.iter().cloned());
+
+        let _ = node.form_block();
+
+        let block = node.blockchain().get_block(1).unwrap();
+        let included: Vec<u32> = block.transactions.iter().map(|tx| tx.priority).collect();
+        assert_eq!(included, vec![5, 3]);
+        assert_eq!(node.pending_transactions, vec![txs[0].clone()]);
+    }
+
+    /// Poll `node`'s swarm until it reports a loopback listen address.
+    async fn loopback_addr(node: &mut Node) -> Multiaddr {
+        loop {
+            let event = node.swarm.select_next_some().await;
+            if let SwarmEvent::NewListenAddr { address, .. } = &event {
+                if address.iter().any(|p| p == Protocol::Ip4([127, 0, 0, 1].into())) {
+                    return address.clone();
+                }
+            }
+            node.handle_swarm_event(event).await;
+        }
+    }
+
+    /// An in-memory node (see [`Node::new_in_memory`]) and the address it
+    /// listens on.
+    async fn memory_node() -> (Node, mpsc::Receiver<NodeEvent>, Multiaddr) {
+        memory_node_with(NodeConfig::default()).await
+    }
+
+    /// Like [`memory_node`], with `config` other than its discovery mode.
+    async fn memory_node_with(config: NodeConfig) -> (Node, mpsc::Receiver<NodeEvent>, Multiaddr) {
+        let config = NodeConfig { discovery_mode: DiscoveryMode::KademliaDht, ..config };
+        let (mut node, rx) = Node::new_in_memory(config).await.unwrap();
+        loop {
+            let event = node.swarm.select_next_some().await;
+            let addr = match &event {
+                SwarmEvent::NewListenAddr { address, .. } => Some(address.clone()),
+                _ => None,
+            };
+            node.handle_swarm_event(event).await;
+            if let Some(addr) = addr {
+                return (node, rx, addr);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn gossiped_transaction_reaches_a_peer_in_memory() {
+        let (mut a, _a_rx, _) = memory_node().await;
+        let (mut b, mut b_rx, b_addr) = memory_node().await;
+        assert!(b_addr.iter().any(|p| matches!(p, Protocol::Memory(_))));
+
+        a.dial(b_addr).unwrap();
+        drive_until(&mut a, &mut b, |a, b| {
+            a.topic_peers(TOPIC_TX) == 1 && b.topic_peers(TOPIC_TX) == 1
+        })
+        .await;
+
+        let tx = Transaction::new(TransactionType::PostCreated, b"over memory".to_vec());
+        a.broadcast_transaction(tx.clone()).unwrap();
+        drive_until(&mut a, &mut b, |_, b| b.pending_transactions.contains(&tx)).await;
+
+        let origin = std::iter::from_fn(|| b_rx.try_recv().ok()).find_map(|ev| match ev {
+            NodeEvent::TransactionReceived { tx: got, origin } if got == tx => Some(origin),
+            _ => None,
+        });
+        assert_eq!(origin, Some(Some(a.peer_id())), "B should report who sent the tx");
+    }
+
+    #[tokio::test]
+    async fn idle_connections_close_after_the_timeout() {
+        let config = NodeConfig {
+            discovery_mode: DiscoveryMode::KademliaDht,
+            idle_timeout: Some(std::time::Duration::from_millis(500)),
+            ..NodeConfig::default()
+        };
+        let (mut node, _rx) = Node::new_in_memory(config).await.unwrap();
+        let addr = loop {
+            match node.swarm.select_next_some().await {
+                SwarmEvent::NewListenAddr { address, .. } => break address,
+                event => node.handle_swarm_event(event).await,
+            }
+        };
+
+        // A bare peer speaks none of the node's protocols, so nothing but the
+        // node's idle timeout keeps the connection open or closes it.
+        let mut peer = libp2p::SwarmBuilder::with_new_identity()
+            .with_tokio()
+            .with_other_transport(|key| {
+                use libp2p::{
+                    core::{transport::MemoryTransport, upgrade::Version},
+                    Transport as _,
+                };
+                MemoryTransport::default()
+                    .upgrade(Version::V1)
+                    .authenticate(libp2p::noise::Config::new(key).unwrap())
+                    .multiplex(libp2p::yamux::Config::default())
+            })
+            .unwrap()
+            .with_behaviour(|_| libp2p::ping::Behaviour::default())
+            .unwrap()
+            .with_swarm_config(|cfg| {
+                cfg.with_idle_connection_timeout(std::time::Duration::from_secs(60))
+            })
+            .build();
+        peer.dial(addr).unwrap();
+
+        let closed = async {
+            let mut opened = None;
+            loop {
+                tokio::select! {
+                    event = node.swarm.select_next_some() => match event {
+                        SwarmEvent::ConnectionEstablished { .. } => {
+                            opened = Some(std::time::Instant::now());
+                        }
+                        SwarmEvent::ConnectionClosed { .. } => return opened.unwrap().elapsed(),
+                        event => node.handle_swarm_event(event).await,
+                    },
+                    _ = peer.select_next_some() => {}
+                }
+            }
+        };
+        let open_for = tokio::time::timeout(std::time::Duration::from_secs(10), closed)
+            .await
+            .expect("the idle connection was never closed");
+        assert!(open_for >= std::time::Duration::from_millis(500), "closed after {open_for:?}");
+    }
+
+    #[tokio::test]
+    async fn address_failures_are_forgotten_once_moot() {
+        let (mut a, _a_rx, _) = memory_node().await;
+        let (mut b, _b_rx, b_addr) = memory_node().await;
+        let b_id = b.peer_id();
+        let [gone, kept]: [Multiaddr; 2] = ["/memory/1", "/memory/2"].map(|a| a.parse().unwrap());
+        a.discovered_peers.insert(b_id, vec![gone.clone(), kept.clone()]);
+
+        a.record_dial_failures(b_id, vec![gone.clone(), kept.clone()]);
+        a.drop_discovered_addrs(b_id, &[gone]);
+        assert_eq!(a.addr_failures.keys().collect::<Vec<_>>(), vec![&(b_id, kept)]);
+
+        a.dial(b_addr).unwrap();
+        drive_until(&mut a, &mut b, |a, _| a.connected_peers_map.contains_key(&b_id)).await;
+        assert!(a.addr_failures.is_empty());
+    }
+
+    #[tokio::test]
+    async fn blocks_sealed_on_shutdown_reach_a_peer() {
+        let (mut a, _a_rx, _) = memory_node().await;
+        let (mut b, _b_rx, b_addr) = memory_node().await;
+        a.seal_on_shutdown = true;
+        a.dial(b_addr).unwrap();
+        drive_until(&mut a, &mut b, |a, b| {
+            [TOPIC_TX, TOPIC_BLOCK].iter().all(|t| a.topic_peers(t) == 1 && b.topic_peers(t) == 1)
+        })
+        .await;
+
+        let tx = Transaction::new(TransactionType::PostCreated, b"last words".to_vec());
+        a.broadcast_transaction(tx.clone()).unwrap();
+        let received = async {
+            while b.blockchain.tip_index() < 1 {
+                b.step().await;
+            }
+        };
+        let received = tokio::time::timeout(std::time::Duration::from_secs(10), received);
+        let (saved, received) = tokio::join!(a.shutdown(), received);
+        saved.unwrap();
+        received.expect("the sealed block never reached the peer");
+        assert!(b.blockchain.get_block(1).unwrap().transactions.contains(&tx));
+    }
+
+    #[tokio::test]
+    async fn oversized_gossip_never_reaches_the_node() {
+        let (mut a, _a_rx, _) = memory_node().await;
+        let small = NodeConfig { max_message_size: 1024, ..NodeConfig::default() };
+        let (mut b, _b_rx, b_addr) = memory_node_with(small).await;
+        a.dial(b_addr).unwrap();
+        drive_until(&mut a, &mut b, |a, b| {
+            a.topic_peers(TOPIC_TX) == 1 && b.topic_peers(TOPIC_TX) == 1
+        })
+        .await;
+
+        let big = Transaction::new(TransactionType::PostCreated, vec![7; 4096]);
+        let fits = Transaction::new(TransactionType::PostCreated, b"fits".to_vec());
+        a.broadcast_transaction(big.clone()).unwrap();
+        a.broadcast_transaction(fits.clone()).unwrap();
+        drive_until(&mut a, &mut b, |_, b| b.pending_transactions.contains(&fits)).await;
+        let big_arrives = drive_until(&mut a, &mut b, |_, b| b.pending_transactions.contains(&big));
+        let waited = tokio::time::timeout(std::time::Duration::from_millis(500), big_arrives).await;
+        assert!(waited.is_err(), "the oversized transaction got through");
+    }
+
+    /// Drive both nodes until `done` holds for them, failing after 10 s.
+    async fn drive_until(a: &mut Node, b: &mut Node, done: impl Fn(&Node, &Node) -> bool) {
+        tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            while !done(a, b) {
+                tokio::select! {
+                    event = a.swarm.select_next_some() => a.handle_swarm_event(event).await,
+                    event = b.swarm.select_next_some() => b.handle_swarm_event(event).await,
+                }
+            }
+        })
+        .await
+        .expect("nodes never reached the expected state");
+    }
+
+    /// Status code of a `GET path` against the health endpoint at `addr`.
+    async fn http_status(addr: SocketAddr, path: &str) -> u16 {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let request = format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response.split_whitespace().nth(1).unwrap().parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn health_endpoint_turns_ready_once_a_peer_connects() {
+        let config = NodeConfig {
+            discovery_mode: DiscoveryMode::KademliaDht,
+            health_addr: Some("127.0.0.1:0".parse().unwrap()),
+            ..NodeConfig::default()
+        };
+        let (mut a, _a_rx) = Node::new_in_memory(config).await.unwrap();
+        let (mut b, _b_rx, b_addr) = memory_node().await;
+        let health = a.health_addr().unwrap();
+        assert_eq!(http_status(health, "/live").await, 503);
+
+        drive_until(&mut a, &mut b, |a, _| a.swarm.listeners().next().is_some()).await;
+        assert_eq!(http_status(health, "/live").await, 200);
+        assert_eq!(http_status(health, "/ready").await, 503);
+
+        a.dial(b_addr).unwrap();
+        drive_until(&mut a, &mut b, |a, _| !a.connected_peers_map.is_empty()).await;
+        assert_eq!(http_status(health, "/ready").await, 200);
+    }
+
+    #[tokio::test]
+    async fn discovery_scan_reports_when_its_bootstrap_completes() {
+        let completions = |rx: &mut mpsc::Receiver<NodeEvent>| -> Vec<usize> {
+            std::iter::from_fn(|| rx.try_recv().ok())
+                .filter_map(|event| match event {
+                    NodeEvent::DiscoveryCompleted { found } => Some(found),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        let (mut a, mut a_rx, _) = memory_node().await;
+        a.trigger_discovery(None);
+        assert_eq!(completions(&mut a_rx), vec![0], "nobody to bootstrap from");
+
+        let (mut b, _b_rx, b_addr) = memory_node().await;
+        let b_id = b.peer_id();
+        a.swarm.behaviour_mut().kademlia.add_address(&b_id, b_addr);
+        a.trigger_discovery(None);
+        assert!(a.discovery_scan.is_some());
+        drive_until(&mut a, &mut b, |a, _| a.discovery_scan.is_none()).await;
+        assert_eq!(completions(&mut a_rx).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn own_peer_id_and_addresses_are_never_discovered() {
+        let (mut node, _rx) = test_node().await;
+        node.discovery_mode = DiscoveryMode::Both;
+        let own_addr = loopback_addr(&mut node).await;
+        let other = PeerId::random();
+        let other_addr: Multiaddr = "/ip4/127.0.0.1/tcp/1".parse().unwrap();
+
+        let discovered = libp2p::mdns::Event::Discovered(vec![
+            (node.peer_id(), own_addr.clone()),
+            (other, own_addr.clone()),
+            (other, other_addr.clone()),
+        ]);
+        node.handle_swarm_event(SwarmEvent::Behaviour(SpBehaviourEvent::Mdns(discovered)))
+            .await;
+
+        assert!(!node.discovered_peers.contains_key(&node.peer_id()));
+        assert_eq!(node.discovered_peers.get(&other), Some(&vec![other_addr]));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn heartbeats_follow_the_configured_interval() {
+        let config = NodeConfig {
+            port: 0,
+            auto_connect: AutoConnectPolicy::None,
+            heartbeat_interval: std::time::Duration::from_millis(100),
+            ..NodeConfig::default()
+        };
+        let zero = NodeConfig { heartbeat_interval: std::time::Duration::ZERO, ..config.clone() };
+        assert!(matches!(Node::new(zero).await, Err(NodeError::InvalidConfig(_))));
+
+        // The clock only moves when the node is idle, so the count is exact.
+        let (mut node, mut rx) = Node::new(config).await.unwrap();
+        let _ = tokio::time::timeout(std::time::Duration::from_millis(550), async {
+            loop {
+                node.step().await;
+            }
+        })
+        .await;
+
+        let beats: Vec<u64> = std::iter::from_fn(|| rx.try_recv().ok())
+            .filter_map(|ev| match ev {
+                NodeEvent::Heartbeat { tip_index, peer_count: 0 } => Some(tip_index),
+                _ => None,
+            })
+            .collect();
+        // One tick straight away, then one every 100 ms.
+        assert_eq!(beats.len(), 6);
+        assert!(beats.iter().all(|&tip| tip == 0));
+    }
+
+    #[tokio::test]
+    async fn proxied_node_builds_its_transport() {
+        let onion: Multiaddr = "/dns/abcdef.onion/tcp/51025".parse().unwrap();
+        let config = NodeConfig {
+            port: 0,
+            proxy: Some("127.0.0.1:9050".parse().unwrap()),
+            external_addrs: vec![onion.clone()],
+            ..NodeConfig::default()
+        };
+        let (node, _rx) = Node::new(config).await.unwrap();
+        assert!(node.proxied);
+        assert_eq!(node.swarm.external_addresses().collect::<Vec<_>>(), vec![&onion]);
+    }
+
+    #[tokio::test]
+    async fn repeated_discovery_is_reported_once() {
+        let (mut node, mut rx) = test_node().await;
+        node.discovery_mode = DiscoveryMode::Both;
+        node.auto_connect = AutoConnectPolicy::None;
+        let other = PeerId::random();
+        let first: Multiaddr = "/ip4/10.0.0.1/tcp/4001".parse().unwrap();
+        let second: Multiaddr = "/ip4/10.0.0.2/tcp/4001".parse().unwrap();
+
+        for addrs in [vec![&first], vec![&first], vec![&first, &second], vec![&second]] {
+            let peers = addrs.into_iter().map(|addr| (other, addr.clone())).collect();
+            let discovered = libp2p::mdns::Event::Discovered(peers);
+            node.handle_swarm_event(SwarmEvent::Behaviour(SpBehaviourEvent::Mdns(discovered)))
+                .await;
+        }
+
+        let mut reported = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            if let NodeEvent::PeerDiscovered { peer_id, addrs, .. } = event {
+                reported.push((peer_id, addrs));
+            }
+        }
+        assert_eq!(reported, vec![(other, vec![first]), (other, vec![second])]);
+    }
+
+    #[tokio::test]
+    async fn discoveries_say_which_mechanism_found_the_peer() {
+        let (mut node, mut rx) = test_node().await;
+        node.discovery_mode = DiscoveryMode::Both;
+        let (lan, dht) = (PeerId::random(), PeerId::random());
+        let addr: Multiaddr = "/ip4/10.0.0.1/tcp/4001".parse().unwrap();
+
+        let mdns = libp2p::mdns::Event::Discovered(vec![(lan, addr.clone())]);
+        node.handle_swarm_event(SwarmEvent::Behaviour(SpBehaviourEvent::Mdns(mdns))).await;
+        let kad = libp2p::kad::Event::RoutingUpdated {
+            peer: dht,
+            is_new_peer: true,
+            addresses: libp2p::kad::Addresses::new(addr),
+            bucket_range: Default::default(),
+            old_peer: None,
+        };
+        node.handle_swarm_event(SwarmEvent::Behaviour(SpBehaviourEvent::Kademlia(kad))).await;
+
+        let sources: Vec<_> = std::iter::from_fn(|| rx.try_recv().ok())
+            .filter_map(|event| match event {
+                NodeEvent::PeerDiscovered { peer_id, source, .. } => Some((peer_id, source)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(sources, vec![(lan, DiscoverySource::Mdns), (dht, DiscoverySource::Kademlia)]);
+    }
+
+    #[tokio::test]
+    async fn auto_connect_policy_limits_discovery_dials() {
+        let (mut node, _rx) = test_node().await;
+        node.discovery_mode = DiscoveryMode::Both;
+        let three_peers = || {
+            let peers = (1..=3)
+                .map(|port| {
+                    let addr: Multiaddr = format!("/ip4/10.0.0.1/tcp/{port}").parse().unwrap();
+                    (PeerId::random(), addr)
+                })
+                .collect();
+            SwarmEvent::Behaviour(SpBehaviourEvent::Mdns(libp2p::mdns::Event::Discovered(peers)))
+        };
+
+        node.auto_connect = AutoConnectPolicy::None;
+        node.handle_swarm_event(three_peers()).await;
+        assert!(node.auto_dials.is_empty());
+        assert_eq!(node.discovered_peers().len(), 3);
+
+        node.auto_connect = AutoConnectPolicy::UpTo(2);
+        node.handle_swarm_event(three_peers()).await;
+        assert_eq!(node.auto_dials.len(), 2);
+        assert_eq!(node.discovered_peers().len(), 6);
+    }
+
+    #[tokio::test]
+    async fn periodic_discovery_does_not_redial_recent_peers() {
+        let (mut node, _rx) = test_node().await;
+        node.discovery_mode = DiscoveryMode::Both;
+        node.auto_connect = AutoConnectPolicy::All;
+        // Nothing listens on port 1, so each dial fails straight away.
+        let peers: Vec<(PeerId, Multiaddr)> = (0..3)
+            .map(|_| (PeerId::random(), "/ip4/127.0.0.1/tcp/1".parse().unwrap()))
+            .collect();
+        let discovered = || {
+            let event = libp2p::mdns::Event::Discovered(peers.clone());
+            SwarmEvent::Behaviour(SpBehaviourEvent::Mdns(event))
+        };
+
+        node.handle_swarm_event(discovered()).await;
+        assert_eq!(node.auto_dials.len(), 3);
+        tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            while !node.auto_dials.is_empty() {
+                let event = node.swarm.select_next_some().await;
+                node.handle_swarm_event(event).await;
+            }
+        })
+        .await
+        .expect("dials never failed");
+
+        node.handle_swarm_event(discovered()).await;
+        assert!(node.auto_dials.is_empty(), "second scan redialled");
+
+        let newcomer = (PeerId::random(), "/ip4/127.0.0.1/tcp/1".parse().unwrap());
+        let event = libp2p::mdns::Event::Discovered(vec![newcomer]);
+        node.handle_swarm_event(SwarmEvent::Behaviour(SpBehaviourEvent::Mdns(event))).await;
+        assert_eq!(node.auto_dials.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn set_port_range_outlives_scans_with_their_own_range() {
+        let (mut node, _rx) = test_node().await;
+        node.set_discovery_port_range(Some((4000, 4100)));
+        node.trigger_discovery(None);
+        assert_eq!(node.discovery_port_range(), Some(4000..=4100));
+
+        node.trigger_discovery(Some((5000, 5001)));
+        assert_eq!(node.discovery_port_range(), Some(5000..=5001));
+        node.trigger_discovery(None);
+        assert_eq!(node.discovery_port_range(), Some(4000..=4100));
+
+        node.set_discovery_port_range(None);
+        node.trigger_discovery(None);
+        assert_eq!(node.discovery_port_range(), None);
+    }
+
+    #[tokio::test]
+    async fn dials_outside_the_port_range_warn_or_are_refused() {
+        let (mut node, mut rx) = test_node().await;
+        node.trigger_discovery(Some((4000, 4100)));
+        std::iter::from_fn(|| rx.try_recv().ok()).for_each(drop);
+        let inside: Multiaddr = "/ip4/10.0.0.1/tcp/4001".parse().unwrap();
+        let outside: Multiaddr = "/ip4/10.0.0.1/tcp/5001".parse().unwrap();
+
+        node.dial(inside.clone()).unwrap();
+        node.dial(outside.clone()).unwrap();
+        let warned: Vec<_> = std::iter::from_fn(|| rx.try_recv().ok())
+            .filter_map(|ev| match ev {
+                NodeEvent::DialOutsidePortRange(addr) => Some(addr),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(warned, vec![outside.clone()]);
+
+        node.strict_port_range = true;
+        node.dial(inside).unwrap();
+        assert!(matches!(
+            node.dial(outside.clone()),
+            Err(NodeError::PortOutsideRange(addr)) if addr == outside
+        ));
+    }
+
+    #[tokio::test]
+    async fn peer_addresses_merge_and_stale_ones_are_pruned() {
+        let (mut node, mut rx) = test_node().await;
+        node.discovery_mode = DiscoveryMode::Both;
+        let other = PeerId::random();
+        let old: Multiaddr = "/ip4/10.0.0.1/tcp/4001".parse().unwrap();
+        let new: Multiaddr = "/ip4/10.0.0.2/tcp/4001".parse().unwrap();
+
+        for addr in [&old, &new, &old] {
+            let discovered = libp2p::mdns::Event::Discovered(vec![(other, addr.clone())]);
+            node.handle_swarm_event(SwarmEvent::Behaviour(SpBehaviourEvent::Mdns(discovered)))
+                .await;
+        }
+        assert_eq!(node.discovered_peers(), vec![(other, vec![old.clone(), new.clone()])]);
+
+        for _ in 0..MAX_ADDR_FAILURES {
+            let error = DialError::Transport(vec![(
+                old.clone(),
+                libp2p::TransportError::Other(std::io::Error::other("refused")),
+            )]);
+            node.handle_swarm_event(SwarmEvent::OutgoingConnectionError {
+                connection_id: ConnectionId::new_unchecked(0),
+                peer_id: Some(other),
+                error,
+            })
+            .await;
+        }
+        assert_eq!(node.discovered_peers(), vec![(other, vec![new.clone()])]);
+
+        let mut pruned = None;
+        while let Ok(event) = rx.try_recv() {
+            if let NodeEvent::PeerAddrsPruned { peer_id, addrs } = event {
+                pruned = Some((peer_id, addrs));
+            }
+        }
+        assert_eq!(pruned, Some((other, vec![new])));
+    }
+
+    #[tokio::test]
+    async fn mdns_expired_peers_leave_the_discovered_set() {
+        let (mut node, mut rx) = test_node().await;
+        node.discovery_mode = DiscoveryMode::Both;
+        let (leaving, staying) = (PeerId::random(), PeerId::random());
+        let leaving_addr: Multiaddr = "/ip4/10.0.0.1/tcp/4001".parse().unwrap();
+        let staying_addr: Multiaddr = "/ip4/10.0.0.2/tcp/4001".parse().unwrap();
+        let mdns = |event| SwarmEvent::Behaviour(SpBehaviourEvent::Mdns(event));
+
+        let found = vec![(leaving, leaving_addr.clone()), (staying, staying_addr.clone())];
+        node.handle_swarm_event(mdns(libp2p::mdns::Event::Discovered(found))).await;
+        std::iter::from_fn(|| rx.try_recv().ok()).for_each(drop);
+
+        let expired = vec![(leaving, leaving_addr)];
+        node.handle_swarm_event(mdns(libp2p::mdns::Event::Expired(expired.clone()))).await;
+        assert_eq!(node.discovered_peers(), vec![(staying, vec![staying_addr])]);
+        let gone: Vec<_> = std::iter::from_fn(|| rx.try_recv().ok())
+            .filter_map(|ev| match ev {
+                NodeEvent::PeerExpired { peer_id } => Some(peer_id),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(gone, vec![leaving]);
+
+        // Expiring it again changes nothing, so says nothing.
+        node.handle_swarm_event(mdns(libp2p::mdns::Event::Expired(expired))).await;
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn connection_time_is_recorded_and_reset_on_reconnect() {
+        let (mut a, _rx) = test_node().await;
+        let (mut b, _rx) = test_node().await;
+        let b_id = b.peer_id();
+        let addr = loopback_addr(&mut b).await;
+        let before = std::time::Instant::now();
+
+        a.dial(addr.clone()).unwrap();
+        drive_until(&mut a, &mut b, |a, _| a.connected_peers_map.contains_key(&b_id)).await;
+        let first = a.connected_since(&b_id).expect("connection time recorded");
+        assert!(first >= before);
+
+        a.disconnect(b_id).unwrap();
+        drive_until(&mut a, &mut b, |a, _| a.connected_since(&b_id).is_none()).await;
+        a.dial(addr).unwrap();
+        drive_until(&mut a, &mut b, |a, _| a.connected_peers_map.contains_key(&b_id)).await;
+        assert!(a.connected_since(&b_id).unwrap() > first);
+    }
+
+    #[tokio::test]
+    async fn configured_agent_version_is_advertised() {
+        let (mut a, _rx) = test_node().await;
+        let config = NodeConfig {
+            port: 0,
+            agent_version: Some("test-agent/9".into()),
+            auto_connect: AutoConnectPolicy::None,
+            ..NodeConfig::default()
+        };
+        let (mut b, _rx) = Node::new(config).await.unwrap();
+        let b_id = b.peer_id();
+        let a_id = a.peer_id();
+        let addr = loopback_addr(&mut b).await;
+
+        a.dial(addr).unwrap();
+        drive_until(&mut a, &mut b, |a, b| {
+            a.peer_agent_version(&b_id).is_some() && b.peer_agent_version(&a_id).is_some()
+        })
+        .await;
+
+        let genesis = a.blockchain().genesis_hash_hex();
+        assert_eq!(
+            a.peer_agent_version(&b_id).unwrap(),
+            format!("test-agent/9 genesis/{genesis}")
+        );
+        assert!(b.peer_agent_version(&a_id).unwrap().starts_with("sp-node/"));
+    }
+
+    #[tokio::test]
+    async fn subscribing_peer_updates_topic_counts() {
+        let (mut a, mut rx) = test_node().await;
+        let (mut b, _rx) = test_node().await;
+        let b_id = b.peer_id();
+        let addr = loopback_addr(&mut b).await;
+        assert_eq!(a.topic_peers(TOPIC_TX), 0);
+
+        a.dial(addr).unwrap();
+        drive_until(&mut a, &mut b, |a, _| {
+            BUILTIN_TOPICS.iter().all(|topic| a.topic_peers(topic) == 1)
+        })
+        .await;
+        let mut changes = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            if let NodeEvent::TopicPeersChanged { topic, peers } = event {
+                changes.push((topic, peers));
+            }
+        }
+        assert!(changes.contains(&(TOPIC_TX.to_string(), 1)));
+
+        a.disconnect(b_id).unwrap();
+        drive_until(&mut a, &mut b, |a, _| a.topic_peers(TOPIC_TX) == 0).await;
+    }
+
+    #[tokio::test]
+    async fn dry_run_forms_blocks_without_publishing() {
+        let config = NodeConfig { port: 0, dry_run: true, ..NodeConfig::default() };
+        let (mut node, mut rx) = Node::new(config).await.unwrap();
+
+        // Without peers a real broadcast fails with NoPeers and a full node's
+        // verification publish fails too; a dry run attempts neither.
+        for i in 0..3 {
+            let tx = Transaction::new(TransactionType::PostCreated, vec![i]);
+            node.broadcast_transaction(tx).unwrap();
+        }
+        node.form_block().unwrap();
+
+        let block = node.blockchain().tip();
+        assert_eq!((block.index, block.transactions.len()), (1, 3));
+        assert!(block.verifications.is_empty());
+        assert!(node.verified_blocks.is_empty());
+        assert!(std::iter::from_fn(|| rx.try_recv().ok())
+            .any(|ev| matches!(ev, NodeEvent::BlockFormed { block_index: 1, tx_count: 3 })));
+    }
+
+    #[tokio::test]
+    async fn dry_run_sends_no_verification() {
+        let config = NodeConfig { port: 0, dry_run: true, ..NodeConfig::default() };
+        let (mut node, _rx) = Node::new(config).await.unwrap();
+        node.blockchain = extended_chain(&node, 1);
+
+        node.send_verification(1).unwrap();
+        assert!(node.blockchain().tip().verifications.is_empty());
+        assert!(node.verified_blocks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn broadcasting_without_peers_keeps_nothing() {
+        let (mut node, _rx) = test_node().await;
+        let tx = Transaction::new(TransactionType::PostCreated, b"alone".to_vec());
+
+        let err = node.broadcast_transaction(tx.clone()).unwrap_err();
+        assert!(matches!(err, NodeError::NoPeers(ref topic) if topic == TOPIC_TX));
+        let err = node.broadcast_transactions(vec![tx]).unwrap_err();
+        assert!(matches!(err, NodeError::NoPeers(_)));
+        assert!(node.pending_transactions.is_empty());
+        assert!(node.assets().is_empty());
+    }
+
+    #[tokio::test]
+    async fn kicked_peer_is_not_readded_but_dropped_peer_is() {
+        let (mut kicker, _rx) = test_node().await;
+        let (mut kicked, _rx) = test_node().await;
+        let (kicker_id, kicked_id) = (kicker.peer_id(), kicked.peer_id());
+        let addr = loopback_addr(&mut kicked).await;
+
+        kicker.dial(addr).unwrap();
+        drive_until(&mut kicker, &mut kicked, |a, b| {
+            a.connected_peers_map.contains_key(&kicked_id)
+                && b.connected_peers_map.contains_key(&kicker_id)
+        })
+        .await;
+
+        kicker.disconnect(kicked_id).unwrap();
+        drive_until(&mut kicker, &mut kicked, |a, b| {
+            a.connected_peers_map.is_empty() && b.connected_peers_map.is_empty()
+        })
+        .await;
+
+        // The user's side keeps the peer out of discovery; the other side
+        // saw a dropped connection and may redial.
+        assert!(!kicker.discovered_peers.contains_key(&kicked_id));
+        assert!(kicker.kicked_peers.contains(&kicked_id));
+        assert!(kicked.discovered_peers.contains_key(&kicker_id));
+    }
+
+    #[tokio::test]
+    async fn filling_a_batch_forms_a_block_and_reports_it() {
+        let (mut node, mut rx) = test_node().await;
+        let txs: Vec<Transaction> = (0..BLOCK_BATCH_SIZE)
+            .map(|_| Transaction::new(TransactionType::PostCreated, vec![]))
+            .collect();
+        let msg = GossipMessage::TransactionBatch(txs);
+
+        node.handle_gossip_message(None, None, &encode_gossip(&msg).unwrap()).await;
+
+        let mut formed = Vec::new();
+        while let Ok(ev) = rx.try_recv() {
+            match ev {
+                NodeEvent::BlockFormed { block_index, tx_count } => {
+                    formed.push((block_index, tx_count));
+                }
+                NodeEvent::BlockReceived { .. } => panic!("own block reported as received"),
+                _ => {}
+            }
+        }
+        assert_eq!(formed, vec![(1, BLOCK_BATCH_SIZE)]);
+    }
+
+    #[tokio::test]
+    async fn peers_on_a_different_genesis_are_disconnected() {
+        let (mut ours, mut rx) = test_node().await;
+        let config = NodeConfig { port: 0, network_id: "testnet".into(), ..NodeConfig::default() };
+        let (mut theirs, _rx) = Node::new(config).await.unwrap();
+        let theirs_id = theirs.peer_id();
+        assert_ne!(ours.blockchain().genesis_hash(), theirs.blockchain().genesis_hash());
+
+        let addr = loopback_addr(&mut theirs).await;
+        ours.dial(addr).unwrap();
+        drive_until(&mut ours, &mut theirs, |a, _| {
+            a.kicked_peers.contains(&theirs_id) && a.connected_peers_map.is_empty()
+        })
+        .await;
+
+        let mut genesis_errors = 0;
+        while let Ok(ev) = rx.try_recv() {
+            if matches!(&ev, NodeEvent::Error(msg) if msg.contains("genesis")) {
+                genesis_errors += 1;
+            }
+        }
+        assert_eq!(genesis_errors, 1);
+    }
+
+    #[tokio::test]
+    async fn stored_chain_from_another_network_is_refused() {
+        let dir = temp_data_dir();
+        chain::save(&dir.join(chain::CHAIN_FILE), &Blockchain::new_with_network("testnet")).unwrap();
+
+        let config = NodeConfig { port: 0, data_dir: Some(dir.clone()), ..NodeConfig::default() };
+        let err = Node::new(config).await.err().expect("node should refuse the chain");
+        assert!(matches!(err, NodeError::Blockchain(BlockchainError::InvalidChain(_))));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    fn chain_synced_events(rx: &mut mpsc::Receiver<NodeEvent>) -> Vec<(usize, usize)> {
+        let mut synced = Vec::new();
+        while let Ok(ev) = rx.try_recv() {
+            if let NodeEvent::ChainSynced { new_length, blocks_applied } = ev {
+                synced.push((new_length, blocks_applied));
+            }
+        }
+        synced
+    }
+
+    #[tokio::test]
+    async fn sync_response_applies_new_blocks() {
+        let (mut node, mut rx) = test_node().await;
+        let remote = extended_chain(&node, 2);
+        let response = encode_response(&SyncResponse::Blocks(remote.blocks_from(1).to_vec())).unwrap();
+
+        node.apply_sync_response(PeerId::random(), &response).await;
+
+        assert_eq!(node.blockchain().len(), 3);
+        assert_eq!(chain_synced_events(&mut rx), vec![(3, 2)]);
+    }
+
+    #[tokio::test]
+    async fn verifications_shipped_with_a_block_are_ignored() {
+        let (mut node, _rx) = test_node().await;
+        let mut remote = extended_chain(&node, 2);
+        let claimed = ["peer-a", "peer-b", "peer-c"];
+        for index in 1..=2 {
+            for peer in claimed {
+                remote.verify_block(index, peer.into()).unwrap();
+            }
+        }
+        let [gossiped, synced] = [1, 2].map(|i| remote.get_block(i).unwrap().clone());
+        assert!(gossiped.is_finalised() && synced.is_finalised());
+
+        node.handle_block(gossiped, None);
+        let response = encode_response(&SyncResponse::Blocks(vec![synced])).unwrap();
+        node.apply_sync_response(PeerId::random(), &response).await;
+
+        assert_eq!(node.blockchain().len(), 3);
+        for block in &node.blockchain().blocks()[1..] {
+            assert!(!block.is_finalised(), "block #{} kept its weight", block.index);
+            assert!(block.verifications.iter().all(|p| !claimed.contains(&p.as_str())));
+        }
+    }
+
+    #[tokio::test]
+    async fn refused_sync_blocks_are_reported() {
+        let (mut node, mut rx) = test_node().await;
+        let remote = extended_chain(&node, 3);
+        let mut blocks = remote.blocks_from(1).to_vec();
+        blocks[1].transactions[0].sign(vec![1; 3]);
+        let response = encode_response(&SyncResponse::Blocks(blocks.clone())).unwrap();
+        node.apply_sync_response(PeerId::random(), &response).await;
+        assert_eq!(node.blockchain().len(), 2);
+
+        // Block 2 now goes in, but block 3 no longer links to it.
+        blocks[1] = remote.blocks()[2].clone();
+        blocks[2].prev_hash = [9; 32];
+        let response = encode_response(&SyncResponse::Blocks(blocks)).unwrap();
+        node.apply_sync_response(PeerId::random(), &response).await;
+        assert_eq!(node.blockchain().len(), 3);
+
+        let rejected: Vec<u64> = std::iter::from_fn(|| rx.try_recv().ok())
+            .filter_map(|ev| match ev {
+                NodeEvent::BlockRejected { block_index, .. } => Some(block_index),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(rejected, vec![2, 3]);
+    }
+
+    #[tokio::test]
+    async fn identical_resync_emits_no_chain_synced() {
+        let (mut node, mut rx) = test_node().await;
+        let remote = extended_chain(&node, 2);
+        let response = encode_response(&SyncResponse::Blocks(remote.blocks_from(1).to_vec())).unwrap();
+
+        node.apply_sync_response(PeerId::random(), &response).await;
+        chain_synced_events(&mut rx);
+        node.apply_sync_response(PeerId::random(), &response).await;
+
+        assert_eq!(node.blockchain().len(), 3);
+        assert!(chain_synced_events(&mut rx).is_empty());
+    }
+
+    /// Feed verifications for block 1 from fresh peers in the given modes and
+    /// report whether the block was finalised.
+    async fn verify_with_modes(modes: &[NodeMode]) -> bool {
+        let (mut node, mut rx) = test_node().await;
+        let remote = extended_chain(&node, 1);
+        node.handle_block(remote.blocks()[1].clone(), None);
+
+        for mode in modes {
+            let peer = libp2p::identity::Keypair::generate_ed25519();
+            let msg = sign_verification(&peer, 1, block_hash(&node, 1), mode.clone()).unwrap();
+            let source = peer.public().to_peer_id();
+            node.handle_gossip_message(None, Some(source), &encode_gossip(&msg).unwrap()).await;
+        }
+
+        std::iter::from_fn(|| rx.try_recv().ok())
+            .any(|ev| matches!(ev, NodeEvent::BlockFinalised { block_index: 1, .. }))
+    }
+
+    /// Hash of `node`'s block at `index`.
+    fn block_hash(node: &Node, index: u64) -> [u8; 32] {
+        node.blockchain().get_block(index).unwrap().hash()
+    }
+
+    /// Feed `node` a full verification of block 1 signed by `peer`.
+    async fn feed_verification(node: &mut Node, peer: &libp2p::identity::Keypair) {
+        let msg = sign_verification(peer, 1, block_hash(node, 1), NodeMode::Full).unwrap();
+        let source = peer.public().to_peer_id();
+        node.handle_gossip_message(None, Some(source), &encode_gossip(&msg).unwrap()).await;
+    }
+
+    /// Drain `rx`, rendering verification progress as "n/threshold".
+    fn verification_progress(rx: &mut mpsc::Receiver<NodeEvent>) -> Vec<String> {
+        std::iter::from_fn(|| rx.try_recv().ok())
+            .filter_map(|ev| match ev {
+                NodeEvent::BlockVerified { verifications, threshold, .. } => {
+                    Some(format!("{verifications}/{threshold}"))
+                }
+                NodeEvent::BlockFinalised { .. } => Some("finalised".into()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn verifications_report_progress_before_finalisation() {
+        let (mut node, mut rx) = test_node().await;
+        let remote = extended_chain(&node, 1);
+        let peers: Vec<_> =
+            (0..3).map(|_| libp2p::identity::Keypair::generate_ed25519()).collect();
+
+        // A full node verifies the block itself on receipt.
+        node.handle_block(remote.blocks()[1].clone(), None);
+        feed_verification(&mut node, &peers[0]).await;
+        // A repeated verification is not progress.
+        feed_verification(&mut node, &peers[0]).await;
+        assert_eq!(verification_progress(&mut rx), vec!["1/3", "2/3"]);
+
+        feed_verification(&mut node, &peers[1]).await;
+        assert_eq!(verification_progress(&mut rx), vec!["3/3", "finalised"]);
+
+        // Finalisation is reported once.
+        feed_verification(&mut node, &peers[2]).await;
+        assert_eq!(verification_progress(&mut rx), vec!["4/3"]);
+    }
+
+    #[tokio::test]
+    async fn finalising_a_block_counts_its_transactions() {
+        let (mut node, _rx) = test_node().await;
+        let mut remote = node.blockchain().clone();
+        let txs = (0..5)
+            .map(|i| Transaction::new(TransactionType::PostCreated, vec![i]))
+            .collect();
+        remote.add_block(txs).unwrap();
+        let peers: Vec<_> =
+            (0..2).map(|_| libp2p::identity::Keypair::generate_ed25519()).collect();
+
+        node.handle_block(remote.blocks()[1].clone(), None);
+        assert_eq!(node.finalised_transactions(), 0);
+        for peer in &peers {
+            feed_verification(&mut node, peer).await;
+        }
+
+        assert_eq!(node.finalised_transactions(), 5);
+        assert!(node.finalised_per_minute() > 0.0);
+    }
+
+    #[tokio::test]
+    async fn forged_verifications_are_dropped() {
+        let (mut node, mut rx) = test_node().await;
+        let remote = extended_chain(&node, 1);
+        node.handle_block(remote.blocks()[1].clone(), None);
+        let victim = libp2p::identity::Keypair::generate_ed25519();
+        let forger = libp2p::identity::Keypair::generate_ed25519();
+        let forger_id = forger.public().to_peer_id();
+
+        let hash = block_hash(&node, 1);
+
+        // Signed by the forger but claiming the victim's peer id.
+        let GossipMessage::BlockVerification { mode, public_key, signature, .. } =
+            sign_verification(&forger, 1, hash, NodeMode::Full).unwrap()
+        else {
+            unreachable!()
+        };
+        let claimed = GossipMessage::BlockVerification {
+            block_index: 1,
+            block_hash: hash,
+            peer_id: victim.public().to_peer_id().to_string(),
+            mode,
+            public_key,
+            signature,
+        };
+        // A genuine verification by the victim, replayed by the forger.
+        let replayed = sign_verification(&victim, 1, hash, NodeMode::Full).unwrap();
+
+        for msg in [claimed, replayed.clone()] {
+            let data = encode_gossip(&msg).unwrap();
+            node.handle_gossip_message(None, Some(forger_id), &data).await;
+        }
+        // The same verification with no known publisher.
+        node.handle_gossip_message(None, None, &encode_gossip(&replayed).unwrap()).await;
+
+        let verifiers = &node.blockchain().get_block(1).unwrap().verifications;
+        assert_eq!(verifiers, &vec![node.peer_id().to_string()]);
+        let errors = std::iter::from_fn(|| rx.try_recv().ok())
+            .filter(|ev| matches!(ev, NodeEvent::Error(_)))
+            .count();
+        assert_eq!(errors, 3);
+    }
+
+    #[tokio::test]
+    async fn verifications_of_a_fork_do_not_count() {
+        let (mut node, _rx) = test_node().await;
+        let remote = extended_chain(&node, 1);
+        let fork = extended_chain(&node, 1);
+        node.handle_block(remote.blocks()[1].clone(), None);
+
+        let peer = libp2p::identity::Keypair::generate_ed25519();
+        let msg = sign_verification(&peer, 1, fork.blocks()[1].hash(), NodeMode::Full).unwrap();
+        let source = peer.public().to_peer_id();
+        node.handle_gossip_message(None, Some(source), &encode_gossip(&msg).unwrap()).await;
+
+        let verifiers = &node.blockchain().get_block(1).unwrap().verifications;
+        assert_eq!(verifiers, &vec![node.peer_id().to_string()]);
+    }
+
+    #[tokio::test]
+    async fn gossip_verifications_do_not_finalise() {
+        use NodeMode::{Full, Gossip};
+        // The node's own (full) verification plus one full peer is not enough,
+        // however many gossip peers chime in; a second full peer is.
+        assert!(!verify_with_modes(&[Full, Gossip, Gossip, Gossip]).await);
+        assert!(verify_with_modes(&[Gossip, Full, Full]).await);
+    }
+
+    #[tokio::test]
+    async fn next_block_connects_to_tip() {
+        let (mut node, mut rx) = test_node().await;
+        let remote = extended_chain(&node, 1);
+
+        node.handle_block(remote.blocks()[1].clone(), None);
+
+        assert_eq!(node.blockchain().len(), 2);
+        assert_eq!(block_events(&mut rx), (vec![1], 0));
+    }
+
+    #[tokio::test]
+    async fn blocks_ahead_of_tip_wait_as_orphans() {
+        let (mut node, mut rx) = test_node().await;
+        let remote = extended_chain(&node, 3);
+
+        node.handle_block(remote.blocks()[3].clone(), None);
+        node.handle_block(remote.blocks()[2].clone(), None);
+        assert_eq!(node.blockchain().len(), 1);
+
+        node.handle_block(remote.blocks()[1].clone(), None);
+        assert_eq!(node.blockchain().len(), 4);
+        assert!(node.orphan_blocks.is_empty());
+        assert_eq!(block_events(&mut rx), (vec![1, 2, 3], 0));
+    }
+
+    #[tokio::test]
+    async fn port_zero_reports_the_bound_port() {
+        let (mut node, _rx) = test_node().await;
+        assert_eq!(node.listen_port(), None);
+
+        tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            while node.listen_port().is_none() {
+                node.step().await;
+            }
+        })
+        .await
+        .expect("node never listened");
+
+        assert_ne!(node.listen_port(), Some(0));
+        assert!(node.swarm.listeners().all(|addr| addr_port(addr) == node.listen_port()));
+    }
+
+    #[tokio::test]
+    async fn stepping_drives_the_node_to_listen() {
+        let (mut node, mut rx) = test_node().await;
+
+        let listening = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            loop {
+                node.step().await;
+                while let Ok(ev) = rx.try_recv() {
+                    if let NodeEvent::Listening(addr) = ev {
+                        return addr;
+                    }
+                }
+            }
+        })
+        .await
+        .expect("no Listening event within 5 s");
+        assert!(addr_port(&listening).is_some_and(|port| port != 0));
+    }
+
+    #[tokio::test]
+    async fn duplicate_blocks_are_dropped() {
+        let (mut node, mut rx) = test_node().await;
+        let remote = extended_chain(&node, 1);
+
+        node.handle_block(remote.blocks()[1].clone(), None);
+        node.handle_block(remote.blocks()[1].clone(), None);
+
+        assert_eq!(node.blockchain().len(), 2);
+        assert_eq!(block_events(&mut rx), (vec![1], 0));
+    }
+
+    #[tokio::test]
+    async fn tampered_block_is_rejected_not_appended() {
+        let (mut node, mut rx) = test_node().await;
+        let remote = extended_chain(&node, 1);
+        let mut tampered = remote.blocks()[1].clone();
+        tampered.transactions[0].payload = b"rewritten".to_vec();
+
+        node.handle_block(tampered, None);
+
+        assert_eq!(node.blockchain().len(), 1);
+        let rejected: Vec<_> = std::iter::from_fn(|| rx.try_recv().ok())
+            .filter_map(|ev| match ev {
+                NodeEvent::BlockRejected { block_index, reason } => Some((block_index, reason)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].0, 1);
+        assert!(rejected[0].1.contains("merkle root"), "{}", rejected[0].1);
+    }
+
+    #[tokio::test]
+    async fn full_nodes_reject_blocks_with_an_invalid_transaction() {
+        let (mut full, mut rx) = test_node().await;
+        let mut bad = Transaction::new(TransactionType::VoteCast, vec![]);
+        bad.sign(vec![1; 3]);
+        let good = Transaction::new(TransactionType::VoteCast, vec![]);
+        let mut remote = full.blockchain().clone();
+        remote.add_block(vec![good, bad.clone()]).unwrap();
+        let block = remote.blocks()[1].clone();
+
+        full.handle_block(block.clone(), None);
+
+        assert_eq!(full.blockchain().len(), 1);
+        let reason = std::iter::from_fn(|| rx.try_recv().ok())
+            .find_map(|ev| match ev {
+                NodeEvent::BlockRejected { block_index: 1, reason } => Some(reason),
+                _ => None,
+            })
+            .expect("block should be rejected");
+        assert!(reason.contains(&bad.id.to_string()), "{reason}");
+
+        // Only full nodes are that strict.
+        let config = NodeConfig {
+            port: 0,
+            mode: NodeMode::Gossip,
+            auto_connect: AutoConnectPolicy::None,
+            ..NodeConfig::default()
+        };
+        let (mut gossip, _rx) = Node::new(config).await.unwrap();
+        gossip.handle_block(block, None);
+        assert_eq!(gossip.blockchain().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn sealed_deterministic_transactions_pass_the_full_node_check() {
+        let config = NodeConfig {
+            port: 0,
+            payload_key: Some(PayloadKey::new([7; 32])),
+            ..NodeConfig::default()
+        };
+        let (sender, _rx) = Node::new(config).await.unwrap();
+        let tx = Transaction::builder(TransactionType::UserEdited)
+            .payload(br#"{"bio":"private"}"#.to_vec())
+            .deterministic()
+            .build()
+            .unwrap();
+        let sealed = sender.seal_payload(tx).unwrap();
+
+        let (mut full, _rx) = test_node().await;
+        let mut remote = full.blockchain().clone();
+        remote.add_block(vec![sealed]).unwrap();
+        let block = remote.blocks()[1].clone();
+        assert!(full.check_transactions(&block).is_ok());
+        full.handle_block(block, None);
+        assert_eq!(full.blockchain().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn bogus_indices_are_reported() {
+        let (mut node, mut rx) = test_node().await;
+        let remote = extended_chain(&node, 1);
+
+        let mut far_ahead = remote.blocks()[1].clone();
+        far_ahead.index = u64::MAX;
+        node.handle_block(far_ahead, None);
+        node.handle_block(remote.blocks()[0].clone(), None);
+
+        assert_eq!(node.blockchain().len(), 1);
+        assert!(node.orphan_blocks.is_empty());
+        assert_eq!(block_events(&mut rx), (vec![], 2));
+    }
+
+    #[tokio::test]
+    async fn chain_past_its_cap_is_pruned_once_blocks_finalise() {
+        let config = NodeConfig {
+            port: 0,
+            auto_connect: AutoConnectPolicy::None,
+            max_chain_blocks: Some(3),
+            ..NodeConfig::default()
+        };
+        let (mut node, mut rx) = Node::new(config).await.unwrap();
+        let remote = extended_chain(&node, 5);
+        for block in &remote.blocks()[1..] {
+            node.handle_block(block.clone(), None);
+        }
+        // Nothing is finalised yet, so nothing may go.
+        assert_eq!(node.blockchain().blocks().len(), 6);
+
+        for index in 1..=3 {
+            finalise_block(&mut node, index);
+        }
+
+        let chain = node.blockchain();
+        let kept: Vec<u64> = chain.blocks().iter().map(|b| b.index).collect();
+        assert_eq!(kept, vec![0, 4, 5]);
+        assert_eq!(chain.len(), 6);
+        assert!(chain.is_valid());
+        let pruned: Vec<u64> = std::iter::from_fn(|| rx.try_recv().ok())
+            .filter_map(|ev| match ev {
+                NodeEvent::ChainPruned { up_to_index } => Some(up_to_index),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(pruned.last(), Some(&3));
+
+        // The chain still links: the next block connects and the oldest
+        // finalised one beyond the window goes.
+        let mut remote = remote;
+        remote.add_block(vec![Transaction::new(TransactionType::PostCreated, vec![])]).unwrap();
+        finalise_block(&mut node, 4);
+        node.handle_block(remote.blocks()[6].clone(), None);
+        let kept: Vec<u64> = node.blockchain().blocks().iter().map(|b| b.index).collect();
+        assert_eq!(kept, vec![0, 5, 6]);
+        assert!(node.blockchain().is_valid());
+        let held: HashSet<Uuid> =
+            node.blockchain().iter_transactions().map(|(_, t)| t.id).collect();
+        assert_eq!(node.chain_tx_ids, held);
+        let verified: BTreeSet<[u8; 32]> =
+            node.blockchain().blocks()[1..].iter().map(Block::hash).collect();
+        assert_eq!(node.verified_blocks, verified);
+    }
+
+    #[tokio::test]
+    async fn verified_record_forgets_pruned_blocks() {
+        let dir = temp_data_dir();
+        let config = NodeConfig {
+            port: 0,
+            auto_connect: AutoConnectPolicy::None,
+            max_chain_blocks: Some(3),
+            data_dir: Some(dir.clone()),
+            ..NodeConfig::default()
+        };
+        let (mut node, _rx) = Node::new(config).await.unwrap();
+        let path = dir.join(verified::VERIFIED_FILE);
+        for block in &extended_chain(&node, 5).blocks()[1..] {
+            node.handle_block(block.clone(), None);
+        }
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 5);
+
+        for index in 1..=3 {
+            finalise_block(&mut node, index);
+        }
+        assert_eq!(node.verified_blocks.len(), 2);
+        assert_eq!(verified::load(&path), node.verified_blocks);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn finalised_blocks_are_counted_per_transaction_type() {
+        let (mut node, _rx) = test_node().await;
+        let mut remote = node.blockchain().clone();
+        let txs = vec![
+            Transaction::new(TransactionType::VoteCast, vec![]),
+            Transaction::new(TransactionType::PostCreated, vec![]),
+            Transaction::new(TransactionType::VoteCast, vec![]),
+        ];
+        remote.add_block(txs).unwrap();
+        node.handle_block(remote.blocks()[1].clone(), None);
+        assert!(node.finalised_by_type().is_empty());
+
+        finalise_block(&mut node, 1);
+
+        let counts = node.finalised_by_type();
+        assert_eq!(counts.get(&TransactionType::VoteCast), Some(&2));
+        assert_eq!(counts.get(&TransactionType::PostCreated), Some(&1));
+        assert_eq!(counts.len(), 2);
+        assert_eq!(node.status().finalised_by_type, *counts);
+    }
+
+    /// Verify block `index` from fresh peers until it finalises (and so may
+    /// be pruned).
+    fn finalise_block(node: &mut Node, index: u64) {
+        let mut peer = 0;
+        while node.blockchain().get_block(index).is_some_and(|b| !b.is_finalised()) {
+            peer += 1;
+            node.record_verification(index, format!("peer-{peer}"), FULL_VERIFICATION_WEIGHT)
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn observer_stores_blocks_but_never_originates() {
+        let config = NodeConfig {
+            port: 0,
+            mode: NodeMode::Observer,
+            auto_connect: AutoConnectPolicy::None,
+            ..NodeConfig::default()
+        };
+        let (mut node, mut rx) = Node::new(config).await.unwrap();
+        let tx = Transaction::new(TransactionType::PostCreated, vec![]);
+        assert!(matches!(node.broadcast_transaction(tx), Err(NodeError::Observer(_))));
+        assert!(node.pending_transactions.is_empty());
+        assert!(matches!(node.form_block(), Err(NodeError::Observer(_))));
+
+        let remote = extended_chain(&node, 1);
+        node.handle_block(remote.blocks()[1].clone(), None);
+
+        assert_eq!(block_events(&mut rx), (vec![1], 0));
+        assert!(node.verified_blocks.is_empty());
+        assert!(node.blockchain().blocks()[1].verifications.is_empty());
+        assert!(matches!(node.send_verification(1), Err(NodeError::Observer(_))));
+    }
+
+    #[tokio::test]
+    async fn gossip_node_refuses_to_serve_its_chain() {
+        let config = NodeConfig {
+            port: 0,
+            mode: NodeMode::Gossip,
+            auto_connect: AutoConnectPolicy::None,
+            ..NodeConfig::default()
+        };
+        let (gossip, _rx) = Node::new(config).await.unwrap();
+        let (mut full, _rx) = test_node().await;
+        let peer = PeerId::random();
+
+        let request = SyncRequest::BlocksFrom { from_index: 0 };
+        let refused = gossip.answer_sync_request(peer, request.clone());
+        assert!(matches!(refused, SyncResponse::NotServing));
+        assert!(matches!(
+            full.answer_sync_request(peer, request),
+            SyncResponse::Blocks(blocks) if blocks.len() == 1
+        ));
+
+        // The refusal is remembered so retries go elsewhere.
+        let gossip_id = gossip.peer_id();
+        full.apply_sync_response(gossip_id, &encode_response(&refused).unwrap()).await;
+        assert!(full.not_serving.contains(&gossip_id));
+    }
+
+    #[tokio::test]
+    async fn the_sync_strategy_decides_what_is_requested_and_kept() {
+        let (mut node, _rx) = test_node().await;
+        let remote = extended_chain(&node, 3);
+        let block_size = remote.blocks()[1].serialised_size() as u64;
+        let tip = encode_response(&SyncResponse::ChainTip { tip_index: 3 }).unwrap();
+
+        // Blocks from a checkpoint past our tip could not be appended.
+        node.sync_manager.set_strategy(SyncStrategy::FromIndex { start: 2 });
+        node.apply_sync_response(PeerId::random(), &tip).await;
+        assert!(node.sync_requests.is_empty());
+
+        // A size budget keeps only what fits, and counts it once.
+        let max_bytes = 2 * block_size - 1;
+        node.sync_manager.set_strategy(SyncStrategy::SizeLimit { max_bytes });
+        let blocks = SyncResponse::Blocks(remote.blocks_from(1).to_vec());
+        let response = encode_response(&blocks).unwrap();
+        node.apply_sync_response(PeerId::random(), &response).await;
+        assert_eq!(node.blockchain().tip_index(), 1);
+        assert_eq!(node.sync_manager.downloaded_bytes(), block_size);
+    }
+
+    #[tokio::test]
+    async fn time_range_server_serves_only_in_range_blocks() {
+        let config = NodeConfig {
+            port: 0,
+            sync_strategy: SyncStrategy::TimeRange { from: 1, to: i64::MAX },
+            ..NodeConfig::default()
+        };
+        let (mut node, _rx) = Node::new(config).await.unwrap();
+        node.blockchain = extended_chain(&node, 2);
+
+        // Genesis is stamped 0, so only the two new blocks are in range.
+        let served = node.blocks_to_serve(0).unwrap();
+        assert_eq!(served.iter().map(|b| b.index).collect::<Vec<_>>(), vec![1, 2]);
+
+        node.sync_manager
+            .set_strategy(SyncStrategy::TimeRange { from: i64::MAX - 1, to: i64::MAX });
+        assert!(node.blocks_to_serve(0).unwrap().is_empty());
+        assert!(node.blocks_to_serve(4).is_err());
+    }
+}
@@ -1,26 +1,74 @@
-use serde::{Deserialize, Serialize};
-use sp_blockchain::Block;
+use std::fmt;
+
+use libp2p::{
+    identity::{Keypair, PublicKey},
+    kad::RecordKey,
+    Multiaddr, PeerId,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sp_blockchain::{Block, BlockHeader, Blockchain};
 use sp_transaction::Transaction;
+use uuid::Uuid;
+
+use crate::{
+    error::{DecodeError, NodeError},
+    mode::NodeMode,
+};
+
+/// Identify protocol version; peers advertising another one are on a
+/// different network protocol.
+pub const PROTOCOL_VERSION: &str = "/sp/1.0.0";
+
+/// Request-response protocol used for block sync.
+pub const SYNC_PROTOCOL: &str = "/sp/sync/1.0.0";
+
+/// Format version prefixed to every gossip and sync message.  Bump it when
+/// the encoding of [`GossipMessage`], [`SyncRequest`] or [`SyncResponse`]
+/// changes, so old and new nodes report each other as incompatible instead
+/// of failing to deserialise.
+pub const WIRE_VERSION: u8 = 6;
+
+/// Version byte of a [`SyncResponse::VersionMismatch`] reply.  No wire
+/// version uses it, so the reply reads the same in every release.
+const MISMATCH_VERSION: u8 = 0;
 
 /// Topics used on the gossipsub overlay.
 pub const TOPIC_TX: &str = "sp/tx";
 pub const TOPIC_VERIFY: &str = "sp/verify";
 pub const TOPIC_BLOCK: &str = "sp/block";
 
+/// The topics every node subscribes to.  They are reserved: applications use
+/// their own via `NodeConfig::extra_topics`.
+pub const BUILTIN_TOPICS: [&str; 3] = [TOPIC_TX, TOPIC_VERIFY, TOPIC_BLOCK];
+
 /// Messages sent over the gossipsub topics.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum GossipMessage {
     /// A new transaction broadcast to all peers.
     Transaction(Transaction),
 
-    /// A peer signals that it has verified the block at `block_index`.
+    /// Several transactions submitted together, sent as one message.
+    TransactionBatch(Vec<Transaction>),
+
+    /// A peer signals that it has verified the block at `block_index`, whose
+    /// hash is `block_hash`; it counts only toward a block with that hash, so
+    /// it can't be replayed onto a fork.  `mode` is the sender's mode and
+    /// decides how much the verification weighs toward finalisation.
+    /// `signature` covers [`verification_signing_bytes`] and must check out
+    /// against `public_key`, whose peer id must equal `peer_id`; build one
+    /// with [`sign_verification`].
     BlockVerification {
         block_index: u64,
+        block_hash: [u8; 32],
         peer_id: String,
+        mode: NodeMode,
+        /// Protobuf-encoded public key of the verifier.
+        public_key: Vec<u8>,
+        signature: Vec<u8>,
     },
 
     /// A newly formed block broadcast to all peers.
-    Block(Block),
+    Block(Box<Block>),
 }
 
 /// Request/response codec for direct peer-to-peer block sync.
@@ -31,40 +79,282 @@ pub enum SyncRequest {
 
     /// Request the current chain length (tip index) from a peer.
     ChainTip,
+
+    /// Request the payload stored under the given content hash: the
+    /// [`sp_merkle::content_hash`] of the payload, not its Merkle leaf hash.
+    Asset { hash: [u8; 32] },
+
+    /// Request the header of block `index` plus a Merkle inclusion proof for
+    /// transaction `tx_id` within it.
+    BlockWithProof { index: u64, tx_id: Uuid },
+
+    /// Request the ids of the peer's pending transactions, e.g. to see how
+    /// far a broadcast has spread.
+    Mempool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum SyncResponse {
     Blocks(Vec<Block>),
     ChainTip { tip_index: u64 },
+    /// The requested asset, or `None` if the peer doesn't hold it.
+    Asset { hash: [u8; 32], data: Option<Vec<u8>> },
+    /// Block header plus a proof encoded with [`sp_merkle::MerkleProof::to_bytes`].
+    BlockWithProof { header: BlockHeader, proof: Vec<u8> },
+    /// The requested block or transaction is not held by the peer.
+    NotFound,
+    /// The peer is gossip-only: its chain is whatever it happened to relay,
+    /// so it won't answer chain requests.  Ask a full node instead.
+    NotServing,
+    /// Ids of the peer's pending transactions; payloads are not shared.
+    Mempool(Vec<Uuid>),
+    /// The peer can't read requests in the requester's [`WIRE_VERSION`];
+    /// `supported` is the one it speaks.  Encoded as two bytes outside the
+    /// versioned format, so a node on any version can decode it.
+    VersionMismatch { supported: u8 },
+}
+
+/// `value` as bincode behind the [`WIRE_VERSION`] byte.
+fn encode_versioned<T: Serialize>(value: &T) -> Result<Vec<u8>, bincode::Error> {
+    let mut bytes = vec![WIRE_VERSION];
+    bincode::serialize_into(&mut bytes, value)?;
+    Ok(bytes)
+}
+
+/// Inverse of [`encode_versioned`]; any other version byte is refused
+/// before the body is looked at.
+fn decode_versioned<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, DecodeError> {
+    match bytes.split_first() {
+        None => Err(DecodeError::Empty),
+        Some((&WIRE_VERSION, body)) => Ok(bincode::deserialize(body)?),
+        Some((&version, _)) => Err(DecodeError::IncompatibleVersion(version)),
+    }
 }
 
 /// Encode a [`GossipMessage`] to bytes for gossipsub.
 pub fn encode_gossip(msg: &GossipMessage) -> Result<Vec<u8>, bincode::Error> {
-    bincode::serialize(msg)
+    encode_versioned(msg)
 }
 
 /// Decode bytes from gossipsub into a [`GossipMessage`].
-pub fn decode_gossip(bytes: &[u8]) -> Result<GossipMessage, bincode::Error> {
-    bincode::deserialize(bytes)
+pub fn decode_gossip(bytes: &[u8]) -> Result<GossipMessage, DecodeError> {
+    decode_versioned(bytes)
 }
 
 /// Encode a [`SyncRequest`] for the request-response protocol.
 pub fn encode_request(req: &SyncRequest) -> Result<Vec<u8>, bincode::Error> {
-    bincode::serialize(req)
+    encode_versioned(req)
 }
 
 /// Decode bytes into a [`SyncRequest`].
-pub fn decode_request(bytes: &[u8]) -> Result<SyncRequest, bincode::Error> {
-    bincode::deserialize(bytes)
+pub fn decode_request(bytes: &[u8]) -> Result<SyncRequest, DecodeError> {
+    decode_versioned(bytes)
 }
 
 /// Encode a [`SyncResponse`].
 pub fn encode_response(resp: &SyncResponse) -> Result<Vec<u8>, bincode::Error> {
-    bincode::serialize(resp)
+    match resp {
+        SyncResponse::VersionMismatch { supported } => Ok(vec![MISMATCH_VERSION, *supported]),
+        _ => encode_versioned(resp),
+    }
 }
 
 /// Decode bytes into a [`SyncResponse`].
-pub fn decode_response(bytes: &[u8]) -> Result<SyncResponse, bincode::Error> {
-    bincode::deserialize(bytes)
+pub fn decode_response(bytes: &[u8]) -> Result<SyncResponse, DecodeError> {
+    match bytes {
+        [MISMATCH_VERSION, supported] => {
+            Ok(SyncResponse::VersionMismatch { supported: *supported })
+        }
+        _ => decode_versioned(bytes),
+    }
+}
+
+/// Bytes covered by a [`GossipMessage::BlockVerification`] signature.
+pub fn verification_signing_bytes(
+    block_index: u64,
+    block_hash: &[u8; 32],
+    peer_id: &str,
+    mode: &NodeMode,
+) -> Result<Vec<u8>, bincode::Error> {
+    bincode::serialize(&(block_index, block_hash, peer_id, mode))
+}
+
+/// Build a verification of block `block_index` with hash `block_hash`,
+/// signed by `keypair`.
+pub fn sign_verification(
+    keypair: &Keypair,
+    block_index: u64,
+    block_hash: [u8; 32],
+    mode: NodeMode,
+) -> Result<GossipMessage, NodeError> {
+    let peer_id = keypair.public().to_peer_id().to_string();
+    let bytes = verification_signing_bytes(block_index, &block_hash, &peer_id, &mode)
+        .map_err(|e| NodeError::Serialisation(e.to_string()))?;
+    let signature = keypair
+        .sign(&bytes)
+        .map_err(|e| NodeError::Serialisation(e.to_string()))?;
+    Ok(GossipMessage::BlockVerification {
+        block_index,
+        block_hash,
+        peer_id,
+        mode,
+        public_key: keypair.public().encode_protobuf(),
+        signature,
+    })
+}
+
+/// The peer that signed a verification, or `None` if the key doesn't
+/// decode, doesn't belong to `peer_id`, or the signature is wrong.
+pub fn verification_signer(
+    block_index: u64,
+    block_hash: &[u8; 32],
+    peer_id: &str,
+    mode: &NodeMode,
+    public_key: &[u8],
+    signature: &[u8],
+) -> Option<PeerId> {
+    let key = PublicKey::try_decode_protobuf(public_key).ok()?;
+    let signer = key.to_peer_id();
+    if signer.to_string() != peer_id {
+        return None;
+    }
+    let bytes = verification_signing_bytes(block_index, block_hash, peer_id, mode).ok()?;
+    key.verify(&bytes, signature).then_some(signer)
+}
+
+/// What two nodes must agree on to talk to each other, as printed by
+/// `sp-node version-info`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionInfo {
+    pub protocol: &'static str,
+    pub wire_version: u8,
+    pub network_id: String,
+    pub genesis_hash: [u8; 32],
+}
+
+impl VersionInfo {
+    /// The versions and genesis hash a node on `network_id` runs with.
+    pub fn for_network(network_id: &str) -> Self {
+        Self {
+            protocol: PROTOCOL_VERSION,
+            wire_version: WIRE_VERSION,
+            network_id: network_id.to_string(),
+            genesis_hash: Blockchain::new_with_network(network_id).genesis_hash(),
+        }
+    }
+}
+
+impl fmt::Display for VersionInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "protocol      {}", self.protocol)?;
+        writeln!(f, "wire version  {}", self.wire_version)?;
+        writeln!(f, "network id    {}", self.network_id)?;
+        write!(f, "genesis hash  {}", hex::encode(self.genesis_hash))
+    }
+}
+
+/// Agent name advertised when `NodeConfig::agent_version` is unset, e.g.
+/// `sp-node/0.1.0`.
+pub fn default_agent() -> String {
+    format!("sp-node/{}", env!("CARGO_PKG_VERSION"))
+}
+
+/// Identify agent string: `agent` followed by this node's genesis hash, e.g.
+/// `sp-node/0.1.0 genesis/<hex>`.
+pub fn agent_version(agent: &str, genesis_hash: &[u8; 32]) -> String {
+    format!("{agent} genesis/{}", hex::encode(genesis_hash))
+}
+
+/// Genesis hash advertised in a peer's agent string, if it has one.
+/// [`agent_version`] appends it after the configurable agent name, so the
+/// last `genesis/` token is the real one.
+pub fn genesis_from_agent(agent_version: &str) -> Option<[u8; 32]> {
+    let hex_hash = agent_version
+        .split_whitespace()
+        .rev()
+        .find_map(|part| part.strip_prefix("genesis/"))?;
+    let mut hash = [0u8; 32];
+    hex::decode_to_slice(hex_hash, &mut hash).ok()?;
+    Some(hash)
+}
+
+/// DHT key under which a peer announces its listen addresses.
+pub fn peer_record_key(peer_id: &PeerId) -> RecordKey {
+    RecordKey::new(&peer_id.to_bytes())
+}
+
+/// Encode a list of addresses as the value of a peer address record.
+pub fn encode_addresses(addrs: &[Multiaddr]) -> Result<Vec<u8>, bincode::Error> {
+    let raw: Vec<Vec<u8>> = addrs.iter().map(|a| a.to_vec()).collect();
+    bincode::serialize(&raw)
+}
+
+/// Decode a peer address record.  Entries that aren't valid multiaddrs are
+/// skipped.
+pub fn decode_addresses(bytes: &[u8]) -> Result<Vec<Multiaddr>, bincode::Error> {
+    let raw: Vec<Vec<u8>> = bincode::deserialize(bytes)?;
+    Ok(raw
+        .into_iter()
+        .filter_map(|a| Multiaddr::try_from(a).ok())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn current_version_round_trips() {
+        let bytes = encode_request(&SyncRequest::BlocksFrom { from_index: 7 }).unwrap();
+        assert_eq!(bytes[0], WIRE_VERSION);
+        assert!(matches!(
+            decode_request(&bytes),
+            Ok(SyncRequest::BlocksFrom { from_index: 7 })
+        ));
+    }
+
+    #[test]
+    fn unknown_version_is_reported_as_incompatible() {
+        let mut bytes = encode_response(&SyncResponse::NotFound).unwrap();
+        bytes[0] = WIRE_VERSION + 1;
+        assert!(matches!(
+            decode_response(&bytes),
+            Err(DecodeError::IncompatibleVersion(v)) if v == WIRE_VERSION + 1
+        ));
+        assert!(matches!(decode_gossip(&[]), Err(DecodeError::Empty)));
+        assert!(matches!(decode_gossip(&[WIRE_VERSION, 0xff]), Err(DecodeError::Malformed(_))));
+    }
+
+    #[test]
+    fn version_mismatch_reads_the_same_in_every_version() {
+        let reply = SyncResponse::VersionMismatch { supported: WIRE_VERSION };
+        let bytes = encode_response(&reply).unwrap();
+        assert_eq!(bytes, vec![0, WIRE_VERSION]);
+        assert!(matches!(
+            decode_response(&bytes),
+            Ok(SyncResponse::VersionMismatch { supported }) if supported == WIRE_VERSION
+        ));
+        assert!(matches!(decode_response(&[0]), Err(DecodeError::IncompatibleVersion(0))));
+    }
+
+    #[test]
+    fn the_appended_genesis_wins_over_one_in_the_agent_name() {
+        let genesis = [7; 32];
+        let spoofed = format!("sp-node/0.1.0 genesis/{}", hex::encode([9; 32]));
+        assert_eq!(genesis_from_agent(&agent_version(&spoofed, &genesis)), Some(genesis));
+        assert_eq!(genesis_from_agent("sp-node/0.1.0"), None);
+    }
+
+    #[test]
+    fn version_info_reports_the_default_network() {
+        let network_id = crate::NodeConfig::default().network_id;
+        let info = VersionInfo::for_network(&network_id);
+        assert_eq!(info.protocol, "/sp/1.0.0");
+        assert_eq!(info.wire_version, WIRE_VERSION);
+        assert_eq!(info.genesis_hash, Blockchain::new().genesis_hash());
+
+        let printed = info.to_string();
+        assert!(printed.contains(&format!("network id    {network_id}")));
+        assert!(printed.contains(&hex::encode(info.genesis_hash)));
+    }
 }
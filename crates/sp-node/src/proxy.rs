@@ -0,0 +1,197 @@
+//! Outbound TCP through a SOCKS5 proxy such as Tor.
+//!
+//! [`Socks5Transport`] hands every dial to the proxy as a CONNECT request
+//! instead of opening a socket to the peer, so the peer only ever sees the
+//! proxy.  DNS names are passed through unresolved, which is what lets
+//! `/dns/<name>.onion/tcp/<port>` reach a hidden service.  The transport never
+//! listens; [`Node`](crate::Node) pairs it with a loopback TCP listener for
+//! inbound connections forwarded by the proxy.
+
+use std::{
+    io,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::future::BoxFuture;
+use libp2p::{
+    core::transport::{DialOpts, ListenerId, TransportError, TransportEvent},
+    multiaddr::Protocol,
+    tcp::tokio::TcpStream,
+    Multiaddr, Transport,
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// Dials TCP multiaddrs through the SOCKS5 proxy at `proxy`.
+#[derive(Debug, Clone, Copy)]
+pub struct Socks5Transport {
+    proxy: SocketAddr,
+}
+
+impl Socks5Transport {
+    pub fn new(proxy: SocketAddr) -> Self {
+        Self { proxy }
+    }
+}
+
+/// Where the proxy should connect to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Target {
+    Ip(IpAddr),
+    Domain(String),
+}
+
+/// Split `/ip4|ip6|dns|dns4|dns6/<host>/tcp/<port>[/p2p/<id>]` into a target
+/// and port; anything else is not ours to dial.
+fn target_of(addr: &Multiaddr) -> Option<(Target, u16)> {
+    let mut iter = addr.iter();
+    let target = match iter.next()? {
+        Protocol::Ip4(ip) => Target::Ip(ip.into()),
+        Protocol::Ip6(ip) => Target::Ip(ip.into()),
+        Protocol::Dns(name) | Protocol::Dns4(name) | Protocol::Dns6(name) => {
+            Target::Domain(name.into_owned())
+        }
+        _ => return None,
+    };
+    let Protocol::Tcp(port) = iter.next()? else { return None };
+    match iter.next() {
+        None | Some(Protocol::P2p(_)) => Some((target, port)),
+        Some(_) => None,
+    }
+}
+
+/// Open a connection to `target:port` through the proxy (RFC 1928, no
+/// authentication).
+async fn connect(proxy: SocketAddr, target: Target, port: u16) -> io::Result<TcpStream> {
+    let mut stream = tokio::net::TcpStream::connect(proxy).await?;
+
+    stream.write_all(&[5, 1, 0]).await?;
+    let mut choice = [0u8; 2];
+    stream.read_exact(&mut choice).await?;
+    if choice != [5, 0] {
+        return Err(io::Error::other("proxy refused unauthenticated SOCKS5"));
+    }
+
+    let mut request = vec![5, 1, 0];
+    match &target {
+        Target::Ip(IpAddr::V4(ip)) => {
+            request.push(1);
+            request.extend_from_slice(&ip.octets());
+        }
+        Target::Ip(IpAddr::V6(ip)) => {
+            request.push(4);
+            request.extend_from_slice(&ip.octets());
+        }
+        Target::Domain(name) => {
+            let len = u8::try_from(name.len())
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "host name too long"))?;
+            request.push(3);
+            request.push(len);
+            request.extend_from_slice(name.as_bytes());
+        }
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await?;
+
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply).await?;
+    if reply[1] != 0 {
+        return Err(io::Error::other(format!("proxy CONNECT failed with code {}", reply[1])));
+    }
+    // Skip the bound address the proxy reports; it is of no use to us.
+    let bound = match reply[3] {
+        1 => 4,
+        4 => 16,
+        3 => stream.read_u8().await? as usize,
+        other => {
+            return Err(io::Error::other(format!("proxy sent address type {other}")));
+        }
+    };
+    let mut skip = vec![0u8; bound + 2];
+    stream.read_exact(&mut skip).await?;
+
+    Ok(TcpStream(stream))
+}
+
+impl Transport for Socks5Transport {
+    type Output = TcpStream;
+    type Error = io::Error;
+    type ListenerUpgrade = BoxFuture<'static, io::Result<TcpStream>>;
+    type Dial = BoxFuture<'static, io::Result<TcpStream>>;
+
+    fn listen_on(
+        &mut self,
+        _id: ListenerId,
+        addr: Multiaddr,
+    ) -> Result<(), TransportError<Self::Error>> {
+        Err(TransportError::MultiaddrNotSupported(addr))
+    }
+
+    fn remove_listener(&mut self, _id: ListenerId) -> bool {
+        false
+    }
+
+    fn dial(
+        &mut self,
+        addr: Multiaddr,
+        _opts: DialOpts,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let Some((target, port)) = target_of(&addr) else {
+            return Err(TransportError::MultiaddrNotSupported(addr));
+        };
+        let proxy = self.proxy;
+        Ok(Box::pin(connect(proxy, target, port)))
+    }
+
+    fn poll(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<TransportEvent<Self::ListenerUpgrade, Self::Error>> {
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_tcp_addresses_are_proxied() {
+        let onion: Multiaddr = "/dns/abcdef.onion/tcp/51025".parse().unwrap();
+        assert_eq!(target_of(&onion), Some((Target::Domain("abcdef.onion".into()), 51025)));
+        let ip: Multiaddr = "/ip4/10.0.0.1/tcp/4001".parse().unwrap();
+        assert_eq!(target_of(&ip), Some((Target::Ip([10, 0, 0, 1].into()), 4001)));
+        assert_eq!(target_of(&"/ip4/10.0.0.1/udp/4001/quic-v1".parse().unwrap()), None);
+        assert_eq!(target_of(&"/memory/1".parse().unwrap()), None);
+    }
+
+    #[tokio::test]
+    async fn connect_speaks_socks5_to_the_proxy() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut conn, _) = listener.accept().await.unwrap();
+            let mut greeting = [0u8; 3];
+            conn.read_exact(&mut greeting).await.unwrap();
+            conn.write_all(&[5, 0]).await.unwrap();
+            let mut request = [0u8; 5 + 12 + 2];
+            conn.read_exact(&mut request).await.unwrap();
+            conn.write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0]).await.unwrap();
+            conn.write_all(b"hi").await.unwrap();
+            (greeting, request)
+        });
+
+        let target = Target::Domain("peer.example".into());
+        let TcpStream(mut stream) = connect(proxy, target, 4001).await.unwrap();
+        let mut hi = [0u8; 2];
+        stream.read_exact(&mut hi).await.unwrap();
+        assert_eq!(&hi, b"hi");
+
+        let (greeting, request) = server.await.unwrap();
+        assert_eq!(greeting, [5, 1, 0]);
+        assert_eq!(&request[..5], &[5, 1, 0, 3, 12]);
+        assert_eq!(&request[5..17], b"peer.example");
+        assert_eq!(&request[17..], &4001u16.to_be_bytes());
+    }
+}
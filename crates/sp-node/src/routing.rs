@@ -0,0 +1,49 @@
+//! On-disk snapshot of known peer addresses.
+//!
+//! Written on [`Node::shutdown`](crate::Node::shutdown) and read back in
+//! [`Node::new`](crate::Node::new) to re-seed the Kademlia routing table, so a
+//! restarted node has bootstrap targets straight away instead of waiting for
+//! discovery to find everyone again.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::Path,
+};
+
+use libp2p::{Multiaddr, PeerId};
+
+/// File name of the snapshot inside the node's data directory.
+pub const ROUTING_FILE: &str = "routing.json";
+
+/// Read a snapshot.  A missing or unreadable file yields an empty map, and
+/// entries that don't parse are skipped.
+pub fn load(path: &Path) -> HashMap<PeerId, Vec<Multiaddr>> {
+    let raw: BTreeMap<String, Vec<String>> = fs::read_to_string(path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+
+    raw.into_iter()
+        .filter_map(|(peer, addrs)| {
+            let peer = peer.parse::<PeerId>().ok()?;
+            let addrs: Vec<Multiaddr> = addrs.iter().filter_map(|a| a.parse().ok()).collect();
+            (!addrs.is_empty()).then_some((peer, addrs))
+        })
+        .collect()
+}
+
+/// Overwrite the snapshot at `path`, creating its directory if needed.
+pub fn save(path: &Path, peers: &HashMap<PeerId, Vec<Multiaddr>>) -> io::Result<()> {
+    let raw: BTreeMap<String, Vec<String>> = peers
+        .iter()
+        .filter(|(_, addrs)| !addrs.is_empty())
+        .map(|(peer, addrs)| (peer.to_string(), addrs.iter().map(|a| a.to_string()).collect()))
+        .collect();
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let json = serde_json::to_string_pretty(&raw).map_err(io::Error::other)?;
+    fs::write(path, json)
+}
@@ -0,0 +1,83 @@
+//! Rolling count of finalised transactions.
+//!
+//! [`Node`](crate::Node) records each block's transaction count as the block
+//! finalises; the rate only looks at the last `window`, so it decays back to
+//! zero once blocks stop finalising while the total keeps growing.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Window the node averages its finalised-transaction rate over.
+pub const THROUGHPUT_WINDOW: Duration = Duration::from_secs(5 * 60);
+
+/// A running total plus the samples recorded within the last `window`.
+#[derive(Debug, Clone)]
+pub struct Throughput {
+    window: Duration,
+    samples: VecDeque<(Instant, usize)>,
+    total: u64,
+}
+
+impl Throughput {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            samples: VecDeque::new(),
+            total: 0,
+        }
+    }
+
+    /// Count `n` items at `now`.
+    pub fn record(&mut self, now: Instant, n: usize) {
+        self.total += n as u64;
+        self.samples.push_back((now, n));
+        while let Some(&(at, _)) = self.samples.front() {
+            if now.duration_since(at) <= self.window {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    /// Everything recorded so far.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Items per minute over the window ending at `now`.
+    pub fn per_minute(&self, now: Instant) -> f64 {
+        let recent: usize = self
+            .samples
+            .iter()
+            .filter(|(at, _)| now.saturating_duration_since(*at) <= self.window)
+            .map(|(_, n)| n)
+            .sum();
+        recent as f64 * 60.0 / self.window.as_secs_f64()
+    }
+}
+
+impl Default for Throughput {
+    fn default() -> Self {
+        Self::new(THROUGHPUT_WINDOW)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_decays_once_samples_leave_the_window() {
+        let start = Instant::now();
+        let mut t = Throughput::new(Duration::from_secs(60));
+        t.record(start, 10);
+        t.record(start + Duration::from_secs(30), 5);
+
+        assert_eq!(t.per_minute(start + Duration::from_secs(30)), 15.0);
+        assert_eq!(t.per_minute(start + Duration::from_secs(61)), 5.0);
+        assert_eq!(t.per_minute(start + Duration::from_secs(200)), 0.0);
+        assert_eq!(t.total(), 15);
+    }
+}
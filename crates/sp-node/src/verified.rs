@@ -0,0 +1,53 @@
+//! On-disk record of the blocks this node has verified.
+//!
+//! [`Node::send_verification`](crate::Node::send_verification) consults it so
+//! a node never verifies the same block twice, even across a restart that
+//! gives it a different peer id.  Blocks are recorded by hash rather than
+//! index: after a reorg or a sync the block at an index may be a different
+//! one, and that one has not been verified yet.
+//!
+//! The file holds one hex hash per line, so a verification appends a line
+//! instead of rewriting the record; it is rewritten only when pruned blocks
+//! are dropped from it.
+
+use std::{
+    collections::BTreeSet,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::Path,
+};
+
+/// File name of the record inside the node's data directory.
+pub const VERIFIED_FILE: &str = "verified.txt";
+
+/// Read the set of verified block hashes.  A missing or unreadable file
+/// yields an empty set, and lines that aren't hex block hashes are skipped.
+pub fn load(path: &Path) -> BTreeSet<[u8; 32]> {
+    let text = fs::read_to_string(path).unwrap_or_default();
+    text.lines()
+        .filter_map(|line| {
+            let mut hash = [0u8; 32];
+            hex::decode_to_slice(line.trim(), &mut hash).ok()?;
+            Some(hash)
+        })
+        .collect()
+}
+
+/// Add `hash` to the record at `path`, creating it and its directory if
+/// needed.
+pub fn append(path: &Path, hash: &[u8; 32]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", hex::encode(hash))
+}
+
+/// Overwrite the record at `path`, creating its directory if needed.
+pub fn save(path: &Path, hashes: &BTreeSet<[u8; 32]>) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let lines: String = hashes.iter().map(|hash| hex::encode(hash) + "\n").collect();
+    fs::write(path, lines)
+}
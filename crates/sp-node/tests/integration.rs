@@ -6,11 +6,25 @@
 /// Network-level tests (gossipsub, peer discovery) require a running async
 /// runtime and real ports, so they are marked `#[tokio::test]` and guarded
 /// with short timeouts.
+use std::time::Duration;
+
+use futures::StreamExt;
+use libp2p::{
+    multiaddr::Protocol,
+    ping,
+    request_response::{self, ProtocolSupport},
+    swarm::{NetworkBehaviour, SwarmEvent},
+    Multiaddr, PeerId,
+};
 use sp_blockchain::Blockchain;
 use sp_merkle::MerkleTree;
-use sp_node::{DiscoveryMode, Node, NodeConfig, NodeMode};
+use sp_node::{
+    await_event, behaviour::SyncCodec, DiscoveryMode, Node, NodeConfig, NodeError, NodeEvent,
+    NodeMode,
+};
 use sp_sync::SyncStrategy;
 use sp_transaction::{Transaction, TransactionType};
+use tokio::sync::mpsc::Receiver;
 
 // ── Pure data-path tests (no network) ───────────────────────────────────────
 
@@ -137,13 +151,20 @@ async fn node_starts_and_listens() {
         sync_strategy: SyncStrategy::OnDemand,
         ..Default::default()
     };
-    let (node, _events) = Node::new(config)
+    let (mut node, mut events) = Node::new(config)
         .await
         .expect("node should start");
+    tokio::spawn(async move { node.run().await });
 
-    // If we reach here without a panic the node has successfully bound a port
-    // and set up all sub-protocols.
-    let _ = node.peer_id(); // peer_id is non-zero
+    let listening = await_event(
+        &mut events,
+        |ev| matches!(ev, NodeEvent::Listening(_)),
+        Duration::from_secs(10),
+    )
+    .await
+    .expect("node should report a listen address");
+    let NodeEvent::Listening(addr) = listening else { unreachable!() };
+    assert!(addr.iter().any(|p| matches!(p, Protocol::Tcp(port) if port != 0)), "{addr}");
 }
 
 #[tokio::test]
@@ -211,8 +232,338 @@ async fn node_broadcasts_transaction_without_peers() {
 
     let tx = Transaction::new(TransactionType::UserRegistered, b"alice".to_vec());
 
-    // Publishing to gossipsub with no connected peers returns an error from
-    // libp2p ("insufficient peers"), which we propagate — that's fine.
-    // The important thing is that the node is alive and doesn't panic.
-    let _ = node.broadcast_transaction(tx);
+    // With no one subscribed there is no one to broadcast to; the node says
+    // so instead of keeping the transaction.
+    let err = node.broadcast_transaction(tx).unwrap_err();
+    assert!(matches!(err, NodeError::NoPeers(_)), "unexpected error: {err}");
+}
+
+// ── Two-node tests ───────────────────────────────────────────────────────────
+
+/// Drive `node` until an event matching `pred` arrives, failing after 10 s.
+async fn next_matching(
+    node: &mut Node,
+    events: &mut Receiver<NodeEvent>,
+    pred: impl Fn(&NodeEvent) -> bool,
+) -> NodeEvent {
+    tokio::select! {
+        _ = node.run() => unreachable!("Node::run never returns"),
+        ev = await_event(events, pred, Duration::from_secs(10)) => {
+            ev.expect("timed out waiting for node event")
+        }
+    }
+}
+
+/// Drive two nodes concurrently for `duration`.
+async fn run_both(a: &mut Node, b: &mut Node, duration: Duration) {
+    let _ = tokio::time::timeout(duration, async { tokio::join!(a.run(), b.run()) }).await;
+}
+
+/// Start a node on an OS-assigned port and return it with its loopback address.
+async fn start_loopback_node(
+    config: NodeConfig,
+) -> (Node, Receiver<NodeEvent>, Multiaddr) {
+    let (mut node, mut events) = Node::new(NodeConfig { port: 0, ..config })
+        .await
+        .expect("node should start");
+    let ev = next_matching(&mut node, &mut events, |ev| {
+        matches!(ev, NodeEvent::Listening(addr)
+            if addr.iter().any(|p| p == Protocol::Ip4([127, 0, 0, 1].into())))
+    })
+    .await;
+    let NodeEvent::Listening(addr) = ev else { unreachable!() };
+    (node, events, addr)
+}
+
+#[tokio::test]
+async fn node_fetches_asset_by_content_hash_from_peer() {
+    let (mut holder, _holder_events, holder_addr) =
+        start_loopback_node(NodeConfig::default()).await;
+    let holder_id = holder.peer_id();
+    let hash = holder.store_asset(b"project banner".to_vec());
+    tokio::spawn(async move { holder.run().await });
+
+    let (mut fetcher, mut events, _) = start_loopback_node(NodeConfig::default()).await;
+    fetcher.dial(holder_addr).unwrap();
+    next_matching(&mut fetcher, &mut events, |ev| {
+        matches!(ev, NodeEvent::PeerConnected(pid) if *pid == holder_id)
+    })
+    .await;
+
+    fetcher.request_asset(holder_id, hash).unwrap();
+    let ev = next_matching(&mut fetcher, &mut events, |ev| {
+        matches!(ev, NodeEvent::AssetReceived { .. })
+    })
+    .await;
+
+    let NodeEvent::AssetReceived { hash: got, data } = ev else { unreachable!() };
+    assert_eq!(got, hash);
+    assert_eq!(data, b"project banner");
+    assert!(fetcher.assets().contains(&hash), "full node should keep the asset");
+}
+
+#[tokio::test]
+async fn node_fetches_block_header_and_inclusion_proof_from_peer() {
+    let (mut holder, _holder_events, holder_addr) =
+        start_loopback_node(NodeConfig::default()).await;
+    let holder_id = holder.peer_id();
+    let (mut fetcher, mut events, _) = start_loopback_node(NodeConfig::default()).await;
+    fetcher.dial(holder_addr).unwrap();
+
+    // Publishing only succeeds once the holder has learnt the fetcher's topic
+    // subscriptions, so keep both nodes running until it does.
+    let tx = Transaction::new(TransactionType::ProjectPosted, b"project-42".to_vec());
+    let mut published = false;
+    for _ in 0..50 {
+        run_both(&mut holder, &mut fetcher, Duration::from_millis(100)).await;
+        if holder.broadcast_transaction(tx.clone()).is_ok() {
+            published = true;
+            break;
+        }
+    }
+    assert!(published, "holder never saw the fetcher subscribe");
+    let _ = holder.form_block();
+    assert_eq!(holder.blockchain().len(), 2);
+    tokio::spawn(async move { holder.run().await });
+
+    fetcher.request_block_proof(holder_id, 1, tx.id).unwrap();
+    let ev = next_matching(&mut fetcher, &mut events, |ev| {
+        matches!(ev, NodeEvent::BlockProofReceived { .. })
+    })
+    .await;
+
+    let NodeEvent::BlockProofReceived { header, proof } = ev else { unreachable!() };
+    assert_eq!(header.index, 1);
+    assert_eq!(proof.leaf_hash, tx.hash().unwrap());
+    assert!(proof.verify(&header.merkle_root));
+}
+
+#[tokio::test]
+async fn node_lists_a_peers_mempool_after_a_broadcast() {
+    let (mut holder, _holder_events, holder_addr) =
+        start_loopback_node(NodeConfig::default()).await;
+    let holder_id = holder.peer_id();
+    let (mut asker, mut events, _) = start_loopback_node(NodeConfig::default()).await;
+    asker.dial(holder_addr).unwrap();
+
+    let tx = Transaction::new(TransactionType::PostCreated, b"still pending".to_vec());
+    let mut published = false;
+    for _ in 0..50 {
+        run_both(&mut holder, &mut asker, Duration::from_millis(100)).await;
+        if holder.broadcast_transaction(tx.clone()).is_ok() {
+            published = true;
+            break;
+        }
+    }
+    assert!(published, "holder never saw the asker subscribe");
+    tokio::spawn(async move { holder.run().await });
+
+    asker.request_mempool(holder_id).unwrap();
+    let ev = next_matching(&mut asker, &mut events, |ev| {
+        matches!(ev, NodeEvent::MempoolReceived { .. })
+    })
+    .await;
+    let NodeEvent::MempoolReceived { peer, ids } = ev else { unreachable!() };
+    assert_eq!(peer, holder_id);
+    assert_eq!(ids, vec![tx.id]);
+}
+
+#[tokio::test]
+async fn extra_topic_message_reaches_a_subscriber() {
+    let config = NodeConfig {
+        extra_topics: vec!["app/chat".into()],
+        ..NodeConfig::default()
+    };
+    let (mut sender, _sender_events, sender_addr) = start_loopback_node(config.clone()).await;
+    let (mut receiver, mut events, _) = start_loopback_node(config).await;
+    receiver.dial(sender_addr).unwrap();
+
+    // As with transactions, publishing fails until the receiver's
+    // subscription has reached the sender.
+    let mut published = false;
+    for _ in 0..50 {
+        run_both(&mut sender, &mut receiver, Duration::from_millis(100)).await;
+        if sender.publish("app/chat", b"hello".to_vec()).is_ok() {
+            published = true;
+            break;
+        }
+    }
+    assert!(published, "sender never saw the receiver subscribe");
+    assert!(sender.publish("sp/tx", b"spoof".to_vec()).is_err(), "built-in topics are reserved");
+    tokio::spawn(async move { sender.run().await });
+
+    let ev = next_matching(&mut receiver, &mut events, |ev| {
+        matches!(ev, NodeEvent::TopicMessage { .. })
+    })
+    .await;
+    let NodeEvent::TopicMessage { topic, data } = ev else { unreachable!() };
+    assert_eq!(topic, "app/chat");
+    assert_eq!(data, b"hello");
+}
+
+#[tokio::test]
+async fn node_resolves_its_own_announced_address_record() {
+    let (mut node, mut events, addr) = start_loopback_node(NodeConfig::default()).await;
+    let peer_id = node.peer_id();
+
+    node.find_peer(peer_id);
+    let ev = next_matching(&mut node, &mut events, |ev| {
+        matches!(ev, NodeEvent::PeerResolved { .. } | NodeEvent::Error(_))
+    })
+    .await;
+
+    let NodeEvent::PeerResolved { peer_id: resolved, addrs } = ev else {
+        panic!("expected an address record, got {ev:?}");
+    };
+    assert_eq!(resolved, peer_id);
+    assert!(addrs.contains(&addr), "record should list {addr}, got {addrs:?}");
+}
+
+/// Bare swarm that accepts sync requests and never answers them.
+#[derive(NetworkBehaviour)]
+#[behaviour(prelude = "libp2p::swarm::derive_prelude")]
+struct SilentBehaviour {
+    ping: ping::Behaviour,
+    request_response: request_response::Behaviour<SyncCodec>,
+}
+
+/// Start a [`SilentBehaviour`] swarm on loopback and return its id and address.
+async fn spawn_silent_peer() -> (PeerId, Multiaddr) {
+    let mut swarm = libp2p::SwarmBuilder::with_new_identity()
+        .with_tokio()
+        .with_tcp(
+            libp2p::tcp::Config::default(),
+            libp2p::noise::Config::new,
+            libp2p::yamux::Config::default,
+        )
+        .unwrap()
+        .with_behaviour(|_| SilentBehaviour {
+            ping: ping::Behaviour::new(ping::Config::new()),
+            request_response: request_response::Behaviour::with_codec(
+                SyncCodec::new(1024),
+                [("/sp/sync/1.0.0".to_string(), ProtocolSupport::Full)],
+                request_response::Config::default(),
+            ),
+        })
+        .unwrap()
+        .build();
+    swarm.listen_on("/ip4/127.0.0.1/tcp/0".parse().unwrap()).unwrap();
+    let addr = loop {
+        if let SwarmEvent::NewListenAddr { address, .. } = swarm.select_next_some().await {
+            break address;
+        }
+    };
+    let peer_id = *swarm.local_peer_id();
+
+    tokio::spawn(async move {
+        // Holding the channels keeps the requests open without a response.
+        let mut unanswered = Vec::new();
+        loop {
+            if let SwarmEvent::Behaviour(SilentBehaviourEvent::RequestResponse(
+                request_response::Event::Message {
+                    message: request_response::Message::Request { channel, .. },
+                    ..
+                },
+            )) = swarm.select_next_some().await
+            {
+                unanswered.push(channel);
+            }
+        }
+    });
+    (peer_id, addr)
+}
+
+#[tokio::test]
+async fn unanswered_sync_request_times_out_with_error_event() {
+    let timeout = Duration::from_millis(500);
+    let (silent_id, silent_addr) = spawn_silent_peer().await;
+    let (mut fetcher, mut events, _) =
+        start_loopback_node(NodeConfig { request_timeout: timeout, ..NodeConfig::default() })
+            .await;
+    assert_eq!(fetcher.request_timeout(), timeout);
+
+    // Connecting makes the fetcher ask the silent peer for its chain tip.
+    fetcher.dial(silent_addr).unwrap();
+    let ev = next_matching(&mut fetcher, &mut events, |ev| matches!(ev, NodeEvent::Error(_))).await;
+    let NodeEvent::Error(msg) = ev else { unreachable!() };
+    assert!(msg.contains(&silent_id.to_string()), "unexpected error: {msg}");
+    assert!(msg.to_lowercase().contains("timeout"), "unexpected error: {msg}");
+}
+
+#[tokio::test]
+async fn node_dials_a_peer_by_hostname() {
+    let (mut listener, _listener_events, addr) = start_loopback_node(NodeConfig::default()).await;
+    let listener_id = listener.peer_id();
+    let port = addr.iter().find_map(|p| match p {
+        Protocol::Tcp(port) => Some(port),
+        _ => None,
+    });
+    tokio::spawn(async move { listener.run().await });
+
+    // What the TUI turns `localhost:<port>` into.
+    let (mut dialer, mut events, _) = start_loopback_node(NodeConfig::default()).await;
+    dialer.dial(format!("/dns4/localhost/tcp/{}", port.unwrap()).parse().unwrap()).unwrap();
+    next_matching(&mut dialer, &mut events, |ev| {
+        matches!(ev, NodeEvent::PeerConnected(pid) if *pid == listener_id)
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn dialing_an_unreachable_address_reports_dial_failed() {
+    let (mut node, mut events, _) = start_loopback_node(NodeConfig::default()).await;
+    // Bind and drop a listener so the port is valid but nothing answers.
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let addr: Multiaddr = format!("/ip4/127.0.0.1/tcp/{port}").parse().unwrap();
+
+    node.dial(addr.clone()).unwrap();
+    let ev = next_matching(&mut node, &mut events, |ev| matches!(ev, NodeEvent::DialFailed { .. }))
+        .await;
+
+    let NodeEvent::DialFailed { addr: failed, peer_id, error } = ev else { unreachable!() };
+    assert_eq!(failed, Some(addr));
+    assert_eq!(peer_id, None);
+    assert!(!error.is_empty());
+}
+
+#[tokio::test]
+async fn a_pruned_node_serves_only_the_blocks_it_kept() {
+    let dir = std::env::temp_dir().join(format!("spn-pruned-test-{}", std::process::id()));
+    let mut chain = Blockchain::new_with_network(&NodeConfig::default().network_id);
+    for i in 0..3 {
+        chain.add_block(vec![Transaction::new(TransactionType::PostCreated, vec![i])]).unwrap();
+    }
+    let behind = chain.clone();
+    chain.add_block(vec![Transaction::new(TransactionType::PostCreated, vec![3])]).unwrap();
+    for index in 1..=3 {
+        for peer in ["peer-1", "peer-2", "peer-3"] {
+            chain.verify_block(index, peer.into()).unwrap();
+        }
+    }
+    assert_eq!(chain.prune_before(3), Some(2));
+    sp_node::chain::save(&dir.join("holder").join(sp_node::chain::CHAIN_FILE), &chain).unwrap();
+    sp_node::chain::save(&dir.join("behind").join(sp_node::chain::CHAIN_FILE), &behind).unwrap();
+
+    let holder_config = NodeConfig { data_dir: Some(dir.join("holder")), ..NodeConfig::default() };
+    let (mut holder, _holder_events, holder_addr) = start_loopback_node(holder_config).await;
+    assert_eq!(holder.blockchain().tip_index(), 4);
+    tokio::spawn(async move { holder.run().await });
+
+    // A fresh node needs blocks 1 and 2, which the holder no longer has.
+    let (mut fresh, mut events, _) = start_loopback_node(NodeConfig::default()).await;
+    fresh.dial(holder_addr.clone()).unwrap();
+    let ev = next_matching(&mut fresh, &mut events, |ev| matches!(ev, NodeEvent::Error(_))).await;
+    let NodeEvent::Error(msg) = ev else { unreachable!() };
+    assert!(msg.contains("does not hold"), "unexpected error: {msg}");
+    assert_eq!(fresh.blockchain().len(), 1);
+
+    // A node that already holds everything before the kept blocks catches up.
+    let behind_config = NodeConfig { data_dir: Some(dir.join("behind")), ..NodeConfig::default() };
+    let (mut caught_up, mut events, _) = start_loopback_node(behind_config).await;
+    caught_up.dial(holder_addr).unwrap();
+    next_matching(&mut caught_up, &mut events, |ev| matches!(ev, NodeEvent::ChainSynced { .. }))
+        .await;
+    assert_eq!(caught_up.blockchain().tip().hash(), chain.tip().hash());
+
+    std::fs::remove_dir_all(&dir).unwrap();
 }
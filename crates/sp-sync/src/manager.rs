@@ -31,18 +31,25 @@ impl SyncManager {
     /// be applied locally according to the active strategy.
     ///
     /// The `local` chain is used to determine the starting point (we only look
-    /// at blocks the local node does not yet have).
+    /// at blocks the local node does not yet have).  Only a gap-free run of
+    /// blocks following on from the local tip is ever selected, since nothing
+    /// else could be appended.
     pub fn blocks_to_sync<'a>(
         &mut self,
         local: &Blockchain,
         remote_blocks: &'a [Block],
     ) -> Result<Vec<&'a Block>, SyncError> {
-        let local_len = local.len() as u64;
+        let Some(next) = self.first_index_to_request(local) else {
+            return Ok(Vec::new());
+        };
 
-        // Only consider blocks beyond our current tip.
+        // Only consider the run of blocks that extends our current tip.
         let new_blocks: Vec<&Block> = remote_blocks
             .iter()
-            .filter(|b| b.index >= local_len)
+            .skip_while(|b| b.index < next)
+            .zip(next..)
+            .take_while(|(b, index)| b.index == *index)
+            .map(|(b, _)| b)
             .collect();
 
         match &self.strategy {
@@ -54,7 +61,7 @@ impl SyncManager {
                 }
                 Ok(new_blocks
                     .into_iter()
-                    .filter(|b| b.timestamp >= *from && b.timestamp <= *to)
+                    .take_while(|b| b.timestamp >= *from && b.timestamp <= *to)
                     .collect())
             }
 
@@ -70,6 +77,58 @@ impl SyncManager {
                 }
                 Ok(selected)
             }
+
+            SyncStrategy::FromIndex { .. } => Ok(new_blocks),
+        }
+    }
+
+    /// Index of the first block to ask peers for, or `None` if nothing they
+    /// could send would be kept.
+    ///
+    /// That is the block after the local tip, except under `FromIndex` when
+    /// the tip is short of the checkpoint: blocks from `start` would leave a
+    /// gap, and the ones below it are not wanted.
+    pub fn first_index_to_request(&self, local: &Blockchain) -> Option<u64> {
+        let next = local.tip_index().checked_add(1)?;
+        match self.strategy {
+            SyncStrategy::FromIndex { start } if start > next => None,
+            _ => Some(next),
+        }
+    }
+
+    /// Select which of `blocks` to hand to a peer that asked for them, so the
+    /// strategy is honoured when serving as well as when fetching.
+    ///
+    /// `TimeRange` serves the run of in-range blocks starting at the first
+    /// one, stopping before any gap so the requester can append them all, and
+    /// `SizeLimit` caps the size of each response without touching the
+    /// download counter.  `OnDemand`
+    /// and `FromIndex` restrict what we fetch, not what we share, so they
+    /// serve everything.
+    pub fn blocks_to_serve<'a>(&self, blocks: &'a [Block]) -> Result<Vec<&'a Block>, SyncError> {
+        match &self.strategy {
+            SyncStrategy::OnDemand | SyncStrategy::FromIndex { .. } => Ok(blocks.iter().collect()),
+
+            SyncStrategy::TimeRange { from, to } => {
+                if from > to {
+                    return Err(SyncError::InvalidTimeRange);
+                }
+                let in_range = |b: &&Block| b.timestamp >= *from && b.timestamp <= *to;
+                Ok(blocks.iter().skip_while(|b| !in_range(b)).take_while(in_range).collect())
+            }
+
+            SyncStrategy::SizeLimit { max_bytes } => {
+                let mut served = 0;
+                let mut selected = Vec::new();
+                for block in blocks {
+                    served += estimated_block_size(block);
+                    if served > *max_bytes {
+                        break;
+                    }
+                    selected.push(block);
+                }
+                Ok(selected)
+            }
         }
     }
 
@@ -84,12 +143,10 @@ impl SyncManager {
     }
 }
 
-/// Rough byte estimate for a block: sum of serialised transaction payload sizes
-/// plus a fixed header overhead.
+/// Byte size of a block as it travels between peers: its full serialised
+/// length, so ids, signatures and verifications count as well as payloads.
 fn estimated_block_size(block: &Block) -> u64 {
-    let payload_bytes: usize = block.transactions.iter().map(|tx| tx.payload.len()).sum();
-    // 256 bytes overhead per block for header fields (hashes, index, timestamp).
-    (payload_bytes + 256) as u64
+    block.serialised_size() as u64
 }
 
 #[cfg(test)]
@@ -126,13 +183,76 @@ mod tests {
         let local = Blockchain::new();
         let remote = make_chain_with_blocks(10);
 
-        // Each block has payload ~5 bytes + 256 overhead = 261 bytes.
-        // Limit of 600 bytes should let through at most 2 blocks.
-        let mut mgr = SyncManager::new(SyncStrategy::SizeLimit { max_bytes: 600 });
+        // A limit just short of three blocks lets through two.
+        let block_size = remote.blocks()[1].serialised_size() as u64;
+        let max_bytes = 3 * block_size - 1;
+        let mut mgr = SyncManager::new(SyncStrategy::SizeLimit { max_bytes });
         let blocks = mgr.blocks_to_sync(&local, remote.blocks()).unwrap();
         assert!(blocks.len() <= 2);
     }
 
+    #[test]
+    fn estimate_tracks_the_serialised_size() {
+        let txs: Vec<Transaction> = (0..20)
+            .map(|_| {
+                let mut tx = Transaction::new(TransactionType::PostCreated, vec![0; 1024]);
+                tx.sign(vec![1; 64]);
+                tx
+            })
+            .collect();
+        let tx_bytes: usize = txs.iter().map(Transaction::serialised_size).sum();
+        let mut chain = Blockchain::new();
+        let block = chain.add_block(txs).unwrap();
+
+        let estimate = estimated_block_size(block);
+        // Only the header fields come on top of the transactions.
+        assert!(estimate >= tx_bytes as u64);
+        assert!(estimate - tx_bytes as u64 <= 256, "{estimate} vs {tx_bytes}");
+        // Ids and signatures alone outweigh the old flat 256-byte allowance.
+        assert!(estimate > 20 * 1024 + 256);
+    }
+
+    #[test]
+    fn from_index_skips_blocks_below_the_checkpoint() {
+        let local = Blockchain::new();
+        let remote = make_chain_with_blocks(5);
+        let mut mgr = SyncManager::new(SyncStrategy::FromIndex { start: 3 });
+        // Short of the checkpoint, blocks from it could not be appended.
+        assert_eq!(mgr.first_index_to_request(&local), None);
+        assert!(mgr.blocks_to_sync(&local, remote.blocks()).unwrap().is_empty());
+
+        let mut local = Blockchain::new();
+        local.try_replace(remote.blocks()[..3].to_vec()).unwrap();
+        assert_eq!(mgr.first_index_to_request(&local), Some(3));
+        let blocks = mgr.blocks_to_sync(&local, remote.blocks()).unwrap();
+        assert_eq!(blocks.iter().map(|b| b.index).collect::<Vec<_>>(), vec![3, 4, 5]);
+
+        // Blocks we already hold stay excluded even above the checkpoint.
+        local.try_replace(remote.blocks()[..5].to_vec()).unwrap();
+        let blocks = mgr.blocks_to_sync(&local, remote.blocks()).unwrap();
+        assert_eq!(blocks.iter().map(|b| b.index).collect::<Vec<_>>(), vec![5]);
+    }
+
+    #[test]
+    fn synced_blocks_never_leave_a_gap() {
+        let remote = make_chain_with_blocks(5);
+        let mut gappy = remote.blocks().to_vec();
+        gappy.remove(3);
+
+        let mut mgr = SyncManager::new(SyncStrategy::FromIndex { start: 0 });
+        let blocks = mgr.blocks_to_sync(&Blockchain::new(), &gappy).unwrap();
+        assert_eq!(blocks.iter().map(|b| b.index).collect::<Vec<_>>(), vec![1, 2]);
+
+        // Nor does an out-of-range block in the middle.
+        let mut blocks = remote.blocks().to_vec();
+        for (block, timestamp) in blocks.iter_mut().zip([0, 10, 99, 20, 30, 40]) {
+            block.timestamp = timestamp;
+        }
+        let mut mgr = SyncManager::new(SyncStrategy::TimeRange { from: 5, to: 50 });
+        let synced = mgr.blocks_to_sync(&Blockchain::new(), &blocks).unwrap();
+        assert_eq!(synced.iter().map(|b| b.index).collect::<Vec<_>>(), vec![1]);
+    }
+
     #[test]
     fn time_range_filters_by_timestamp() {
         let local = Blockchain::new();
@@ -148,6 +268,39 @@ mod tests {
         assert!(blocks.is_empty());
     }
 
+    #[test]
+    fn serving_honours_the_strategy() {
+        let chain = make_chain_with_blocks(3);
+
+        let on_demand = SyncManager::new(SyncStrategy::OnDemand);
+        assert_eq!(on_demand.blocks_to_serve(chain.blocks()).unwrap().len(), 4);
+
+        // Genesis has timestamp 0; every later block is stamped "now".
+        let recent = SyncManager::new(SyncStrategy::TimeRange { from: 1, to: i64::MAX });
+        let served = recent.blocks_to_serve(chain.blocks()).unwrap();
+        assert_eq!(served.iter().map(|b| b.index).collect::<Vec<_>>(), vec![1, 2, 3]);
+
+        // Genesis is only a little bigger than the other blocks, so two and a
+        // half blocks' worth covers two of them either way.
+        let max_bytes = chain.blocks()[1].serialised_size() as u64 * 5 / 2;
+        let mut capped = SyncManager::new(SyncStrategy::SizeLimit { max_bytes });
+        assert_eq!(capped.blocks_to_serve(chain.blocks()).unwrap().len(), 2);
+        // Serving does not count against our own download budget.
+        assert_eq!(capped.blocks_to_sync(&Blockchain::new(), chain.blocks()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn time_range_serving_stops_at_the_first_gap() {
+        let mut blocks = make_chain_with_blocks(4).blocks().to_vec();
+        for (block, timestamp) in blocks.iter_mut().zip([0, 10, 20, 99, 30]) {
+            block.timestamp = timestamp;
+        }
+
+        let mgr = SyncManager::new(SyncStrategy::TimeRange { from: 5, to: 50 });
+        let served = mgr.blocks_to_serve(&blocks).unwrap();
+        assert_eq!(served.iter().map(|b| b.index).collect::<Vec<_>>(), vec![1, 2]);
+    }
+
     #[test]
     fn invalid_time_range_returns_error() {
         let local = Blockchain::new();
@@ -15,6 +15,11 @@ pub enum SyncStrategy {
     /// exceeds `max_bytes`.
     SizeLimit { max_bytes: u64 },
 
+    /// Sync only blocks at index `start` and above, e.g. when restoring from
+    /// a known checkpoint.  A chain whose tip is still short of `start`
+    /// syncs nothing, since the blocks from `start` would not link to it.
+    FromIndex { start: u64 },
+
     /// Do not proactively request blocks; only sync when the application
     /// explicitly requests a specific block or transaction.
     OnDemand,
@@ -0,0 +1,112 @@
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::{Transaction, TransactionError, TransactionType};
+
+/// Step-by-step construction of a [`Transaction`].
+///
+/// By default the built transaction gets a random id, exactly like
+/// [`Transaction::new`].  Call [`TransactionBuilder::deterministic`] to derive
+/// the id from the transaction's content instead, so that two nodes recording
+/// the same logical event produce the same id and it can be deduplicated
+/// network-wide.
+#[derive(Debug, Clone)]
+pub struct TransactionBuilder {
+    kind: TransactionType,
+    payload: Vec<u8>,
+    timestamp: Option<i64>,
+    priority: u32,
+    deterministic: bool,
+}
+
+impl TransactionBuilder {
+    pub fn new(kind: TransactionType) -> Self {
+        Self {
+            kind,
+            payload: Vec::new(),
+            timestamp: None,
+            priority: 0,
+            deterministic: false,
+        }
+    }
+
+    /// JSON-encoded domain payload.
+    pub fn payload(mut self, payload: impl Into<Vec<u8>>) -> Self {
+        self.payload = payload.into();
+        self
+    }
+
+    /// Override the creation timestamp (Unix seconds).  Defaults to now.
+    pub fn timestamp(mut self, timestamp: i64) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    /// Block-inclusion priority; higher goes first.  Defaults to `0`.
+    pub fn priority(mut self, priority: u32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Derive the id from content via [`Transaction::content_id`] rather than
+    /// generating a random one.
+    pub fn deterministic(mut self) -> Self {
+        self.deterministic = true;
+        self
+    }
+
+    pub fn build(self) -> Result<Transaction, TransactionError> {
+        let mut tx = Transaction {
+            id: Uuid::new_v4(),
+            kind: self.kind,
+            payload: self.payload,
+            timestamp: self.timestamp.unwrap_or_else(|| Utc::now().timestamp()),
+            priority: self.priority,
+            signature: Vec::new(),
+        };
+        if self.deterministic {
+            tx.id = tx.content_id();
+        }
+        Ok(tx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vote() -> TransactionBuilder {
+        TransactionBuilder::new(TransactionType::VoteCast)
+            .payload(b"{\"proposal\":7}".to_vec())
+            .timestamp(1_700_000_000)
+    }
+
+    #[test]
+    fn deterministic_ids_match_for_identical_content() {
+        let a = vote().deterministic().build().unwrap();
+        let b = vote().deterministic().build().unwrap();
+        assert_eq!(a.id, b.id);
+        assert_eq!(a.id, a.content_id());
+    }
+
+    #[test]
+    fn deterministic_ids_differ_for_different_content() {
+        let a = vote().deterministic().build().unwrap();
+        let b = vote().payload(b"{\"proposal\":8}".to_vec()).deterministic().build().unwrap();
+        assert_ne!(a.id, b.id);
+    }
+
+    #[test]
+    fn priority_defaults_to_zero() {
+        assert_eq!(vote().build().unwrap().priority, 0);
+        assert_eq!(vote().priority(9).build().unwrap().priority, 9);
+    }
+
+    #[test]
+    fn ids_are_random_by_default() {
+        let a = vote().build().unwrap();
+        let b = vote().build().unwrap();
+        assert_ne!(a.id, b.id);
+        assert_eq!(a.content_id(), b.content_id());
+    }
+}
@@ -0,0 +1,159 @@
+use std::{fmt, str::FromStr};
+
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    XChaCha20Poly1305, XNonce,
+};
+
+use uuid::Version;
+
+use crate::{Transaction, TransactionError};
+
+/// Length of the random nonce prepended to an encrypted payload.
+const NONCE_LEN: usize = 24;
+
+/// A symmetric key shared by the members of a network, used to keep private
+/// payloads unreadable to relays that don't hold it.
+///
+/// Parses from 64 hex characters.  `Debug` never prints the key itself.
+#[derive(Clone, PartialEq, Eq)]
+pub struct PayloadKey([u8; 32]);
+
+impl PayloadKey {
+    pub fn new(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new(&self.0.into())
+    }
+}
+
+impl fmt::Debug for PayloadKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PayloadKey(..)")
+    }
+}
+
+impl FromStr for PayloadKey {
+    type Err = TransactionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = hex::decode(s.trim()).map_err(|_| TransactionError::InvalidKey)?;
+        let bytes = <[u8; 32]>::try_from(bytes).map_err(|_| TransactionError::InvalidKey)?;
+        Ok(Self(bytes))
+    }
+}
+
+impl Transaction {
+    /// Replace the payload with `nonce || ciphertext` under `key`
+    /// (XChaCha20-Poly1305).  The hash, and so any Merkle proof, covers the
+    /// ciphertext, so relays can verify the transaction without reading it.
+    ///
+    /// An id derived from the content (see [`Transaction::content_id`]) is
+    /// derived again from the ciphertext, so the transaction still passes
+    /// [`Transaction::validate`].  A signature covers the payload too, so a
+    /// signed transaction is refused with [`TransactionError::AlreadySigned`]:
+    /// encrypt first, then sign.
+    pub fn encrypt_payload(&mut self, key: &PayloadKey) -> Result<(), TransactionError> {
+        if self.is_signed() {
+            return Err(TransactionError::AlreadySigned);
+        }
+        let nonce: [u8; NONCE_LEN] = rand::random();
+        let ciphertext = key
+            .cipher()
+            .encrypt(XNonce::from_slice(&nonce), self.payload.as_slice())
+            .map_err(|_| TransactionError::Encryption)?;
+        let mut payload = nonce.to_vec();
+        payload.extend_from_slice(&ciphertext);
+        self.payload = payload;
+        if self.id.get_version() == Some(Version::Sha1) {
+            self.id = self.content_id();
+        }
+        Ok(())
+    }
+
+    /// The plaintext of a payload sealed with [`Transaction::encrypt_payload`].
+    /// Fails if `key` is not the one it was sealed with, or if the payload
+    /// was never encrypted or has been tampered with.
+    pub fn decrypt_payload(&self, key: &PayloadKey) -> Result<Vec<u8>, TransactionError> {
+        if self.payload.len() < NONCE_LEN {
+            return Err(TransactionError::Decryption);
+        }
+        let (nonce, ciphertext) = self.payload.split_at(NONCE_LEN);
+        key.cipher()
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| TransactionError::Decryption)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TransactionType;
+
+    fn key(byte: u8) -> PayloadKey {
+        PayloadKey::new([byte; 32])
+    }
+
+    #[test]
+    fn only_the_same_key_decrypts() {
+        let plaintext = br#"{"bio":"private"}"#.to_vec();
+        let mut tx = Transaction::new(TransactionType::UserEdited, plaintext.clone());
+        tx.encrypt_payload(&key(1)).unwrap();
+
+        assert_ne!(tx.payload, plaintext);
+        assert_eq!(tx.decrypt_payload(&key(1)).unwrap(), plaintext);
+        assert!(matches!(tx.decrypt_payload(&key(2)), Err(TransactionError::Decryption)));
+
+        let plain = Transaction::new(TransactionType::UserEdited, plaintext);
+        assert!(plain.decrypt_payload(&key(1)).is_err());
+    }
+
+    #[test]
+    fn hash_is_stable_over_the_ciphertext() {
+        let mut tx = Transaction::new(TransactionType::UserEdited, b"secret".to_vec());
+        let plaintext_hash = tx.hash().unwrap();
+        tx.encrypt_payload(&key(1)).unwrap();
+
+        let hash = tx.hash().unwrap();
+        assert_ne!(hash, plaintext_hash);
+        assert_eq!(tx.decrypt_payload(&key(1)).unwrap(), b"secret");
+        assert_eq!(tx.hash().unwrap(), hash);
+        assert_eq!(tx.clone().hash().unwrap(), hash);
+    }
+
+    #[test]
+    fn sealed_transactions_still_validate() {
+        let mut tx = Transaction::builder(TransactionType::UserEdited)
+            .payload(b"secret".to_vec())
+            .deterministic()
+            .build()
+            .unwrap();
+        let plaintext_id = tx.id;
+        tx.encrypt_payload(&key(1)).unwrap();
+        assert_ne!(tx.id, plaintext_id);
+        assert_eq!(tx.id, tx.content_id());
+        assert!(tx.validate().is_ok());
+
+        // Random ids are kept as they are.
+        let mut random = Transaction::new(TransactionType::UserEdited, b"secret".to_vec());
+        let id = random.id;
+        random.encrypt_payload(&key(1)).unwrap();
+        assert_eq!(random.id, id);
+
+        let mut signed = Transaction::new(TransactionType::UserEdited, b"secret".to_vec());
+        signed.sign(vec![1; crate::SIGNATURE_LEN]);
+        assert!(matches!(signed.encrypt_payload(&key(1)), Err(TransactionError::AlreadySigned)));
+        assert_eq!(signed.payload, b"secret");
+    }
+
+    #[test]
+    fn keys_parse_from_hex_and_stay_out_of_debug() {
+        let parsed: PayloadKey = "01".repeat(32).parse().unwrap();
+        assert_eq!(parsed, key(1));
+        assert!("01".repeat(31).parse::<PayloadKey>().is_err());
+        assert!("zz".repeat(32).parse::<PayloadKey>().is_err());
+        assert_eq!(format!("{parsed:?}"), "PayloadKey(..)");
+    }
+}
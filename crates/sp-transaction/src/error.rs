@@ -10,4 +10,16 @@ pub enum TransactionError {
 
     #[error("invalid transaction id")]
     InvalidId,
+
+    #[error("payload key must be 64 hex characters")]
+    InvalidKey,
+
+    #[error("payload encryption failed")]
+    Encryption,
+
+    #[error("a signed transaction's payload can't be encrypted; encrypt before signing")]
+    AlreadySigned,
+
+    #[error("payload could not be decrypted with this key")]
+    Decryption,
 }
@@ -0,0 +1,21 @@
+use sha2::{Digest, Sha256};
+
+/// The digest used for transaction hashes, Merkle nodes and block hashes.
+///
+/// It is a type parameter rather than a value so an `sp_merkle::MerkleTree`
+/// and the proofs it hands out are tied to one algorithm at compile time: a
+/// proof can only be verified with the function that built the tree.
+pub trait Hasher {
+    /// Hash `data` to a 32-byte digest.
+    fn digest(data: &[u8]) -> [u8; 32];
+}
+
+/// SHA-256, the network's default [`Hasher`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sha256Hasher;
+
+impl Hasher for Sha256Hasher {
+    fn digest(data: &[u8]) -> [u8; 32] {
+        Sha256::digest(data).into()
+    }
+}
@@ -1,7 +1,13 @@
+pub mod builder;
+pub mod encryption;
 pub mod error;
+pub mod hasher;
 pub mod transaction;
 pub mod transaction_type;
 
+pub use builder::TransactionBuilder;
+pub use encryption::PayloadKey;
 pub use error::TransactionError;
-pub use transaction::Transaction;
+pub use hasher::{Hasher, Sha256Hasher};
+pub use transaction::{Transaction, SIGNATURE_LEN};
 pub use transaction_type::TransactionType;
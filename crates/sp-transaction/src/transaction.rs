@@ -1,9 +1,12 @@
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use uuid::Uuid;
+use uuid::{Builder, Uuid, Version};
 
-use crate::{TransactionError, TransactionType};
+use crate::{Hasher, Sha256Hasher, TransactionBuilder, TransactionError, TransactionType};
+
+/// Length of an ed25519 signature, the kind nodes attach.
+pub const SIGNATURE_LEN: usize = 64;
 
 /// A single immutable record of an event on the Social Production network.
 ///
@@ -27,7 +30,12 @@ pub struct Transaction {
     /// Unix timestamp (seconds) when this transaction was created.
     pub timestamp: i64,
 
-    /// Cryptographic signature of `id || kind || payload || timestamp`.
+    /// How eagerly block producers should include this transaction; higher
+    /// goes first.  Defaults to `0`.
+    #[serde(default)]
+    pub priority: u32,
+
+    /// Cryptographic signature of [`Transaction::signing_bytes`].
     /// Empty until signed by the originating node.
     pub signature: Vec<u8>,
 }
@@ -40,16 +48,47 @@ impl Transaction {
             kind,
             payload,
             timestamp: Utc::now().timestamp(),
+            priority: 0,
             signature: Vec::new(),
         }
     }
 
+    /// Start building a transaction of the given kind.
+    pub fn builder(kind: TransactionType) -> TransactionBuilder {
+        TransactionBuilder::new(kind)
+    }
+
+    /// Canonical bytes of the transaction's content (`kind || payload ||
+    /// timestamp || priority`), excluding the id and signature.  This is what
+    /// gets signed and what [`Transaction::content_id`] is derived from, so a
+    /// relay can't raise a transaction's priority unnoticed.
+    pub fn signing_bytes(&self) -> Result<Vec<u8>, TransactionError> {
+        Ok(bincode::serialize(&(&self.kind, &self.payload, self.timestamp, self.priority))?)
+    }
+
+    /// A UUIDv5-style id derived from [`Transaction::signing_bytes`]: the first
+    /// 16 bytes of their SHA-256 digest with the version/variant bits set.
+    /// Identical content always yields the same id.
+    pub fn content_id(&self) -> Uuid {
+        // The content is a type tag, a byte vector and two integers, none of
+        // which bincode can fail to encode.
+        let content = self.signing_bytes().expect("transaction content always encodes");
+        let digest = Sha256::digest(content);
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&digest[..16]);
+        Builder::from_sha1_bytes(bytes).into_uuid()
+    }
+
     /// Compute the SHA-256 hash of the canonical byte representation of this
     /// transaction.  Used as the leaf value in the Merkle tree.
     pub fn hash(&self) -> Result<[u8; 32], TransactionError> {
+        self.hash_with::<Sha256Hasher>()
+    }
+
+    /// Like [`Transaction::hash`], but with the digest algorithm `H`.
+    pub fn hash_with<H: Hasher>(&self) -> Result<[u8; 32], TransactionError> {
         let bytes = bincode::serialize(self)?;
-        let digest = Sha256::digest(&bytes);
-        Ok(digest.into())
+        Ok(H::digest(&bytes))
     }
 
     /// Hex-encoded hash, useful for display and logging.
@@ -57,6 +96,12 @@ impl Transaction {
         Ok(hex::encode(self.hash()?))
     }
 
+    /// Length of this transaction's bincode encoding, as sent on the wire.
+    pub fn serialised_size(&self) -> usize {
+        // Every field has a known length, so measuring cannot fail.
+        bincode::serialized_size(self).expect("transaction has a fixed layout") as usize
+    }
+
     /// Attach a pre-computed signature (e.g. from an ed25519 keypair).
     pub fn sign(&mut self, signature: Vec<u8>) {
         self.signature = signature;
@@ -66,6 +111,23 @@ impl Transaction {
     pub fn is_signed(&self) -> bool {
         !self.signature.is_empty()
     }
+
+    /// Check what can be checked without the signer's key: the id is not nil
+    /// and, if derived from the content (see [`Transaction::content_id`]),
+    /// matches it; and the signature is either absent or [`SIGNATURE_LEN`]
+    /// bytes.  The payload may be encrypted, so it is not inspected.
+    pub fn validate(&self) -> Result<(), TransactionError> {
+        if self.id.is_nil() {
+            return Err(TransactionError::InvalidId);
+        }
+        if self.id.get_version() == Some(Version::Sha1) && self.id != self.content_id() {
+            return Err(TransactionError::InvalidId);
+        }
+        if self.is_signed() && self.signature.len() != SIGNATURE_LEN {
+            return Err(TransactionError::InvalidSignature);
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -79,6 +141,51 @@ mod tests {
         assert_ne!(a.id, b.id);
     }
 
+    #[test]
+    fn validate_checks_ids_and_signature_lengths() {
+        let mut tx = Transaction::new(TransactionType::VoteCast, b"{}".to_vec());
+        assert!(tx.validate().is_ok());
+        tx.sign(vec![1; SIGNATURE_LEN]);
+        assert!(tx.validate().is_ok());
+
+        tx.sign(vec![1; 3]);
+        assert!(matches!(tx.validate(), Err(TransactionError::InvalidSignature)));
+
+        tx.signature.clear();
+        tx.id = Uuid::nil();
+        assert!(matches!(tx.validate(), Err(TransactionError::InvalidId)));
+
+        let mut derived = Transaction::builder(TransactionType::VoteCast)
+            .payload(b"{}".to_vec())
+            .deterministic()
+            .build()
+            .unwrap();
+        assert!(derived.validate().is_ok());
+        derived.payload = b"{\"forged\":true}".to_vec();
+        assert!(matches!(derived.validate(), Err(TransactionError::InvalidId)));
+    }
+
+    #[test]
+    fn raising_the_priority_changes_what_is_signed() {
+        let tx = Transaction::builder(TransactionType::VoteCast)
+            .payload(b"{}".to_vec())
+            .deterministic()
+            .build()
+            .unwrap();
+        let mut bumped = tx.clone();
+        bumped.priority = u32::MAX;
+        assert_ne!(bumped.signing_bytes().unwrap(), tx.signing_bytes().unwrap());
+        assert!(matches!(bumped.validate(), Err(TransactionError::InvalidId)));
+    }
+
+    #[test]
+    fn serialised_size_is_the_encoded_length() {
+        let mut tx = Transaction::new(TransactionType::PostCreated, vec![7; 100]);
+        tx.sign(vec![1; 64]);
+        assert_eq!(tx.serialised_size(), bincode::serialize(&tx).unwrap().len());
+        assert!(tx.serialised_size() > 100 + 64);
+    }
+
     #[test]
     fn hash_is_deterministic_for_same_data() {
         let tx = Transaction {
@@ -86,6 +193,7 @@ mod tests {
             kind: TransactionType::ProjectPosted,
             payload: b"hello".to_vec(),
             timestamp: 0,
+            priority: 0,
             signature: vec![],
         };
         assert_eq!(tx.hash().unwrap(), tx.hash().unwrap());
@@ -98,6 +206,7 @@ mod tests {
             kind: TransactionType::PostCreated,
             payload: p.to_vec(),
             timestamp: 0,
+            priority: 0,
             signature: vec![],
         };
         assert_ne!(make(b"a").hash().unwrap(), make(b"b").hash().unwrap());
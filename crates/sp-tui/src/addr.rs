@@ -0,0 +1,139 @@
+//! Addresses typed by the user for `/connect` and `/disconnect`.
+//!
+//! Accepts `ip:port`, `[ipv6]:port`, `host:port` (dialled as
+//! `/dns4/<host>/tcp/<port>`) and full multiaddrs, and checks before dialing
+//! that the result names a host and a TCP port the node can actually reach.
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use libp2p::{multiaddr::Protocol, Multiaddr};
+use thiserror::Error;
+
+/// Why a typed address can't be dialled.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AddrError {
+    #[error("{0} is not a multiaddr")]
+    Malformed(String),
+
+    #[error("{0} names no host; start with /ip4, /ip6, /dns, /dns4 or /dns6")]
+    NoHost(String),
+
+    #[error("{0} has no transport; add /tcp/<port>")]
+    NoTransport(String),
+
+    #[error("{0} has no port; use <host>:<port>")]
+    NoPort(String),
+
+    #[error("{0} is not a usable port")]
+    BadPort(String),
+
+    #[error("{0} is not a valid host name")]
+    BadHost(String),
+}
+
+/// Normalise `input` to a dialable multiaddr.
+pub fn parse_dial_addr(input: &str) -> Result<Multiaddr, AddrError> {
+    let input = input.trim();
+    let addr = if input.starts_with('/') {
+        input.parse::<Multiaddr>().map_err(|_| AddrError::Malformed(input.to_string()))?
+    } else {
+        from_host_port(input)?
+    };
+    validate(&addr)?;
+    Ok(addr)
+}
+
+/// `ip:port`, `[ipv6]:port` or `host:port` as a multiaddr.
+fn from_host_port(input: &str) -> Result<Multiaddr, AddrError> {
+    let Some((host, port)) = input.rsplit_once(':') else {
+        return Err(AddrError::NoPort(input.to_string()));
+    };
+    let port = match port.parse::<u16>() {
+        Ok(port) if port != 0 => port,
+        _ => return Err(AddrError::BadPort(port.to_string())),
+    };
+    let host = if let Ok(ip) = host.parse::<Ipv4Addr>() {
+        Protocol::Ip4(ip)
+    } else if let Some(ip) = host.strip_prefix('[').and_then(|h| h.strip_suffix(']')) {
+        let ip = ip.parse::<Ipv6Addr>().map_err(|_| AddrError::BadHost(host.to_string()))?;
+        Protocol::Ip6(ip)
+    } else if is_host_name(host) {
+        Protocol::Dns4(host.into())
+    } else {
+        return Err(AddrError::BadHost(host.to_string()));
+    };
+    Ok(Multiaddr::empty().with(host).with(Protocol::Tcp(port)))
+}
+
+/// Letters, digits, `-` and `.`, in non-empty labels that don't start or end
+/// with `-`.  A dotted quad is not a name: it failed to parse as an address.
+fn is_host_name(host: &str) -> bool {
+    !host.is_empty()
+        && !host.split('.').all(|label| label.bytes().all(|b| b.is_ascii_digit()))
+        && host.split('.').all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+        })
+}
+
+/// A host, then TCP with a non-zero port, then at most a `/p2p/<id>`.
+fn validate(addr: &Multiaddr) -> Result<(), AddrError> {
+    let mut iter = addr.iter();
+    match iter.next() {
+        Some(Protocol::Ip4(_) | Protocol::Ip6(_))
+        | Some(Protocol::Dns(_) | Protocol::Dns4(_) | Protocol::Dns6(_)) => {}
+        _ => return Err(AddrError::NoHost(addr.to_string())),
+    }
+    match iter.next() {
+        Some(Protocol::Tcp(0)) => return Err(AddrError::BadPort("0".into())),
+        Some(Protocol::Tcp(_)) => {}
+        _ => return Err(AddrError::NoTransport(addr.to_string())),
+    }
+    match iter.next() {
+        None | Some(Protocol::P2p(_)) if iter.next().is_none() => Ok(()),
+        _ => Err(AddrError::Malformed(addr.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ok(input: &str) -> String {
+        parse_dial_addr(input).unwrap().to_string()
+    }
+
+    #[test]
+    fn well_formed_addresses_are_normalised() {
+        assert_eq!(ok("1.2.3.4:51025"), "/ip4/1.2.3.4/tcp/51025");
+        assert_eq!(ok(" [::1]:51025 "), "/ip6/::1/tcp/51025");
+        assert_eq!(ok("node.example.org:4001"), "/dns4/node.example.org/tcp/4001");
+        assert_eq!(ok("localhost:4001"), "/dns4/localhost/tcp/4001");
+        assert_eq!(ok("/ip4/1.2.3.4/tcp/51025"), "/ip4/1.2.3.4/tcp/51025");
+        assert_eq!(ok("/dns4/node.example.org/tcp/4001"), "/dns4/node.example.org/tcp/4001");
+        let peer = libp2p::PeerId::random();
+        let with_peer = format!("/ip4/1.2.3.4/tcp/51025/p2p/{peer}");
+        assert_eq!(ok(&with_peer), with_peer);
+    }
+
+    #[test]
+    fn malformed_addresses_say_what_is_wrong() {
+        let err = |input: &str| parse_dial_addr(input).unwrap_err();
+        assert_eq!(err("/ip4/1.2.3.4"), AddrError::NoTransport("/ip4/1.2.3.4".into()));
+        assert!(matches!(err("/ip4/1.2.3.4/udp/4001"), AddrError::NoTransport(_)));
+        assert_eq!(err("/tcp/4001"), AddrError::NoHost("/tcp/4001".into()));
+        assert_eq!(err("/ip4/1.2.3.4/tcp/0"), AddrError::BadPort("0".into()));
+        assert!(matches!(err("/ip4/1.2.3/tcp/4001"), AddrError::Malformed(_)));
+        assert!(matches!(err("/ip4/1.2.3.4/tcp/1/tcp/2"), AddrError::Malformed(_)));
+        assert_eq!(err("1.2.3.4"), AddrError::NoPort("1.2.3.4".into()));
+        assert_eq!(err("1.2.3.4:"), AddrError::BadPort("".into()));
+        assert_eq!(err("1.2.3.4:70000"), AddrError::BadPort("70000".into()));
+        assert_eq!(err("1.2.3.4:0"), AddrError::BadPort("0".into()));
+        assert_eq!(err("1.2.3.400:4001"), AddrError::BadHost("1.2.3.400".into()));
+        assert_eq!(err("[::g]:4001"), AddrError::BadHost("[::g]".into()));
+        assert_eq!(err("bad_host:4001"), AddrError::BadHost("bad_host".into()));
+        assert_eq!(err(":4001"), AddrError::BadHost("".into()));
+    }
+}
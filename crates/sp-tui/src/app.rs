@@ -1,4 +1,12 @@
-use std::collections::VecDeque;
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use sp_blockchain::Block;
+use sp_node::{DiscoverySource, NodeConfig, Throughput};
+
+use crate::session::Session;
 
 /// Maximum number of traffic entries kept in memory.
 const MAX_TRAFFIC: usize = 500;
@@ -34,12 +42,58 @@ pub enum ContentView {
     Discovered,
     /// Currently connected peers.
     Connected,
+    /// Details for one peer.  `stored` records whether any of its addresses
+    /// was in the peers store when the view was opened.
+    Peer { peer_id: String, stored: bool },
+    /// The block in [`App::shown_block`].
+    Block,
+}
+
+/// How a traffic entry should be highlighted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TrafficLevel {
+    /// Routine activity (default).
+    #[default]
+    Info,
+    /// Something went well: a peer connected, a block was finalised.
+    Good,
+    /// Worth noticing but not a failure: a peer left, the node stopped.
+    Warn,
+    Error,
+    /// Block-related activity.
+    Block,
+    /// Transaction-related activity.
+    Tx,
+}
+
+impl TrafficLevel {
+    /// Order in which the traffic filter cycles through levels.
+    const FILTER_CYCLE: [TrafficLevel; 6] = [
+        TrafficLevel::Error,
+        TrafficLevel::Warn,
+        TrafficLevel::Block,
+        TrafficLevel::Tx,
+        TrafficLevel::Good,
+        TrafficLevel::Info,
+    ];
+
+    pub fn label(&self) -> &str {
+        match self {
+            TrafficLevel::Info => "info",
+            TrafficLevel::Good => "good",
+            TrafficLevel::Warn => "warnings",
+            TrafficLevel::Error => "errors",
+            TrafficLevel::Block => "blocks",
+            TrafficLevel::Tx => "transactions",
+        }
+    }
 }
 
 /// A timestamped traffic event.
 #[derive(Debug, Clone)]
 pub struct TrafficEntry {
     pub timestamp: String,
+    pub level: TrafficLevel,
     pub message: String,
 }
 
@@ -48,14 +102,53 @@ pub struct TrafficEntry {
 pub struct NodeStatus {
     pub peer_id: String,
     pub listen_addr: String,
+    /// Every address the node listens on, as of the last status query.
+    pub listen_addrs: Vec<String>,
+    pub network_id: String,
+    /// Hex hash of the genesis block; empty until the node has been asked.
+    pub genesis_hash: String,
     pub peers_connected: usize,
     pub peers_discovered: usize,
     pub chain_length: usize,
+    /// Hex hash of the tip; empty until the node has been asked for it.
+    pub tip_hash: String,
+    pub last_finalised: Option<u64>,
     pub pending_txs: usize,
     pub mode: String,
     pub sync_strategy: String,
     pub discovery_mode: String,
+    /// Ports discovered addresses are filtered to, e.g. `"4000-4100"`.
+    pub port_range: String,
     pub port: u16,
+    /// Connected peers subscribed to each gossip topic, by topic name.
+    pub topic_peers: BTreeMap<String, usize>,
+    /// Transactions finalised by the node, by type, as of the last status
+    /// query.
+    pub finalised_by_type: BTreeMap<String, u64>,
+    /// Transactions in blocks that finalised while the TUI was watching.
+    pub finalised_txs: Throughput,
+    /// When the node last sent a heartbeat, and how often it is meant to.
+    pub last_heartbeat: Option<Instant>,
+    pub heartbeat_interval: Duration,
+}
+
+impl NodeStatus {
+    /// The node's identity as `/whoami` prints it: peer id, every listen
+    /// address, port, network and genesis hash.
+    pub fn whoami_lines(&self) -> Vec<String> {
+        let or_dash = |s: &str| if s.is_empty() { "-".to_string() } else { s.to_string() };
+        let mut lines = vec![format!("peer id       {}", or_dash(&self.peer_id))];
+        if self.listen_addrs.is_empty() {
+            lines.push("listening on  -".to_string());
+        }
+        for addr in &self.listen_addrs {
+            lines.push(format!("listening on  {addr}"));
+        }
+        lines.push(format!("port          {}", self.port));
+        lines.push(format!("network       {}", or_dash(&self.network_id)));
+        lines.push(format!("genesis hash  {}", or_dash(&self.genesis_hash)));
+        lines
+    }
 }
 
 /// Maximum entries kept in the command history shown in the input panel.
@@ -66,7 +159,13 @@ pub struct App {
     pub node_state: NodeState,
     pub status: NodeStatus,
     pub traffic: VecDeque<TrafficEntry>,
+    /// Selected row within the *visible* (filtered) traffic entries.
     pub traffic_scroll: usize,
+    /// When `true` (the default) new traffic scrolls the view to the newest
+    /// entry; scrolling up turns this off so history stays put.
+    pub traffic_follow: bool,
+    /// When set, only traffic entries of this level are shown.
+    pub traffic_level_filter: Option<TrafficLevel>,
     pub view: ContentView,
     /// Text the user is currently typing.
     pub input: String,
@@ -84,6 +183,18 @@ pub struct App {
     pub discovered_peers: Vec<(String, Vec<String>)>,
     /// Currently connected peers: (peer_id_str, addrs).
     pub connected_peers: Vec<(String, Vec<String>)>,
+    /// When each connected peer's current connection was established.
+    pub connected_since: HashMap<String, Instant>,
+    /// Identify agent string each peer reported, by peer id string.
+    pub peer_agents: HashMap<String, String>,
+    /// Which mechanisms have reported each discovered peer, by peer id string.
+    pub discovery_sources: HashMap<String, Vec<DiscoverySource>>,
+    /// The block found by the last `/block` lookup.
+    pub shown_block: Option<Block>,
+    /// The settings the node runs with, saved for the next launch.
+    pub session: Session,
+    /// Set by `/whoami` until the status it asked for arrives.
+    pub whoami_pending: bool,
     pub should_quit: bool,
 }
 
@@ -100,6 +211,8 @@ impl App {
             },
             traffic: VecDeque::new(),
             traffic_scroll: 0,
+            traffic_follow: true,
+            traffic_level_filter: None,
             view: ContentView::Traffic,
             input: String::new(),
             command_history: VecDeque::new(),
@@ -108,6 +221,12 @@ impl App {
             command_output: None,
             discovered_peers: Vec::new(),
             connected_peers: Vec::new(),
+            connected_since: HashMap::new(),
+            peer_agents: HashMap::new(),
+            discovery_sources: HashMap::new(),
+            shown_block: None,
+            session: Session::from_config(&NodeConfig::default()),
+            whoami_pending: false,
             should_quit: false,
         }
     }
@@ -170,30 +289,98 @@ impl App {
     }
 
     /// Push a timestamped entry into the traffic log.
-    pub fn push_traffic(&mut self, message: impl Into<String>) {
+    pub fn push_traffic(&mut self, level: TrafficLevel, message: impl Into<String>) {
         use chrono::Local;
         let entry = TrafficEntry {
             timestamp: Local::now().format("%H:%M:%S").to_string(),
+            level,
             message: message.into(),
         };
         self.traffic.push_back(entry);
         if self.traffic.len() > MAX_TRAFFIC {
-            self.traffic.pop_front();
+            let evicted = self.traffic.pop_front();
+            // Keep a locked view on the same entry as the list shifts up.
+            let filter = self.traffic_level_filter;
+            let evicted_visible =
+                evicted.is_some_and(|e| filter.is_none() || filter == Some(e.level));
+            if !self.traffic_follow && evicted_visible {
+                self.traffic_scroll = self.traffic_scroll.saturating_sub(1);
+            }
         }
-        self.traffic_scroll = self.traffic.len().saturating_sub(1);
+        if self.traffic_follow {
+            self.traffic_scroll = self.visible_traffic().count().saturating_sub(1);
+        }
+    }
+
+    /// Traffic entries that pass the current level filter, oldest first.
+    pub fn visible_traffic(&self) -> impl Iterator<Item = &TrafficEntry> {
+        let filter = self.traffic_level_filter;
+        self.traffic
+            .iter()
+            .filter(move |entry| filter.is_none() || filter == Some(entry.level))
+    }
+
+    /// Advance the traffic filter: all → errors → … → info → all.  Jumps the
+    /// scroll position to the newest visible entry.
+    pub fn cycle_traffic_filter(&mut self) {
+        let cycle = &TrafficLevel::FILTER_CYCLE;
+        self.traffic_level_filter = match self.traffic_level_filter {
+            None => Some(cycle[0]),
+            Some(level) => {
+                let pos = cycle.iter().position(|l| *l == level).unwrap_or(cycle.len());
+                cycle.get(pos + 1).copied()
+            }
+        };
+        self.traffic_scroll = self.visible_traffic().count().saturating_sub(1);
     }
 
+    /// Scroll towards older entries.  This locks the view so new traffic no
+    /// longer moves it.
     pub fn scroll_traffic_up(&mut self) {
+        self.traffic_follow = false;
         self.traffic_scroll = self.traffic_scroll.saturating_sub(1);
     }
 
+    /// Jump to the newest entry and follow new traffic again.
+    pub fn follow_traffic(&mut self) {
+        self.traffic_follow = true;
+        self.traffic_scroll = self.visible_traffic().count().saturating_sub(1);
+    }
+
     pub fn scroll_traffic_down(&mut self) {
-        let max = self.traffic.len().saturating_sub(1);
+        let max = self.visible_traffic().count().saturating_sub(1);
         if self.traffic_scroll < max {
             self.traffic_scroll += 1;
         }
     }
 
+    /// Full ids of known peers (connected first, then discovered) that start
+    /// with `prefix`.  A trailing `…` from a truncated id in the UI is ignored.
+    pub fn find_peers_by_prefix(&self, prefix: &str) -> Vec<String> {
+        peers_with_prefix(self.connected_peers.iter().chain(&self.discovered_peers), prefix)
+    }
+
+    /// Like [`App::find_peers_by_prefix`], over connected peers only.
+    pub fn find_connected_peers_by_prefix(&self, prefix: &str) -> Vec<String> {
+        peers_with_prefix(self.connected_peers.iter(), prefix)
+    }
+
+    /// Every address known for `peer_id` across the connected and discovered
+    /// lists, without duplicates.
+    pub fn peer_addrs(&self, peer_id: &str) -> Vec<String> {
+        let mut addrs: Vec<String> = Vec::new();
+        for (pid, list) in self.connected_peers.iter().chain(&self.discovered_peers) {
+            if pid == peer_id {
+                for a in list {
+                    if !addrs.contains(a) {
+                        addrs.push(a.clone());
+                    }
+                }
+            }
+        }
+        addrs
+    }
+
     pub fn set_output(&mut self, msg: impl Into<String>) {
         self.command_output = Some(msg.into());
     }
@@ -202,3 +389,97 @@ impl App {
         self.command_output = None;
     }
 }
+
+/// Ids in `peers` that start with `prefix`, ignoring a trailing `…`.
+fn peers_with_prefix<'a>(
+    peers: impl Iterator<Item = &'a (String, Vec<String>)>,
+    prefix: &str,
+) -> Vec<String> {
+    let prefix = prefix.trim_end_matches('…');
+    let mut matches: Vec<String> = Vec::new();
+    for (pid, _) in peers {
+        if pid.starts_with(prefix) && !matches.contains(pid) {
+            matches.push(pid.clone());
+        }
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn traffic_filter_limits_visible_entries_and_scroll() {
+        let mut app = App::new();
+        app.push_traffic(TrafficLevel::Info, "one");
+        app.push_traffic(TrafficLevel::Error, "two");
+        app.push_traffic(TrafficLevel::Info, "three");
+
+        app.cycle_traffic_filter();
+        assert_eq!(app.traffic_level_filter, Some(TrafficLevel::Error));
+        assert_eq!(app.visible_traffic().count(), 1);
+        assert_eq!(app.traffic_scroll, 0);
+        app.scroll_traffic_down();
+        assert_eq!(app.traffic_scroll, 0);
+
+        for _ in 0..TrafficLevel::FILTER_CYCLE.len() {
+            app.cycle_traffic_filter();
+        }
+        assert_eq!(app.traffic_level_filter, None);
+        assert_eq!(app.visible_traffic().count(), 3);
+        assert_eq!(app.traffic_scroll, 2);
+    }
+
+    #[test]
+    fn connected_prefix_lookup_ignores_discovered_peers() {
+        let mut app = App::new();
+        app.connected_peers.push(("12D3KooWAlpha".into(), Vec::new()));
+        app.connected_peers.push(("12D3KooWAlps".into(), Vec::new()));
+        app.discovered_peers.push(("12D3KooWBeta".into(), Vec::new()));
+
+        assert_eq!(app.find_connected_peers_by_prefix("12D3KooWAlph…"), vec!["12D3KooWAlpha"]);
+        assert_eq!(app.find_connected_peers_by_prefix("12D3KooWAl").len(), 2);
+        assert!(app.find_connected_peers_by_prefix("12D3KooWB").is_empty());
+        assert_eq!(app.find_peers_by_prefix("12D3KooWB"), vec!["12D3KooWBeta"]);
+    }
+
+    #[test]
+    fn whoami_lists_every_listen_address() {
+        let status = NodeStatus {
+            peer_id: "12D3KooWAlpha".into(),
+            listen_addrs: vec!["/ip4/127.0.0.1/tcp/51025".into(), "/ip4/10.0.0.2/tcp/51025".into()],
+            port: 51025,
+            network_id: "social-production".into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            status.whoami_lines(),
+            vec![
+                "peer id       12D3KooWAlpha",
+                "listening on  /ip4/127.0.0.1/tcp/51025",
+                "listening on  /ip4/10.0.0.2/tcp/51025",
+                "port          51025",
+                "network       social-production",
+                "genesis hash  -",
+            ]
+        );
+    }
+
+    #[test]
+    fn scrolling_up_locks_the_traffic_view() {
+        let mut app = App::new();
+        for i in 0..5 {
+            app.push_traffic(TrafficLevel::Info, format!("entry {i}"));
+        }
+        app.scroll_traffic_up();
+        assert!(!app.traffic_follow);
+        app.push_traffic(TrafficLevel::Info, "entry 5");
+        assert_eq!(app.traffic_scroll, 3);
+
+        app.follow_traffic();
+        assert_eq!(app.traffic_scroll, 5);
+        app.push_traffic(TrafficLevel::Info, "entry 6");
+        assert_eq!(app.traffic_scroll, 6);
+    }
+}
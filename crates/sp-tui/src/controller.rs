@@ -1,25 +1,95 @@
+use std::time::Duration;
+
 use libp2p::{Multiaddr, PeerId};
-use sp_node::{Node, NodeConfig, NodeEvent};
-use tokio::{sync::mpsc, task::JoinHandle};
+use sp_blockchain::{Block, Blockchain};
+use sp_node::{Node, NodeConfig, NodeError, NodeEvent, NodeStatusSnapshot};
+use thiserror::Error;
+use tokio::{
+    sync::mpsc,
+    task::{JoinError, JoinHandle},
+    time::Instant,
+};
 use tracing::warn;
 
 /// Commands sent from the TUI to the controller task.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ControlCommand {
     Start,
     Stop,
     Restart,
     Connect(Multiaddr),
+    /// Dial a peer by id, falling back to a DHT lookup if no address is known.
+    ConnectPeer(PeerId),
     Disconnect(PeerId),
     /// Trigger active discovery; `None` means use the node's own port.
     Discover(Option<(u16, u16)>),
+    /// Filter discovered addresses to this port range, or to none, from now
+    /// on and across restarts.
+    SetPortRange(Option<(u16, u16)>),
+    /// Look up a block by hex hash or hash prefix.
+    ShowBlock(String),
+    /// Ask the running node for a [`NodeStatusSnapshot`].
+    QueryStatus,
 }
 
 /// Messages sent from the controller task back to the TUI.
 pub enum ControlEvent {
-    NodeStarted { peer_id: String, listen_addr: String },
+    /// `port` is the TCP port actually bound, if the node got that far.
+    NodeStarted { peer_id: String, listen_addr: String, port: Option<u16> },
     NodeStopped,
-    NodeEvent(NodeEvent),
-    Error(String),
+    /// The node stopped without being asked to and will be started again
+    /// after `delay`; this is automatic restart number `attempt` of `max`.
+    Restarting { attempt: u32, max: u32, delay: Duration },
+    NodeEvent(Box<NodeEvent>),
+    /// Answer to [`ControlCommand::ShowBlock`].
+    Block(BlockLookup),
+    /// Answer to [`ControlCommand::QueryStatus`].
+    Status(Box<NodeStatusSnapshot>),
+    Error(ControlError),
+}
+
+/// Something the controller or the node task it runs failed to do.
+#[derive(Debug, Error)]
+pub enum ControlError {
+    /// The node could not be built; restarting won't help until the
+    /// configuration or environment changes.
+    #[error("node failed to start: {0}")]
+    StartupFailed(NodeError),
+
+    /// A connection the user asked for could not be started.
+    #[error("dial failed: {0}")]
+    DialFailed(NodeError),
+
+    #[error("disconnect failed: {0}")]
+    DisconnectFailed(NodeError),
+
+    /// Node state could not be saved while stopping.
+    #[error("shutdown failed: {0}")]
+    ShutdownFailed(NodeError),
+
+    /// The node task ended without being asked to.  The controller restarts
+    /// it on its own while its [`RestartPolicy`] allows.
+    #[error("node crashed: {0}")]
+    NodeCrashed(String),
+}
+
+/// Outcome of looking a block up by hash prefix.
+pub enum BlockLookup {
+    Found(Box<Block>),
+    NotFound,
+    /// The prefix matched this many blocks.
+    Ambiguous(usize),
+}
+
+impl BlockLookup {
+    /// Resolve a hex hash or hash prefix against `chain`.
+    pub fn resolve(chain: &Blockchain, prefix: &str) -> Self {
+        match chain.blocks_by_hash_prefix(prefix).as_slice() {
+            [] => Self::NotFound,
+            [block] => Self::Found(Box::new((*block).clone())),
+            blocks => Self::Ambiguous(blocks.len()),
+        }
+    }
 }
 
 /// Manages the lifecycle of a [`Node`] in a background Tokio task.
@@ -34,50 +104,152 @@ impl NodeController {
         let (cmd_tx, cmd_rx) = mpsc::unbounded_channel::<ControlCommand>();
         let (event_tx, event_rx) = mpsc::unbounded_channel::<ControlEvent>();
 
-        tokio::spawn(run_controller(config, cmd_rx, event_tx));
+        tokio::spawn(run_controller(config, cmd_rx, event_tx, spawn_node));
 
         Self { cmd_tx, event_rx }
     }
 }
 
-async fn run_controller(
-    config: NodeConfig,
+/// Most automatic restarts after the node dies unexpectedly; after that it
+/// stays stopped until the user starts it again.
+const MAX_RESTARTS: u32 = 3;
+
+/// Delay before the first automatic restart, doubled for each one after.
+const RESTART_BACKOFF: Duration = Duration::from_secs(1);
+
+/// How long a node task gets to shut down after being told to stop.
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Decides whether a node task that ended should be started again.
+///
+/// Only unexpected exits count: a user stop or a node that could not be
+/// built at all is left alone.  The attempt count is only reset by the user
+/// starting the node, so a node that keeps dying is restarted at most `max`
+/// times.
+#[derive(Debug)]
+struct RestartPolicy {
+    max: u32,
+    backoff: Duration,
+    attempts: u32,
+}
+
+impl RestartPolicy {
+    fn new(max: u32, backoff: Duration) -> Self {
+        Self { max, backoff, attempts: 0 }
+    }
+
+    /// How long to wait before restarting after `exit`, or `None` to leave
+    /// the node stopped.
+    fn after(&mut self, exit: &Result<NodeExit, JoinError>) -> Option<Duration> {
+        match exit {
+            Ok(NodeExit::Stopped | NodeExit::StartupFailed) => return None,
+            Err(e) if e.is_cancelled() => return None,
+            Ok(NodeExit::Ended) | Err(_) => {}
+        }
+        if self.attempts >= self.max {
+            return None;
+        }
+        let delay = self.backoff * 2u32.saturating_pow(self.attempts);
+        self.attempts += 1;
+        Some(delay)
+    }
+
+    fn reset(&mut self) {
+        self.attempts = 0;
+    }
+}
+
+/// Serve `cmd_rx` until it closes, starting node tasks with `spawn`.
+async fn run_controller<S>(
+    mut config: NodeConfig,
     mut cmd_rx: mpsc::UnboundedReceiver<ControlCommand>,
     event_tx: mpsc::UnboundedSender<ControlEvent>,
-) {
+    spawn: S,
+) where
+    S: Fn(
+        &NodeConfig,
+        &mpsc::UnboundedSender<ControlEvent>,
+    ) -> (mpsc::UnboundedSender<NodeCommand>, JoinHandle<NodeExit>),
+{
     // Channel used to send commands to a running node task.
     let mut node_cmd_tx: Option<mpsc::UnboundedSender<NodeCommand>> = None;
-    let mut node_handle: Option<JoinHandle<()>> = None;
+    let mut node_handle: Option<JoinHandle<NodeExit>> = None;
+    let mut restarts = RestartPolicy::new(MAX_RESTARTS, RESTART_BACKOFF);
+    // When the pending automatic restart is due, if one is.
+    let mut restart_at: Option<Instant> = None;
 
-    while let Some(cmd) = cmd_rx.recv().await {
-        match cmd {
-            ControlCommand::Start | ControlCommand::Restart => {
-                // Stop any running node first.
-                if let Some(tx) = node_cmd_tx.take() {
-                    let _ = tx.send(NodeCommand::Stop);
+    loop {
+        let node_exited = async {
+            match node_handle.as_mut() {
+                Some(handle) => handle.await,
+                None => std::future::pending().await,
+            }
+        };
+        let restart_due = async {
+            match restart_at {
+                Some(at) => tokio::time::sleep_until(at).await,
+                None => std::future::pending().await,
+            }
+        };
+        let cmd = tokio::select! {
+            cmd = cmd_rx.recv() => match cmd {
+                Some(cmd) => cmd,
+                None => break,
+            },
+            result = node_exited => {
+                node_handle = None;
+                node_cmd_tx = None;
+                let restart = restarts.after(&result);
+                // An exit from inside the task already reported NodeStopped.
+                match result {
+                    Ok(NodeExit::Ended) => {
+                        let _ = event_tx.send(ControlEvent::Error(ControlError::NodeCrashed(
+                            "node run loop ended".to_string(),
+                        )));
+                    }
+                    Err(e) if !e.is_cancelled() => {
+                        let _ = event_tx.send(ControlEvent::Error(ControlError::NodeCrashed(
+                            panic_message(e),
+                        )));
+                        let _ = event_tx.send(ControlEvent::NodeStopped);
+                    }
+                    _ => {}
                 }
-                if let Some(handle) = node_handle.take() {
-                    handle.abort();
-                    let _ = event_tx.send(ControlEvent::NodeStopped);
+                if let Some(delay) = restart {
+                    restart_at = Some(Instant::now() + delay);
+                    let _ = event_tx.send(ControlEvent::Restarting {
+                        attempt: restarts.attempts,
+                        max: restarts.max,
+                        delay,
+                    });
                 }
+                continue;
+            }
+            () = restart_due => {
+                restart_at = None;
+                let (tx, handle) = spawn(&config, &event_tx);
+                node_cmd_tx = Some(tx);
+                node_handle = Some(handle);
+                continue;
+            }
+        };
 
-                let ev_tx = event_tx.clone();
-                let cfg = config.clone();
-
-                let (n_cmd_tx, n_cmd_rx) = mpsc::unbounded_channel::<NodeCommand>();
-                node_cmd_tx = Some(n_cmd_tx);
+        match cmd {
+            ControlCommand::Start | ControlCommand::Restart => {
+                // Stop any running node first.
+                stop_node(node_cmd_tx.take(), node_handle.take(), &event_tx).await;
+                restarts.reset();
+                restart_at = None;
 
-                node_handle = Some(tokio::spawn(run_node(cfg, n_cmd_rx, ev_tx)));
+                let (tx, handle) = spawn(&config, &event_tx);
+                node_cmd_tx = Some(tx);
+                node_handle = Some(handle);
             }
 
             ControlCommand::Stop => {
-                if let Some(tx) = node_cmd_tx.take() {
-                    let _ = tx.send(NodeCommand::Stop);
-                }
-                if let Some(handle) = node_handle.take() {
-                    handle.abort();
-                    let _ = event_tx.send(ControlEvent::NodeStopped);
-                }
+                stop_node(node_cmd_tx.take(), node_handle.take(), &event_tx).await;
+                // A stop also cancels a restart that was still waiting.
+                restart_at = None;
             }
 
             ControlCommand::Connect(addr) => {
@@ -86,6 +258,12 @@ async fn run_controller(
                 }
             }
 
+            ControlCommand::ConnectPeer(peer_id) => {
+                if let Some(tx) = &node_cmd_tx {
+                    let _ = tx.send(NodeCommand::DialPeer(peer_id));
+                }
+            }
+
             ControlCommand::Disconnect(peer_id) => {
                 if let Some(tx) = &node_cmd_tx {
                     let _ = tx.send(NodeCommand::Disconnect(peer_id));
@@ -97,71 +275,342 @@ async fn run_controller(
                     let _ = tx.send(NodeCommand::Discover(range));
                 }
             }
+
+            ControlCommand::SetPortRange(range) => {
+                config.discovery_port_range = range.map(|(start, end)| start..=end);
+                if let Some(tx) = &node_cmd_tx {
+                    let _ = tx.send(NodeCommand::SetPortRange(range));
+                }
+            }
+
+            ControlCommand::ShowBlock(prefix) => {
+                if let Some(tx) = &node_cmd_tx {
+                    let _ = tx.send(NodeCommand::ShowBlock(prefix));
+                }
+            }
+
+            ControlCommand::QueryStatus => {
+                if let Some(tx) = &node_cmd_tx {
+                    let _ = tx.send(NodeCommand::QueryStatus);
+                }
+            }
         }
     }
 }
 
+/// Start a node task, returning the channel that commands it.
+fn spawn_node(
+    config: &NodeConfig,
+    event_tx: &mpsc::UnboundedSender<ControlEvent>,
+) -> (mpsc::UnboundedSender<NodeCommand>, JoinHandle<NodeExit>) {
+    let (cmd_tx, cmd_rx) = mpsc::unbounded_channel::<NodeCommand>();
+    let handle = tokio::spawn(run_node(config.clone(), cmd_rx, event_tx.clone()));
+    (cmd_tx, handle)
+}
+
+/// Ask a node task to stop and wait for it to shut down cleanly, which it
+/// reports with [`ControlEvent::NodeStopped`].  A task that takes longer
+/// than [`STOP_TIMEOUT`] is aborted instead.
+async fn stop_node(
+    cmd_tx: Option<mpsc::UnboundedSender<NodeCommand>>,
+    handle: Option<JoinHandle<NodeExit>>,
+    event_tx: &mpsc::UnboundedSender<ControlEvent>,
+) {
+    if let Some(tx) = cmd_tx {
+        let _ = tx.send(NodeCommand::Stop);
+    }
+    let Some(mut handle) = handle else { return };
+    match tokio::time::timeout(STOP_TIMEOUT, &mut handle).await {
+        Ok(Ok(_)) => {}
+        // It died before it got the message; nobody has said so yet.
+        Ok(Err(_)) => {
+            let _ = event_tx.send(ControlEvent::NodeStopped);
+        }
+        Err(_) => {
+            warn!("Node task did not stop within {STOP_TIMEOUT:?}, aborting it");
+            handle.abort();
+            let _ = event_tx.send(ControlEvent::NodeStopped);
+        }
+    }
+}
+
+/// What a node task that died took with it.
+fn panic_message(e: JoinError) -> String {
+    match e.try_into_panic() {
+        Ok(panic) => panic
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "node task panicked".to_string()),
+        Err(e) => e.to_string(),
+    }
+}
+
+/// Why a node task ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NodeExit {
+    /// Asked to stop, or nobody is listening for its events any more.
+    Stopped,
+    /// [`Node::new`] failed; see [`ControlError::StartupFailed`].
+    StartupFailed,
+    /// The node's run loop returned on its own.
+    Ended,
+}
+
 /// Internal commands forwarded into the node task.
 enum NodeCommand {
     Stop,
     Dial(Multiaddr),
+    DialPeer(PeerId),
     Disconnect(PeerId),
     Discover(Option<(u16, u16)>),
+    SetPortRange(Option<(u16, u16)>),
+    ShowBlock(String),
+    QueryStatus,
 }
 
 async fn run_node(
     config: NodeConfig,
     mut cmd_rx: mpsc::UnboundedReceiver<NodeCommand>,
     event_tx: mpsc::UnboundedSender<ControlEvent>,
-) {
+) -> NodeExit {
     match Node::new(config).await {
         Err(e) => {
-            let _ = event_tx.send(ControlEvent::Error(e.to_string()));
+            let _ = event_tx.send(ControlEvent::Error(ControlError::StartupFailed(e)));
+            NodeExit::StartupFailed
         }
         Ok((mut node, mut node_events)) => {
             let peer_id = node.peer_id().to_string();
 
-            // Brief pause so the swarm binds its port before we announce.
-            tokio::time::sleep(std::time::Duration::from_millis(150)).await;
+            // Let the swarm bind its port before we announce, so the real
+            // port is known even when the config asked for any (0).
+            let _ = tokio::time::timeout(std::time::Duration::from_secs(2), async {
+                while node.listen_port().is_none() {
+                    node.step().await;
+                }
+            })
+            .await;
 
             let _ = event_tx.send(ControlEvent::NodeStarted {
                 peer_id: peer_id.clone(),
                 listen_addr: String::new(), // updated when Listening event arrives
+                port: node.listen_port(),
             });
 
-            loop {
+            let exit = loop {
                 tokio::select! {
                     Some(ctrl) = cmd_rx.recv() => {
                         match ctrl {
-                            NodeCommand::Stop => break,
+                            NodeCommand::Stop => break NodeExit::Stopped,
                             NodeCommand::Dial(addr) => {
                                 if let Err(e) = node.dial(addr) {
-                                    let _ = event_tx.send(ControlEvent::Error(e.to_string()));
+                                    let _ = event_tx
+                                        .send(ControlEvent::Error(ControlError::DialFailed(e)));
+                                }
+                            }
+                            NodeCommand::DialPeer(pid) => {
+                                if let Err(e) = node.dial_peer_id(pid) {
+                                    if matches!(e, NodeError::NoKnownAddress(_)) {
+                                        node.find_peer(pid);
+                                    }
+                                    let _ = event_tx
+                                        .send(ControlEvent::Error(ControlError::DialFailed(e)));
                                 }
                             }
                             NodeCommand::Disconnect(pid) => {
                                 if let Err(e) = node.disconnect(pid) {
-                                    let _ = event_tx.send(ControlEvent::Error(e.to_string()));
+                                    let _ = event_tx.send(ControlEvent::Error(
+                                        ControlError::DisconnectFailed(e),
+                                    ));
                                 }
                             }
                             NodeCommand::Discover(range) => {
                                 node.trigger_discovery(range);
                             }
+                            NodeCommand::SetPortRange(range) => {
+                                node.set_discovery_port_range(range);
+                            }
+                            NodeCommand::ShowBlock(prefix) => {
+                                let lookup = BlockLookup::resolve(node.blockchain(), &prefix);
+                                let _ = event_tx.send(ControlEvent::Block(lookup));
+                            }
+                            NodeCommand::QueryStatus => {
+                                let status = Box::new(node.status());
+                                let _ = event_tx.send(ControlEvent::Status(status));
+                            }
                         }
                     }
                     Some(ev) = node_events.recv() => {
-                        if event_tx.send(ControlEvent::NodeEvent(ev)).is_err() {
-                            break;
+                        if event_tx.send(ControlEvent::NodeEvent(Box::new(ev))).is_err() {
+                            break NodeExit::Stopped;
                         }
                     }
                     _ = node.run() => {
-                        break;
+                        break NodeExit::Ended;
                     }
                 }
-            }
+            };
 
+            if let Err(e) = node.shutdown().await {
+                let _ = event_tx.send(ControlEvent::Error(ControlError::ShutdownFailed(e)));
+            }
             warn!("Node task exiting");
             let _ = event_tx.send(ControlEvent::NodeStopped);
+            exit
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sp_transaction::{Transaction, TransactionType};
+
+    use super::*;
+
+    /// The first error `run_node` reports for `config` after `commands`.
+    async fn first_error(config: NodeConfig, commands: Vec<NodeCommand>) -> ControlError {
+        let (cmd_tx, cmd_rx) = mpsc::unbounded_channel();
+        let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+        for cmd in commands {
+            cmd_tx.send(cmd).unwrap();
+        }
+        tokio::spawn(run_node(config, cmd_rx, event_tx));
+        tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            loop {
+                if let Some(ControlEvent::Error(e)) = event_rx.recv().await {
+                    return e;
+                }
+            }
+        })
+        .await
+        .expect("no error reported")
+    }
+
+    #[tokio::test]
+    async fn startup_and_dial_failures_are_told_apart() {
+        // A data directory that is really a file can't hold the identity.
+        let file = std::env::temp_dir().join(format!("spn-ctl-test-{}", std::process::id()));
+        std::fs::write(&file, b"not a directory").unwrap();
+        let broken = NodeConfig { port: 0, data_dir: Some(file.clone()), ..NodeConfig::default() };
+        let startup = first_error(broken, Vec::new()).await;
+        std::fs::remove_file(&file).unwrap();
+        assert!(matches!(startup, ControlError::StartupFailed(_)), "{startup}");
+
+        let config = NodeConfig { port: 0, ..NodeConfig::default() };
+        let dial = first_error(config, vec![NodeCommand::DialPeer(PeerId::random())]).await;
+        assert!(matches!(dial, ControlError::DialFailed(NodeError::NoKnownAddress(_))), "{dial}");
+    }
+
+    #[tokio::test]
+    async fn port_range_filter_persists_across_discovery() {
+        let config = NodeConfig { port: 0, strict_port_range: true, ..NodeConfig::default() };
+        let outside: Multiaddr = "/ip4/127.0.0.1/tcp/5001".parse().unwrap();
+        let commands = vec![
+            NodeCommand::SetPortRange(Some((4000, 4100))),
+            NodeCommand::Discover(None),
+            NodeCommand::Discover(None),
+            NodeCommand::Dial(outside),
+        ];
+        let refused = first_error(config, commands).await;
+        assert!(
+            matches!(refused, ControlError::DialFailed(NodeError::PortOutsideRange(_))),
+            "{refused}"
+        );
+    }
+
+    #[tokio::test]
+    async fn user_stop_does_not_restart_but_a_crash_does() {
+        let mut restarts = RestartPolicy::new(2, Duration::from_secs(1));
+
+        let (cmd_tx, cmd_rx) = mpsc::unbounded_channel();
+        let (event_tx, _event_rx) = mpsc::unbounded_channel();
+        let config = NodeConfig { port: 0, ..NodeConfig::default() };
+        let node = tokio::spawn(run_node(config, cmd_rx, event_tx));
+        cmd_tx.send(NodeCommand::Stop).unwrap();
+        let stopped = node.await;
+        assert_eq!(stopped.as_ref().ok(), Some(&NodeExit::Stopped));
+        assert_eq!(restarts.after(&stopped), None);
+
+        let aborted = tokio::spawn(std::future::pending::<NodeExit>());
+        aborted.abort();
+        assert_eq!(restarts.after(&aborted.await), None);
+
+        let crashed: Result<NodeExit, _> = tokio::spawn(async { panic!("node blew up") }).await;
+        assert_eq!(restarts.after(&crashed), Some(Duration::from_secs(1)));
+        assert_eq!(restarts.after(&Ok(NodeExit::Ended)), Some(Duration::from_secs(2)));
+        assert_eq!(restarts.after(&crashed), None, "gives up after max attempts");
+
+        restarts.reset();
+        assert_eq!(restarts.after(&crashed), Some(Duration::from_secs(1)));
+    }
+
+    /// The next controller event, or `None` if none comes within 30 s.
+    async fn next_event(rx: &mut mpsc::UnboundedReceiver<ControlEvent>) -> Option<ControlEvent> {
+        tokio::time::timeout(Duration::from_secs(30), rx.recv()).await.ok().flatten()
+    }
+
+    /// Start a stand-in node task that stops cleanly on
+    /// [`NodeCommand::Stop`] and panics on any other command.
+    fn fragile_node(
+        _config: &NodeConfig,
+        event_tx: &mpsc::UnboundedSender<ControlEvent>,
+    ) -> (mpsc::UnboundedSender<NodeCommand>, JoinHandle<NodeExit>) {
+        let (cmd_tx, mut cmd_rx) = mpsc::unbounded_channel();
+        let event_tx = event_tx.clone();
+        let _ = event_tx.send(ControlEvent::NodeStarted {
+            peer_id: String::new(),
+            listen_addr: String::new(),
+            port: None,
+        });
+        let handle = tokio::spawn(async move {
+            match cmd_rx.recv().await {
+                Some(NodeCommand::Stop) => {
+                    let _ = event_tx.send(ControlEvent::NodeStopped);
+                    NodeExit::Stopped
+                }
+                _ => panic!("node blew up"),
+            }
+        });
+        (cmd_tx, handle)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn the_controller_restarts_a_crashed_node_but_not_a_stopped_one() {
+        let (cmd_tx, cmd_rx) = mpsc::unbounded_channel();
+        let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+        tokio::spawn(run_controller(NodeConfig::default(), cmd_rx, event_tx, fragile_node));
+
+        cmd_tx.send(ControlCommand::Start).unwrap();
+        assert!(matches!(next_event(&mut event_rx).await, Some(ControlEvent::NodeStarted { .. })));
+        cmd_tx.send(ControlCommand::Disconnect(PeerId::random())).unwrap();
+        assert!(matches!(
+            next_event(&mut event_rx).await,
+            Some(ControlEvent::Error(ControlError::NodeCrashed(msg))) if msg == "node blew up"
+        ));
+        assert!(matches!(next_event(&mut event_rx).await, Some(ControlEvent::NodeStopped)));
+        let restarting = next_event(&mut event_rx).await;
+        assert!(matches!(restarting, Some(ControlEvent::Restarting { attempt: 1, .. })));
+        assert!(matches!(next_event(&mut event_rx).await, Some(ControlEvent::NodeStarted { .. })));
+
+        cmd_tx.send(ControlCommand::Stop).unwrap();
+        assert!(matches!(next_event(&mut event_rx).await, Some(ControlEvent::NodeStopped)));
+        assert!(next_event(&mut event_rx).await.is_none(), "a stopped node must stay stopped");
+    }
+
+    #[test]
+    fn block_lookup_tells_a_miss_from_an_ambiguous_prefix() {
+        let mut chain = Blockchain::new();
+        chain
+            .add_block(vec![Transaction::new(TransactionType::PostCreated, vec![])])
+            .unwrap();
+        let hash = chain.tip().hash_hex();
+
+        match BlockLookup::resolve(&chain, &hash[..6]) {
+            BlockLookup::Found(block) => assert_eq!(block.index, 1),
+            _ => panic!("expected the tip"),
         }
+        let missing = if hash.starts_with('0') { "f" } else { "0" }.repeat(64);
+        assert!(matches!(BlockLookup::resolve(&chain, &missing), BlockLookup::NotFound));
+        assert!(matches!(BlockLookup::resolve(&chain, ""), BlockLookup::Ambiguous(2)));
     }
 }
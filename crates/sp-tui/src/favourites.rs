@@ -0,0 +1,72 @@
+//! Peer addresses the user always wants reconnected.
+//!
+//! Kept apart from `peers_store`, which caches every address discovery turns
+//! up: favourites only change through `/fav`, and are dialled every time the
+//! node starts, before the best-effort discovered peers.
+
+use std::{fs, io, path::Path};
+
+/// File name of the favourites list inside the data directory.
+pub const FAVOURITES_FILE: &str = "favourites.json";
+
+/// Read the favourites at `path`.  A missing or unreadable file is an empty
+/// list.
+pub fn load(path: &Path) -> Vec<String> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Overwrite the favourites at `path`, creating its directory if needed.
+fn save(path: &Path, addrs: &[String]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let json = serde_json::to_string_pretty(addrs).map_err(io::Error::other)?;
+    fs::write(path, json)
+}
+
+/// Append `addr`; `false` if it was already a favourite.
+pub fn add(path: &Path, addr: &str) -> io::Result<bool> {
+    let mut addrs = load(path);
+    if addrs.iter().any(|a| a == addr) {
+        return Ok(false);
+    }
+    addrs.push(addr.to_string());
+    save(path, &addrs).map(|()| true)
+}
+
+/// Drop `addr`; `false` if it wasn't a favourite.
+pub fn remove(path: &Path, addr: &str) -> io::Result<bool> {
+    let mut addrs = load(path);
+    let before = addrs.len();
+    addrs.retain(|a| a != addr);
+    if addrs.len() == before {
+        return Ok(false);
+    }
+    save(path, &addrs).map(|()| true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn favourites_round_trip_through_their_file() {
+        let dir = std::env::temp_dir().join(format!("spn-fav-test-{}", std::process::id()));
+        let path = dir.join(FAVOURITES_FILE);
+        assert!(load(&path).is_empty());
+
+        assert!(add(&path, "/ip4/1.2.3.4/tcp/4001").unwrap());
+        assert!(add(&path, "/dns4/node.example.org/tcp/4001").unwrap());
+        assert!(!add(&path, "/ip4/1.2.3.4/tcp/4001").unwrap());
+        assert_eq!(load(&path), ["/ip4/1.2.3.4/tcp/4001", "/dns4/node.example.org/tcp/4001"]);
+
+        assert!(remove(&path, "/ip4/1.2.3.4/tcp/4001").unwrap());
+        assert!(!remove(&path, "/ip4/1.2.3.4/tcp/4001").unwrap());
+        assert_eq!(load(&path), ["/dns4/node.example.org/tcp/4001"]);
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
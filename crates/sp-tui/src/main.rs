@@ -1,13 +1,16 @@
+mod addr;
 mod app;
 mod controller;
 mod events;
+mod favourites;
 mod peers_store;
+mod session;
 mod ui;
 
 use std::{io, path::PathBuf, time::{Duration, Instant}};
 
-use app::{App, ContentView, NodeState};
-use controller::{ControlCommand, ControlEvent, NodeController};
+use app::{App, ContentView, NodeState, TrafficLevel};
+use controller::{BlockLookup, ControlCommand, ControlError, ControlEvent, NodeController};
 use crossterm::{
     event::{DisableMouseCapture, EnableMouseCapture, KeyCode, KeyModifiers},
     execute,
@@ -15,7 +18,8 @@ use crossterm::{
 };
 use events::TuiEvent;
 use ratatui::{backend::CrosstermBackend, Terminal};
-use sp_node::{NodeConfig, NodeEvent};
+use session::Session;
+use sp_node::{AutoConnectPolicy, DiscoveryMode, NodeConfig, NodeEvent};
 use tracing_subscriber::EnvFilter;
 
 #[tokio::main]
@@ -63,14 +67,26 @@ async fn run_tui(
     let discover_interval = Duration::from_secs(60);
     let mut last_discovery = Instant::now();
 
-    let config = NodeConfig { quiet: true, ..NodeConfig::default() };
+    let mut config = tui_config();
+    if let Some(session) = session::load(&spn_session_path()) {
+        session.apply(&mut config);
+    }
+    app.session = Session::from_config(&config);
+    app.status.mode = format!("{:?}", config.mode);
+    app.status.sync_strategy = format!("{:?}", config.sync_strategy);
+    app.status.discovery_mode = format!("{:?}", config.discovery_mode);
+    app.status.port_range = describe_port_range(
+        config.discovery_port_range.as_ref().map(|r| (*r.start(), *r.end())),
+    );
+    app.status.port = config.port;
+    app.status.heartbeat_interval = config.heartbeat_interval;
     let controller = NodeController::spawn(config);
     let cmd_tx = controller.cmd_tx;
     let mut event_rx = controller.event_rx;
 
     // Auto-start: kick off the node immediately on launch.
     app.node_state = NodeState::Starting;
-    app.push_traffic("Auto-starting node…");
+    app.push_traffic(TrafficLevel::Info, "Auto-starting node…");
     let _ = cmd_tx.send(ControlCommand::Start);
 
     loop {
@@ -91,6 +107,10 @@ async fn run_tui(
                     app.should_quit = true;
                     break;
                 }
+                if key.modifiers == KeyModifiers::CONTROL && key.code == KeyCode::Char('f') {
+                    app.cycle_traffic_filter();
+                    continue;
+                }
 
                 match key.code {
                     KeyCode::Enter => {
@@ -101,7 +121,8 @@ async fn run_tui(
                         if !raw.is_empty() {
                             app.push_history(raw.clone());
                         }
-                        execute_command(&mut app, &cmd_tx, &raw);
+                        let outcomes = execute_command(&mut app, &raw);
+                        apply_outcomes(&mut app, &cmd_tx, outcomes);
                     }
                     KeyCode::Backspace => {
                         app.input.pop();
@@ -120,6 +141,10 @@ async fn run_tui(
                     KeyCode::PageDown => {
                         app.scroll_traffic_down();
                     }
+                    // End jumps back to the newest entry and follows again.
+                    KeyCode::End => {
+                        app.follow_traffic();
+                    }
                     KeyCode::Char(c) => {
                         // Typing a character exits history navigation.
                         app.reset_history_nav();
@@ -147,50 +172,105 @@ async fn run_tui(
     Ok(())
 }
 
-/// Parse and execute a slash command entered by the user.
-fn execute_command(
-    app: &mut App,
-    cmd_tx: &tokio::sync::mpsc::UnboundedSender<ControlCommand>,
-    raw: &str,
-) {
+/// What a slash command asks of the TUI beyond its own bookkeeping, in the
+/// order [`apply_outcomes`] carries it out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CommandOutcome {
+    /// Send this command to the node controller.
+    Sent(ControlCommand),
+    /// Show this on the output line under the prompt.
+    Feedback(String),
+    /// Add a line to the traffic log.
+    Traffic(TrafficLevel, String),
+    ViewChanged(ContentView),
+    Quit,
+}
+
+/// Parse a slash command entered by the user and say what it should do.
+///
+/// Node state, the session and the status fields a command changes are
+/// updated on `app` directly; everything the user sees or the node is asked
+/// for comes back as outcomes, so parsing can be tested without a controller.
+fn execute_command(app: &mut App, raw: &str) -> Vec<CommandOutcome> {
     let parts: Vec<&str> = raw.splitn(2, ' ').collect();
     let cmd = parts[0];
     let arg = parts.get(1).copied().unwrap_or("").trim();
+    let feedback = |msg: String| vec![CommandOutcome::Feedback(msg)];
+    let not_running = || feedback("Node is not running.".to_string());
 
     match cmd {
         "/start" => {
             if app.node_state == NodeState::Stopped {
                 app.node_state = NodeState::Starting;
-                app.push_traffic("Starting node…");
-                let _ = cmd_tx.send(ControlCommand::Start);
+                vec![
+                    CommandOutcome::Traffic(TrafficLevel::Info, "Starting node…".into()),
+                    CommandOutcome::Sent(ControlCommand::Start),
+                ]
             } else {
-                app.set_output("Node is already running. Use /stop or /restart.");
+                feedback("Node is already running. Use /stop or /restart.".into())
             }
         }
 
         "/stop" => {
             if app.node_state == NodeState::Running {
-                app.push_traffic("Stopping node…");
-                let _ = cmd_tx.send(ControlCommand::Stop);
+                vec![
+                    CommandOutcome::Traffic(TrafficLevel::Info, "Stopping node…".into()),
+                    CommandOutcome::Sent(ControlCommand::Stop),
+                ]
             } else {
-                app.set_output("Node is not running.");
+                not_running()
             }
         }
 
         "/restart" => {
             app.node_state = NodeState::Restarting;
-            app.push_traffic("Restarting node…");
-            let _ = cmd_tx.send(ControlCommand::Restart);
+            vec![
+                CommandOutcome::Traffic(TrafficLevel::Info, "Restarting node…".into()),
+                CommandOutcome::Sent(ControlCommand::Restart),
+            ]
         }
 
-        "/traffic" => {
-            app.view = ContentView::Traffic;
+        "/reconnect" => {
+            if app.node_state != NodeState::Running {
+                return not_running();
+            }
+            let addrs: Vec<libp2p::Multiaddr> =
+                favourite_addrs().into_iter().chain(stored_peer_addrs()).collect();
+            if addrs.is_empty() {
+                return feedback("No stored peers".into());
+            }
+            let msg = format!("Reconnecting to {} stored peer(s)…", addrs.len());
+            let mut out = vec![
+                CommandOutcome::Traffic(TrafficLevel::Info, msg.clone()),
+                CommandOutcome::Feedback(msg),
+            ];
+            out.extend(
+                addrs
+                    .into_iter()
+                    .map(|addr| CommandOutcome::Sent(ControlCommand::Connect(addr))),
+            );
+            out
         }
 
+        "/traffic" => vec![CommandOutcome::ViewChanged(ContentView::Traffic)],
+
         "/status" => {
-            app.view = ContentView::Status;
-            app.status.peers_connected = app.connected_peers.len();
-            app.status.peers_discovered = app.discovered_peers.len();
+            let mut out = vec![CommandOutcome::ViewChanged(ContentView::Status)];
+            // The answer arrives as ControlEvent::Status.
+            if app.node_state == NodeState::Running {
+                out.push(CommandOutcome::Sent(ControlCommand::QueryStatus));
+            }
+            out
+        }
+
+        "/whoami" => {
+            if app.node_state != NodeState::Running {
+                not_running()
+            } else {
+                // Printed once the fresh status arrives as ControlEvent::Status.
+                app.whoami_pending = true;
+                vec![CommandOutcome::Sent(ControlCommand::QueryStatus)]
+            }
         }
 
         "/discover" => {
@@ -199,114 +279,264 @@ fn execute_command(
             } else {
                 match parse_port_range(arg) {
                     Some(range) => Some(range),
-                    None => {
-                        app.set_output("Usage: /discover [<start port>-<end port>]");
-                        return;
-                    }
+                    None => return feedback("Usage: /discover [<start port>-<end port>]".into()),
                 }
             };
             let desc = match port_range {
                 None => "Discovering peers on node port…".to_string(),
                 Some((s, e)) => format!("Discovering peers on ports {s}–{e}…"),
             };
-            app.push_traffic(desc);
-            app.view = ContentView::Discovered;
-            let _ = cmd_tx.send(ControlCommand::Discover(port_range));
+            // Discovery switches the node to both mechanisms.
+            if app.session.discovery_mode != DiscoveryMode::Both {
+                app.session.discovery_mode = DiscoveryMode::Both;
+                app.status.discovery_mode = format!("{:?}", DiscoveryMode::Both);
+                save_session(app);
+            }
+            vec![
+                CommandOutcome::Traffic(TrafficLevel::Info, desc),
+                CommandOutcome::ViewChanged(ContentView::Discovered),
+                CommandOutcome::Sent(ControlCommand::Discover(port_range)),
+            ]
+        }
+
+        "/portrange" => {
+            let port_range = if arg == "clear" {
+                None
+            } else {
+                match parse_port_range(arg) {
+                    Some(range) => Some(range),
+                    None => {
+                        return feedback(
+                            "Usage: /portrange <start port>-<end port> | clear".into(),
+                        )
+                    }
+                }
+            };
+            app.status.port_range = describe_port_range(port_range);
+            vec![
+                CommandOutcome::Traffic(
+                    TrafficLevel::Info,
+                    format!("Discovery port range: {}", app.status.port_range),
+                ),
+                CommandOutcome::Sent(ControlCommand::SetPortRange(port_range)),
+            ]
         }
 
-        "/connected" => {
-            app.view = ContentView::Connected;
-            app.push_traffic("Switched to connected peers view");
+        "/connected" => vec![
+            CommandOutcome::ViewChanged(ContentView::Connected),
+            CommandOutcome::Traffic(TrafficLevel::Info, "Switched to connected peers view".into()),
+        ],
+
+        "/peer" => {
+            if arg.is_empty() {
+                return feedback("Usage: /peer <node-id or prefix>".into());
+            }
+            let matches = app.find_peers_by_prefix(arg);
+            match matches.as_slice() {
+                [] => feedback(format!("No peer matches {arg}")),
+                [peer_id] => {
+                    let stored_addrs = peers_store::load();
+                    let stored = app
+                        .peer_addrs(peer_id)
+                        .iter()
+                        .any(|a| stored_addrs.contains(a));
+                    let peer_id = peer_id.clone();
+                    vec![CommandOutcome::ViewChanged(ContentView::Peer { peer_id, stored })]
+                }
+                _ => feedback(format!(
+                    "Ambiguous prefix {arg}: {} peers match; type more of the id",
+                    matches.len()
+                )),
+            }
+        }
+
+        "/block" => {
+            let prefix = arg.trim_end_matches('…');
+            let is_hex = prefix.chars().all(|c| c.is_ascii_hexdigit());
+            if prefix.is_empty() || prefix.len() > 64 || !is_hex {
+                feedback("Usage: /block <hex hash or prefix>".into())
+            } else if app.node_state != NodeState::Running {
+                not_running()
+            } else {
+                vec![CommandOutcome::Sent(ControlCommand::ShowBlock(prefix.to_string()))]
+            }
         }
 
         "/disconnect" => {
+            let disconnect = |shown: &str, peer_id| {
+                vec![
+                    CommandOutcome::Traffic(
+                        TrafficLevel::Info,
+                        format!("Disconnecting from {shown}"),
+                    ),
+                    CommandOutcome::Sent(ControlCommand::Disconnect(peer_id)),
+                ]
+            };
             if arg.is_empty() {
-                app.set_output("Usage: /disconnect <node-id>  or  /disconnect <ip>:<port>");
+                feedback("Usage: /disconnect <node-id or prefix>  or  /disconnect <ip:port>".into())
             } else if let Ok(peer_id) = arg.parse::<libp2p::PeerId>() {
                 // Argument is a bare peer-id.
-                app.push_traffic(format!("Disconnecting from {}", &arg[..arg.len().min(20)]));
-                let _ = cmd_tx.send(ControlCommand::Disconnect(peer_id));
-            } else if let Some(addr_str) = parse_ip_port(arg).or_else(|| {
-                // Also accept a raw multiaddr like /ip4/1.2.3.4/tcp/1234
-                if arg.starts_with('/') { Some(arg.to_string()) } else { None }
-            }) {
+                disconnect(&arg[..arg.len().min(20)], peer_id)
+            } else if let Ok(addr) = addr::parse_dial_addr(arg) {
                 // Argument looks like ip:port or a multiaddr — look up the peer by address.
-                match find_peer_by_addr(&app.connected_peers, &addr_str) {
-                    Some(peer_id) => {
-                        app.push_traffic(format!("Disconnecting from {arg}"));
-                        let _ = cmd_tx.send(ControlCommand::Disconnect(peer_id));
-                    }
-                    None => {
-                        app.set_output(format!("No connected peer found at {arg}"));
-                    }
+                match find_peer_by_addr(&app.connected_peers, &addr.to_string()) {
+                    Some(peer_id) => disconnect(arg, peer_id),
+                    None => feedback(format!("No connected peer found at {arg}")),
                 }
             } else {
-                app.set_output("Invalid argument. Use a peer-id or ip:port.");
+                // A prefix of a connected peer's id, as shown truncated in the UI.
+                let matches = app.find_connected_peers_by_prefix(arg);
+                match matches.as_slice() {
+                    [] => feedback(format!("No connected peer matches {arg}")),
+                    [pid] => match pid.parse::<libp2p::PeerId>() {
+                        Ok(peer_id) => disconnect(&pid[..pid.len().min(20)], peer_id),
+                        Err(_) => feedback(format!("Invalid peer id: {pid}")),
+                    },
+                    _ => feedback(format!(
+                        "Ambiguous prefix {arg}: {} connected peers match; type more of the id",
+                        matches.len()
+                    )),
+                }
             }
         }
 
         "/connect" => {
             if arg.is_empty() {
-                app.set_output("Usage: /connect <ip>:<port>");
+                feedback(
+                    "Usage: /connect <host>:<port>  or  /connect <multiaddr>  or  /connect <node-id>"
+                        .into(),
+                )
+            } else if let Ok(peer_id) = arg.parse::<libp2p::PeerId>() {
+                // Bare peer-id: dial a known address, else look it up in the DHT.
+                vec![
+                    CommandOutcome::Traffic(
+                        TrafficLevel::Info,
+                        format!("Connecting to {}", &arg[..arg.len().min(20)]),
+                    ),
+                    CommandOutcome::Sent(ControlCommand::ConnectPeer(peer_id)),
+                ]
             } else {
-                // Accept both /ip4/... multiaddr syntax and plain ip:port.
-                let multiaddr_str = if arg.starts_with('/') {
-                    arg.to_string()
-                } else {
-                    match parse_ip_port(arg) {
-                        Some(m) => m,
-                        None => {
-                            app.set_output("Invalid address. Use ip:port or /ip4/x.x.x.x/tcp/port");
-                            return;
-                        }
-                    }
-                };
-                match multiaddr_str.parse::<libp2p::Multiaddr>() {
+                // Accept ip:port, host:port and full multiaddrs.
+                match addr::parse_dial_addr(arg) {
                     Ok(addr) => {
-                        app.push_traffic(format!("Connecting to {multiaddr_str}"));
-                        let _ = cmd_tx.send(ControlCommand::Connect(addr));
+                        let line = format!("Connecting to {addr}");
+                        vec![
+                            CommandOutcome::Traffic(TrafficLevel::Info, line),
+                            CommandOutcome::Sent(ControlCommand::Connect(addr)),
+                        ]
+                    }
+                    Err(e) => feedback(format!("Invalid address: {e}")),
+                }
+            }
+        }
+
+        "/fav" => {
+            let (sub, addr_arg) = arg.split_once(' ').map_or((arg, ""), |(s, a)| (s, a.trim()));
+            let path = spn_favourites_path();
+            match (sub, addr_arg) {
+                ("list" | "", "") => {
+                    let favs = favourites::load(&path);
+                    let mut out = vec![CommandOutcome::ViewChanged(ContentView::Traffic)];
+                    if favs.is_empty() {
+                        out.push(CommandOutcome::Feedback(
+                            "No favourites; add one with /fav add <addr>".into(),
+                        ));
                     }
-                    Err(_) => {
-                        app.set_output("Could not parse address as multiaddr.");
+                    out.extend(favs.into_iter().map(|fav| {
+                        CommandOutcome::Traffic(TrafficLevel::Info, format!("favourite  {fav}"))
+                    }));
+                    out
+                }
+                ("add", a) if !a.is_empty() => match addr::parse_dial_addr(a) {
+                    Ok(addr) => match favourites::add(&path, &addr.to_string()) {
+                        Ok(true) => {
+                            let mut out = feedback(format!("Added {addr} to favourites"));
+                            if app.node_state == NodeState::Running {
+                                out.push(CommandOutcome::Sent(ControlCommand::Connect(addr)));
+                            }
+                            out
+                        }
+                        Ok(false) => feedback(format!("{addr} is already a favourite")),
+                        Err(e) => feedback(format!("Could not save favourites: {e}")),
+                    },
+                    Err(e) => feedback(format!("Invalid address: {e}")),
+                },
+                ("rm", a) if !a.is_empty() => {
+                    // Favourites are stored as multiaddrs; accept the short
+                    // form too, and anything already stored verbatim.
+                    let stored = addr::parse_dial_addr(a).map_or(a.to_string(), |m| m.to_string());
+                    match favourites::remove(&path, &stored) {
+                        Ok(true) => feedback(format!("Removed {stored} from favourites")),
+                        Ok(false) => feedback(format!("{stored} is not a favourite")),
+                        Err(e) => feedback(format!("Could not save favourites: {e}")),
                     }
                 }
+                _ => feedback("Usage: /fav add <addr>  or  /fav list  or  /fav rm <addr>".into()),
             }
         }
 
         "/help" => {
-            app.view = ContentView::Traffic;
+            let mut out = vec![CommandOutcome::ViewChanged(ContentView::Traffic)];
             for line in [
                 "─── available commands ─────────────────────────────────────",
                 "/start                       start the node",
                 "/stop                        stop the node",
                 "/restart                     restart the node",
+                "/reconnect                   re-dial every favourite and stored peer",
                 "/traffic                     see the node's traffic",
                 "/status                      see the node's status",
+                "/whoami                      show peer id, addresses, network and genesis",
                 "/discover [start-end]        discover peers (internet-wide Kademlia scan)",
+                "/portrange <start-end>|clear filter discovery to a port range",
                 "/connected                   see nodes currently connected",
-                "/connect <ip>:<port>         connect to a node",
+                "/peer <node id or prefix>    show details for one node",
+                "/block <hash or prefix>      show details for one block",
+                "/connect <host>:<port>       connect to a node (or give a multiaddr)",
+                "/connect <node id>           connect to a node by peer-id",
                 "/disconnect <node id>        disconnect from a node by peer-id",
                 "/disconnect <ip>:<port>      disconnect from a node by address",
+                "/fav add|rm <host>:<port>    add or remove a favourite, dialled on every start",
+                "/fav list                    list the favourites",
                 "/help                        show this help",
                 "/quit                        quit spn",
-                "keys: ↑/↓ history · PgUp/PgDn scroll · Ctrl-C quit",
+                "keys: ↑/↓ history · PgUp/PgDn scroll · End follow · Ctrl-F filter · Ctrl-C quit",
                 "────────────────────────────────────────────────────────────",
             ] {
-                app.push_traffic(line);
+                out.push(CommandOutcome::Traffic(TrafficLevel::Info, line.to_string()));
             }
+            out
         }
 
         "/quit" | "/exit" => {
+            let mut out = Vec::new();
             if app.node_state == NodeState::Running {
-                let _ = cmd_tx.send(ControlCommand::Stop);
+                out.push(CommandOutcome::Sent(ControlCommand::Stop));
             }
-            app.should_quit = true;
+            out.push(CommandOutcome::Quit);
+            out
         }
 
-        "" => {}
+        "" => Vec::new(),
 
-        other => {
-            app.set_output(format!("Unknown command: {other}  (try /help)"));
+        other => feedback(format!("Unknown command: {other}  (try /help)")),
+    }
+}
+
+/// Carry out what [`execute_command`] asked for.
+fn apply_outcomes(
+    app: &mut App,
+    cmd_tx: &tokio::sync::mpsc::UnboundedSender<ControlCommand>,
+    outcomes: Vec<CommandOutcome>,
+) {
+    for outcome in outcomes {
+        match outcome {
+            CommandOutcome::Sent(cmd) => {
+                let _ = cmd_tx.send(cmd);
+            }
+            CommandOutcome::Feedback(msg) => app.set_output(msg),
+            CommandOutcome::Traffic(level, line) => app.push_traffic(level, line),
+            CommandOutcome::ViewChanged(view) => app.view = view,
+            CommandOutcome::Quit => app.should_quit = true,
         }
     }
 }
@@ -318,63 +548,150 @@ fn handle_controller_event(
     ev: ControlEvent,
 ) {
     match ev {
-        ControlEvent::NodeStarted { peer_id, listen_addr } => {
+        ControlEvent::NodeStarted { peer_id, listen_addr, port } => {
             app.node_state = NodeState::Running;
             app.status.peer_id = peer_id.clone();
+            if let Some(port) = port {
+                app.status.port = port;
+                if app.session.port != port {
+                    app.session.port = port;
+                    save_session(app);
+                }
+            }
             if !listen_addr.is_empty() {
                 app.status.listen_addr = listen_addr.clone();
             }
-            app.push_traffic(format!("Node started  peer {peer_id}"));
+            app.push_traffic(TrafficLevel::Good, format!("Node started  peer {peer_id}"));
 
-            // Auto-connect to previously known peers.
-            let stored = peers_store::load();
+            // Favourites are always dialled; previously known peers on a
+            // best-effort basis.
+            let favourites = favourite_addrs();
+            if !favourites.is_empty() {
+                let msg = format!("Connecting to {} favourite(s)…", favourites.len());
+                app.push_traffic(TrafficLevel::Info, msg);
+            }
+            let stored = stored_peer_addrs();
             if !stored.is_empty() {
-                app.push_traffic(format!(
-                    "Reconnecting to {} stored peer(s)…",
-                    stored.len()
-                ));
-                for addr_str in stored {
-                    if let Ok(addr) = addr_str.parse::<libp2p::Multiaddr>() {
-                        let _ = cmd_tx.send(ControlCommand::Connect(addr));
-                    }
-                }
+                let msg = format!("Reconnecting to {} stored peer(s)…", stored.len());
+                app.push_traffic(TrafficLevel::Info, msg);
+            }
+            for addr in favourites.into_iter().chain(stored) {
+                let _ = cmd_tx.send(ControlCommand::Connect(addr));
             }
         }
 
         ControlEvent::NodeStopped => {
             app.node_state = NodeState::Stopped;
             app.connected_peers.clear();
+            app.connected_since.clear();
+            app.peer_agents.clear();
             app.status.peers_connected = 0;
-            app.push_traffic("Node stopped");
+            app.status.topic_peers.clear();
+            app.status.last_heartbeat = None;
+            app.push_traffic(TrafficLevel::Warn, "Node stopped");
+        }
+
+        ControlEvent::Restarting { attempt, max, delay } => {
+            app.node_state = NodeState::Restarting;
+            app.push_traffic(
+                TrafficLevel::Info,
+                format!("Restarting node in {}s (attempt {attempt}/{max})…", delay.as_secs()),
+            );
         }
 
-        ControlEvent::NodeEvent(node_ev) => match node_ev {
+        ControlEvent::NodeEvent(node_ev) => match *node_ev {
             NodeEvent::Listening(addr) => {
                 app.status.listen_addr = addr.to_string();
-                app.push_traffic(format!("Listening on {addr}"));
+                app.push_traffic(TrafficLevel::Info, format!("Listening on {addr}"));
             }
             NodeEvent::PeerConnected(pid) => {
                 let pid_str = pid.to_string();
-                // Move from discovered → connected.
-                app.discovered_peers.retain(|(id, _)| id != &pid_str);
+                // Move from discovered → connected, keeping the addresses so
+                // the peer can be listed again once it disconnects.
+                let addrs = app
+                    .discovered_peers
+                    .iter()
+                    .position(|(id, _)| id == &pid_str)
+                    .map(|i| app.discovered_peers.remove(i).1)
+                    .unwrap_or_default();
                 if !app.connected_peers.iter().any(|(id, _)| id == &pid_str) {
-                    app.connected_peers.push((pid_str.clone(), Vec::new()));
+                    app.connected_peers.push((pid_str.clone(), addrs));
+                    app.connected_since.insert(pid_str.clone(), std::time::Instant::now());
                 }
                 app.status.peers_connected = app.connected_peers.len();
                 app.status.peers_discovered = app.discovered_peers.len();
-                app.push_traffic(format!("Peer connected: {pid_str}"));
+                app.push_traffic(TrafficLevel::Good, format!("Peer connected: {pid_str}"));
             }
             NodeEvent::PeerDisconnected(pid) => {
                 let pid_str = pid.to_string();
-                app.connected_peers.retain(|(id, _)| id != &pid_str);
+                // Back to discovered: the node won't report it again, since it
+                // still knows the addresses.
+                if let Some(i) = app.connected_peers.iter().position(|(id, _)| id == &pid_str) {
+                    let (_, addrs) = app.connected_peers.remove(i);
+                    if !addrs.is_empty() {
+                        app.discovered_peers.push((pid_str.clone(), addrs));
+                    }
+                }
+                app.connected_since.remove(&pid_str);
+                app.peer_agents.remove(&pid_str);
                 app.status.peers_connected = app.connected_peers.len();
-                app.push_traffic(format!("Peer disconnected: {pid_str}"));
+                app.status.peers_discovered = app.discovered_peers.len();
+                app.push_traffic(TrafficLevel::Warn, format!("Peer disconnected: {pid_str}"));
+            }
+            NodeEvent::PeerIdentified { peer_id, agent_version } => {
+                app.peer_agents.insert(peer_id.to_string(), agent_version);
             }
-            NodeEvent::PeerDiscovered { peer_id, addrs } => {
+            NodeEvent::DialFailed { addr, peer_id, error } => {
+                let target = match (addr, peer_id) {
+                    (Some(addr), _) => addr.to_string(),
+                    (None, Some(pid)) => pid.to_string(),
+                    (None, None) => "peer".to_string(),
+                };
+                app.push_traffic(
+                    TrafficLevel::Error,
+                    format!("Connection to {target} failed: {error}"),
+                );
+                app.set_output(format!("error: connection to {target} failed"));
+            }
+            NodeEvent::DialOutsidePortRange(addr) => {
+                app.push_traffic(
+                    TrafficLevel::Warn,
+                    format!("Dialling {addr} outside the discovery port range"),
+                );
+            }
+            NodeEvent::PeerAddrsPruned { peer_id, addrs } => {
+                let pid_str = peer_id.to_string();
+                if addrs.is_empty() {
+                    app.discovered_peers.retain(|(id, _)| id != &pid_str);
+                } else if let Some((_, known)) =
+                    app.discovered_peers.iter_mut().find(|(id, _)| id == &pid_str)
+                {
+                    *known = addrs.iter().map(|a| a.to_string()).collect();
+                }
+                app.status.peers_discovered = app.discovered_peers.len();
+            }
+            NodeEvent::DiscoveryCompleted { found } => {
+                let msg = format!("Discovery done, {found} new peers");
+                app.push_traffic(TrafficLevel::Info, msg.clone());
+                app.set_output(msg);
+            }
+            NodeEvent::PeerExpired { peer_id } => {
+                let pid_str = peer_id.to_string();
+                app.discovered_peers.retain(|(id, _)| id != &pid_str);
+                app.discovery_sources.remove(&pid_str);
+                app.status.peers_discovered = app.discovered_peers.len();
+                app.push_traffic(TrafficLevel::Info, format!("Peer expired (mDNS): {pid_str}"));
+            }
+            NodeEvent::PeerDiscovered { peer_id, addrs, source } => {
                 let pid_str = peer_id.to_string();
                 let addr_strs: Vec<String> = addrs.iter().map(|a| a.to_string()).collect();
+                let sources = app.discovery_sources.entry(pid_str.clone()).or_default();
+                if !sources.contains(&source) {
+                    sources.push(source);
+                }
 
-                // Persist each address for future reconnection.
+                // The node only reports addresses it hasn't reported before;
+                // persist each for future reconnection.
                 for addr in &addr_strs {
                     peers_store::add(addr);
                 }
@@ -395,60 +712,237 @@ fn handle_controller_event(
                     }
                 }
                 app.status.peers_discovered = app.discovered_peers.len();
-                app.push_traffic(format!(
-                    "Peer discovered: {}  ({})",
+                app.push_traffic(TrafficLevel::Info, format!(
+                    "Peer discovered via {source}: {}  ({})",
                     &pid_str[..pid_str.len().min(20)],
                     addr_strs.first().map(String::as_str).unwrap_or("-")
                 ));
-
-                // Auto-connect to every newly discovered peer.
-                for addr_str in &addr_strs {
-                    if let Ok(addr) = addr_str.parse::<libp2p::Multiaddr>() {
-                        let _ = cmd_tx.send(ControlCommand::Connect(addr));
-                    }
-                }
             }
-            NodeEvent::TransactionReceived(tx) => {
-                app.push_traffic(format!("Transaction received: {} ({:?})", tx.id, tx.kind));
+            NodeEvent::PeerResolved { peer_id, addrs } => {
+                let pid_str = peer_id.to_string();
+                app.push_traffic(TrafficLevel::Info, format!(
+                    "Peer resolved: {}  ({})",
+                    &pid_str[..pid_str.len().min(20)],
+                    addrs.first().map(|a| a.to_string()).unwrap_or_else(|| "-".into())
+                ));
+            }
+            NodeEvent::TransactionReceived { tx, origin } => {
+                let from = relayed_by(origin);
+                app.push_traffic(
+                    TrafficLevel::Tx,
+                    format!("Transaction received: {} ({:?}){from}", tx.id, tx.kind),
+                );
+            }
+            NodeEvent::TransactionRejected { id, reason } => {
+                app.push_traffic(TrafficLevel::Warn, format!("rejected tx {id}: {reason}"));
+            }
+            NodeEvent::Heartbeat { tip_index, peer_count } => {
+                app.status.last_heartbeat = Some(Instant::now());
+                app.status.chain_length = tip_index as usize + 1;
+                app.status.peers_connected = peer_count;
+            }
+            NodeEvent::TransactionsEvicted(ids) => {
+                app.push_traffic(
+                    TrafficLevel::Warn,
+                    format!("Mempool evicted {} transactions", ids.len()),
+                );
             }
-            NodeEvent::BlockReceived(block) => {
-                app.push_traffic(format!(
-                    "Block received: #{} ({} txs)",
+            NodeEvent::BlockFormed { block_index, tx_count } => {
+                app.status.chain_length = block_index as usize + 1;
+                app.push_traffic(
+                    TrafficLevel::Good,
+                    format!("Block formed: #{block_index} ({tx_count} txs)"),
+                );
+            }
+            NodeEvent::BlockReceived { block, origin } => {
+                app.push_traffic(TrafficLevel::Block, format!(
+                    "Block received: #{} ({} txs){}",
                     block.index,
-                    block.transactions.len()
+                    block.transactions.len(),
+                    relayed_by(origin)
                 ));
             }
-            NodeEvent::BlockFinalised { block_index } => {
-                app.push_traffic(format!("Block finalised: #{block_index}"));
+            NodeEvent::BlockRejected { block_index, reason } => {
+                app.push_traffic(
+                    TrafficLevel::Error,
+                    format!("Block #{block_index} rejected: {reason}"),
+                );
+            }
+            NodeEvent::BlockVerified { block_index, verifications, threshold } => {
+                app.push_traffic(
+                    TrafficLevel::Info,
+                    format!("Block #{block_index}: {verifications}/{threshold} verifications"),
+                );
+            }
+            NodeEvent::BlockFinalised { block_index, tx_count } => {
+                app.status.finalised_txs.record(Instant::now(), tx_count);
+                app.push_traffic(TrafficLevel::Good, format!("Block finalised: #{block_index}"));
             }
-            NodeEvent::ChainSynced { new_length } => {
+            NodeEvent::ChainSynced { new_length, blocks_applied } => {
                 app.status.chain_length = new_length;
-                app.push_traffic(format!("Chain synced — length {new_length}"));
+                app.push_traffic(TrafficLevel::Good, format!(
+                    "Chain synced — length {new_length} (+{blocks_applied} blocks)"
+                ));
+            }
+            NodeEvent::ChainPruned { up_to_index } => {
+                app.push_traffic(
+                    TrafficLevel::Info,
+                    format!("Chain pruned up to block #{up_to_index}"),
+                );
+            }
+            NodeEvent::AssetReceived { hash, data } => {
+                app.push_traffic(TrafficLevel::Info, format!(
+                    "Asset received: {} ({} bytes)",
+                    &hex::encode(hash)[..16],
+                    data.len()
+                ));
+            }
+            NodeEvent::BlockProofReceived { header, proof } => {
+                let (verdict, level) = if proof.verify(&header.merkle_root) {
+                    ("valid", TrafficLevel::Block)
+                } else {
+                    ("INVALID", TrafficLevel::Error)
+                };
+                app.push_traffic(
+                    level,
+                    format!("Block proof received: #{} ({verdict})", header.index),
+                );
+            }
+            NodeEvent::MempoolReceived { peer, ids } => {
+                let pid = peer.to_string();
+                app.push_traffic(TrafficLevel::Info, format!(
+                    "Mempool of {}: {} pending",
+                    &pid[..pid.len().min(20)],
+                    ids.len()
+                ));
+            }
+            NodeEvent::TopicMessage { topic, data } => {
+                app.push_traffic(
+                    TrafficLevel::Info,
+                    format!("Message on {topic} ({} bytes)", data.len()),
+                );
+            }
+            NodeEvent::TopicPeersChanged { topic, peers } => {
+                app.status.topic_peers.insert(topic, peers);
+            }
+            NodeEvent::Error(msg) => {
+                app.push_traffic(TrafficLevel::Error, format!("error: {msg}"));
+            }
+        },
+
+        ControlEvent::Block(lookup) => match lookup {
+            BlockLookup::Found(block) => {
+                app.shown_block = Some(*block);
+                app.view = ContentView::Block;
+            }
+            BlockLookup::NotFound => app.set_output("no block with that hash"),
+            BlockLookup::Ambiguous(n) => {
+                app.set_output(format!("ambiguous prefix: {n} blocks match; type more of the hash"))
             }
         },
 
-        ControlEvent::Error(msg) => {
-            app.push_traffic(format!("error: {msg}"));
-            app.set_output(format!("error: {msg}"));
+        ControlEvent::Status(snapshot) => {
+            let s = &mut app.status;
+            s.peer_id = snapshot.peer_id.to_string();
+            if let Some(addr) = snapshot.listen_addrs.first() {
+                s.listen_addr = addr.to_string();
+            }
+            s.peers_connected = snapshot.connected_peers;
+            s.peers_discovered = snapshot.discovered_peers;
+            s.chain_length = snapshot.chain_length;
+            s.tip_hash = hex::encode(snapshot.tip_hash);
+            s.last_finalised = snapshot.last_finalised;
+            s.pending_txs = snapshot.pending_txs;
+            s.mode = format!("{:?}", snapshot.mode);
+            s.sync_strategy = format!("{:?}", snapshot.sync_strategy);
+            s.discovery_mode = format!("{:?}", snapshot.discovery_mode);
+            s.listen_addrs = snapshot.listen_addrs.iter().map(|a| a.to_string()).collect();
+            s.network_id = snapshot.network_id;
+            s.genesis_hash = hex::encode(snapshot.genesis_hash);
+            s.finalised_by_type = snapshot
+                .finalised_by_type
+                .iter()
+                .map(|(kind, count)| (format!("{kind:?}"), *count))
+                .collect();
+            if std::mem::take(&mut app.whoami_pending) {
+                app.view = ContentView::Traffic;
+                for line in app.status.whoami_lines() {
+                    app.push_traffic(TrafficLevel::Info, line);
+                }
+            }
+        }
+
+        ControlEvent::Error(err) => {
+            let level = match &err {
+                ControlError::StartupFailed(_) | ControlError::NodeCrashed(_) => {
+                    TrafficLevel::Error
+                }
+                _ => TrafficLevel::Warn,
+            };
+            app.push_traffic(level, format!("error: {err}"));
+            app.set_output(format!("error: {err}"));
+
+            if let ControlError::StartupFailed(_) = err {
+                app.node_state = NodeState::Stopped;
+            }
         }
     }
 }
 
-/// Resolve the path for the TUI's log file.
+/// The embedded node's configuration before the saved session is applied.
+/// Unlike the daemon, the TUI dials every peer it discovers.
+fn tui_config() -> NodeConfig {
+    NodeConfig {
+        quiet: true,
+        data_dir: Some(spn_data_dir()),
+        auto_connect: AutoConnectPolicy::All,
+        ..NodeConfig::default()
+    }
+}
+
+/// Resolve the embedded node's data directory.
 ///
-/// Uses `$XDG_DATA_HOME/spn/spn.log` when the env var is set, otherwise
-/// falls back to `~/.local/share/spn/spn.log`.
-fn spn_log_path() -> PathBuf {
-    let base = std::env::var("XDG_DATA_HOME")
-        .ok()
+/// Honours `$SPN_DATA_DIR` like the `sp-node` daemon, otherwise uses
+/// `$XDG_DATA_HOME/spn` (default `~/.local/share/spn`).
+fn spn_data_dir() -> PathBuf {
+    std::env::var_os("SPN_DATA_DIR")
         .map(PathBuf::from)
-        .or_else(|| {
-            std::env::var("HOME")
-                .ok()
-                .map(|h| PathBuf::from(h).join(".local").join("share"))
-        })
-        .unwrap_or_else(|| PathBuf::from("."));
-    base.join("spn").join("spn.log")
+        .unwrap_or_else(sp_node::default_data_dir)
+}
+
+/// Resolve the path for the saved session, `session.json` in the data directory.
+fn spn_session_path() -> PathBuf {
+    spn_data_dir().join(session::SESSION_FILE)
+}
+
+/// Write `app.session` for the next launch, noting in the traffic log if
+/// that fails.
+fn save_session(app: &mut App) {
+    if let Err(e) = session::save(&spn_session_path(), &app.session) {
+        app.push_traffic(TrafficLevel::Warn, format!("Could not save session: {e}"));
+    }
+}
+
+/// Resolve the path for the favourites list, `favourites.json` in the data
+/// directory.
+fn spn_favourites_path() -> PathBuf {
+    spn_data_dir().join(favourites::FAVOURITES_FILE)
+}
+
+/// Resolve the path for the TUI's log file, `spn.log` in the data directory.
+fn spn_log_path() -> PathBuf {
+    spn_data_dir().join("spn.log")
+}
+
+/// Every peer address in the peer store.  Entries that no longer parse are
+/// skipped.
+fn stored_peer_addrs() -> Vec<libp2p::Multiaddr> {
+    peers_store::load().iter().filter_map(|a| a.parse().ok()).collect()
+}
+
+/// Every favourite address.  Entries that no longer parse are skipped.
+fn favourite_addrs() -> Vec<libp2p::Multiaddr> {
+    favourites::load(&spn_favourites_path()).iter().filter_map(|a| a.parse().ok()).collect()
 }
 
 /// Parse a `start-end` port range string (e.g. `"51025-51030"`).
@@ -459,11 +953,23 @@ fn parse_port_range(s: &str) -> Option<(u16, u16)> {
     if start <= end { Some((start, end)) } else { None }
 }
 
-/// Convert a plain `ip:port` string to a `/ip4/<ip>/tcp/<port>` multiaddr string.
-fn parse_ip_port(s: &str) -> Option<String> {
-    let (ip, port) = s.rsplit_once(':')?;
-    port.parse::<u16>().ok()?;
-    Some(format!("/ip4/{ip}/tcp/{port}"))
+/// How a discovery port range reads in the status view.
+fn describe_port_range(range: Option<(u16, u16)>) -> String {
+    match range {
+        Some((start, end)) => format!("{start}-{end}"),
+        None => "any".to_string(),
+    }
+}
+
+/// " from <peer>" for a traffic line about something `origin` relayed, if known.
+fn relayed_by(origin: Option<libp2p::PeerId>) -> String {
+    match origin {
+        Some(peer_id) => {
+            let pid = peer_id.to_string();
+            format!(" from {}", &pid[..pid.len().min(20)])
+        }
+        None => String::new(),
+    }
 }
 
 /// Look through connected peers for one whose address list contains `addr_str`
@@ -472,7 +978,7 @@ fn find_peer_by_addr(
     connected_peers: &[(String, Vec<String>)],
     addr_str: &str,
 ) -> Option<libp2p::PeerId> {
-    let alt = parse_ip_port(addr_str);
+    let alt = addr::parse_dial_addr(addr_str).ok().map(|a| a.to_string());
 
     for (pid_str, addrs) in connected_peers {
         let matched = addrs.iter().any(|a| {
@@ -484,3 +990,144 @@ fn find_peer_by_addr(
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sent(outcomes: &[CommandOutcome]) -> Vec<&ControlCommand> {
+        outcomes
+            .iter()
+            .filter_map(|o| match o {
+                CommandOutcome::Sent(cmd) => Some(cmd),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn connect_dials_addresses_and_peer_ids() {
+        let mut app = App::new();
+        let addr: libp2p::Multiaddr = "/ip4/1.2.3.4/tcp/51025".parse().unwrap();
+        assert_eq!(
+            execute_command(&mut app, "/connect 1.2.3.4:51025"),
+            vec![
+                CommandOutcome::Traffic(TrafficLevel::Info, format!("Connecting to {addr}")),
+                CommandOutcome::Sent(ControlCommand::Connect(addr)),
+            ]
+        );
+
+        let peer = libp2p::PeerId::random();
+        let outcomes = execute_command(&mut app, &format!("/connect {peer}"));
+        assert_eq!(sent(&outcomes), vec![&ControlCommand::ConnectPeer(peer)]);
+
+        let outcomes = execute_command(&mut app, "/connect 1.2.3.4");
+        assert!(sent(&outcomes).is_empty());
+        assert!(matches!(
+            &outcomes[..],
+            [CommandOutcome::Feedback(msg)] if msg.starts_with("Invalid address")
+        ));
+        assert!(matches!(
+            &execute_command(&mut app, "/connect")[..],
+            [CommandOutcome::Feedback(msg)] if msg.starts_with("Usage: /connect")
+        ));
+    }
+
+    #[test]
+    fn disconnect_finds_the_peer_by_id_address_or_prefix() {
+        let mut app = App::new();
+        let peer = libp2p::PeerId::random();
+        let pid = peer.to_string();
+        app.connected_peers.push((pid.clone(), vec!["/ip4/1.2.3.4/tcp/51025".into()]));
+
+        for arg in [pid.as_str(), "1.2.3.4:51025", &pid[..20]] {
+            let outcomes = execute_command(&mut app, &format!("/disconnect {arg}"));
+            assert_eq!(sent(&outcomes), vec![&ControlCommand::Disconnect(peer)], "{arg}");
+        }
+
+        assert_eq!(
+            execute_command(&mut app, "/disconnect 5.6.7.8:51025"),
+            vec![CommandOutcome::Feedback("No connected peer found at 5.6.7.8:51025".into())]
+        );
+    }
+
+    #[test]
+    fn discover_switches_view_and_validates_its_range() {
+        let mut app = App::new();
+        // Already using both mechanisms, so the session isn't rewritten.
+        app.session.discovery_mode = DiscoveryMode::Both;
+
+        assert_eq!(
+            execute_command(&mut app, "/discover 51025-51030"),
+            vec![
+                CommandOutcome::Traffic(
+                    TrafficLevel::Info,
+                    "Discovering peers on ports 51025–51030…".into()
+                ),
+                CommandOutcome::ViewChanged(ContentView::Discovered),
+                CommandOutcome::Sent(ControlCommand::Discover(Some((51025, 51030)))),
+            ]
+        );
+        let outcomes = execute_command(&mut app, "/discover");
+        assert_eq!(sent(&outcomes), vec![&ControlCommand::Discover(None)]);
+        assert_eq!(
+            execute_command(&mut app, "/discover 51030-51025"),
+            vec![CommandOutcome::Feedback("Usage: /discover [<start port>-<end port>]".into())]
+        );
+    }
+
+    #[test]
+    fn peer_agents_are_forgotten_on_disconnect_and_stop() {
+        let mut app = App::new();
+        let (cmd_tx, _cmd_rx) = tokio::sync::mpsc::unbounded_channel();
+        let identify = |peer_id| {
+            let agent_version = "sp-node/0.1.0".to_string();
+            ControlEvent::NodeEvent(Box::new(NodeEvent::PeerIdentified { peer_id, agent_version }))
+        };
+        let [a, b] = [libp2p::PeerId::random(), libp2p::PeerId::random()];
+
+        handle_controller_event(&mut app, &cmd_tx, identify(a));
+        handle_controller_event(&mut app, &cmd_tx, identify(b));
+        let gone = ControlEvent::NodeEvent(Box::new(NodeEvent::PeerDisconnected(a)));
+        handle_controller_event(&mut app, &cmd_tx, gone);
+        assert_eq!(app.peer_agents.keys().collect::<Vec<_>>(), vec![&b.to_string()]);
+
+        handle_controller_event(&mut app, &cmd_tx, ControlEvent::NodeStopped);
+        assert!(app.peer_agents.is_empty());
+    }
+
+    #[test]
+    fn disconnected_peers_are_listed_as_discovered_again() {
+        let mut app = App::new();
+        let (cmd_tx, _cmd_rx) = tokio::sync::mpsc::unbounded_channel();
+        let peer_id = libp2p::PeerId::random();
+        let addr: libp2p::Multiaddr = "/ip4/127.0.0.1/tcp/4001".parse().unwrap();
+        let node_event = |ev| ControlEvent::NodeEvent(Box::new(ev));
+
+        app.discovered_peers.push((peer_id.to_string(), vec![addr.to_string()]));
+        handle_controller_event(&mut app, &cmd_tx, node_event(NodeEvent::PeerConnected(peer_id)));
+        assert!(app.discovered_peers.is_empty());
+
+        let gone = NodeEvent::PeerDisconnected(peer_id);
+        handle_controller_event(&mut app, &cmd_tx, node_event(gone));
+        assert_eq!(app.discovered_peers, vec![(peer_id.to_string(), vec![addr.to_string()])]);
+        assert_eq!(app.status.peers_discovered, 1);
+    }
+
+    #[test]
+    fn the_tui_dials_discovered_peers() {
+        let mut config = tui_config();
+        Session::from_config(&config).apply(&mut config);
+        assert_eq!(config.auto_connect, AutoConnectPolicy::All);
+    }
+
+    #[test]
+    fn unknown_commands_point_at_help() {
+        let mut app = App::new();
+        assert_eq!(
+            execute_command(&mut app, "/frobnicate now"),
+            vec![CommandOutcome::Feedback("Unknown command: /frobnicate  (try /help)".into())]
+        );
+        assert!(execute_command(&mut app, "").is_empty());
+    }
+}
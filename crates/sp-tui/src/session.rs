@@ -0,0 +1,89 @@
+//! Node settings the TUI last ran with.
+//!
+//! Saved to the data directory whenever they change and applied on top of the
+//! defaults at launch, so a restarted TUI brings the node back on the same
+//! port and with the same mode, discovery and sync settings.
+
+use std::{fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+use sp_node::{DiscoveryMode, NodeConfig, NodeMode};
+use sp_sync::SyncStrategy;
+
+/// File name of the session inside the data directory.
+pub const SESSION_FILE: &str = "session.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    /// The port the node last listened on.
+    pub port: u16,
+    pub mode: NodeMode,
+    pub discovery_mode: DiscoveryMode,
+    pub sync_strategy: SyncStrategy,
+}
+
+impl Session {
+    /// The settings `config` would run the node with.
+    pub fn from_config(config: &NodeConfig) -> Self {
+        Self {
+            port: config.port,
+            mode: config.mode.clone(),
+            discovery_mode: config.discovery_mode.clone(),
+            sync_strategy: config.sync_strategy.clone(),
+        }
+    }
+
+    /// Overwrite the matching fields of `config`.
+    pub fn apply(&self, config: &mut NodeConfig) {
+        config.port = self.port;
+        config.mode = self.mode.clone();
+        config.discovery_mode = self.discovery_mode.clone();
+        config.sync_strategy = self.sync_strategy.clone();
+    }
+}
+
+/// Read a saved session.  A missing or unreadable file yields `None`, so a
+/// bad session never stops the TUI from starting.
+pub fn load(path: &Path) -> Option<Session> {
+    let json = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+/// Overwrite the session at `path`, creating its directory if needed.
+pub fn save(path: &Path, session: &Session) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let json = serde_json::to_string_pretty(session).map_err(io::Error::other)?;
+    fs::write(path, json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_round_trips_through_its_file() {
+        let dir = std::env::temp_dir().join(format!("spn-session-test-{}", std::process::id()));
+        let path = dir.join(SESSION_FILE);
+        assert_eq!(load(&path), None);
+
+        let session = Session {
+            port: 51030,
+            mode: NodeMode::Gossip,
+            discovery_mode: DiscoveryMode::Mdns,
+            sync_strategy: SyncStrategy::TimeRange { from: 10, to: 20 },
+        };
+        save(&path, &session).unwrap();
+        let loaded = load(&path).unwrap();
+        assert_eq!(loaded, session);
+
+        let mut config = NodeConfig::default();
+        loaded.apply(&mut config);
+        assert_eq!(Session::from_config(&config), session);
+
+        fs::write(&path, "{ not json").unwrap();
+        assert_eq!(load(&path), None);
+        let _ = fs::remove_dir_all(dir);
+    }
+}
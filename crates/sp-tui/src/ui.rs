@@ -6,7 +6,15 @@ use ratatui::{
     Frame,
 };
 
-use crate::app::{App, ContentView, NodeState};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use sp_blockchain::{FULL_VERIFICATION_WEIGHT, MIN_VERIFICATIONS};
+use sp_node::DiscoverySource;
+
+use crate::app::{App, ContentView, NodeState, TrafficLevel};
 
 // ── Green-based colour palette ────────────────────────────────────────────────
 const PRIMARY: Color = Color::Green;
@@ -70,18 +78,47 @@ fn draw_content_panel(frame: &mut Frame, app: &App, area: ratatui::layout::Rect)
         ContentView::Traffic => draw_traffic(frame, app, inner),
         ContentView::Status => draw_status(frame, app, inner),
         ContentView::Discovered => {
-            draw_peer_list(frame, &app.discovered_peers, "discovered peers", inner);
+            draw_peer_list(
+                frame,
+                &app.discovered_peers,
+                None,
+                Some(&app.discovery_sources),
+                "discovered peers",
+                inner,
+            );
         }
         ContentView::Connected => {
-            draw_peer_list(frame, &app.connected_peers, "connected peers", inner);
+            draw_peer_list(
+                frame,
+                &app.connected_peers,
+                Some(&app.connected_since),
+                None,
+                "connected peers",
+                inner,
+            );
+        }
+        ContentView::Peer { peer_id, stored } => {
+            draw_peer_detail(frame, app, peer_id, *stored, inner);
         }
+        ContentView::Block => draw_block_detail(frame, app, inner),
     }
 }
 
 // ── Traffic view ──────────────────────────────────────────────────────────────
 
 fn draw_traffic(frame: &mut Frame, app: &App, area: ratatui::layout::Rect) {
-    let total = app.traffic.len();
+    let total = app.visible_traffic().count();
+    let mut title = match app.traffic_level_filter {
+        None => format!("traffic  ({total} events)"),
+        Some(level) => format!(
+            "traffic  ({total} of {} events · filter: {})",
+            app.traffic.len(),
+            level.label()
+        ),
+    };
+    if !app.traffic_follow {
+        title.push_str("  [locked]");
+    }
 
     let split = Layout::default()
         .direction(Direction::Vertical)
@@ -89,22 +126,18 @@ fn draw_traffic(frame: &mut Frame, app: &App, area: ratatui::layout::Rect) {
         .split(area);
 
     frame.render_widget(
-        Paragraph::new(Span::styled(
-            format!("traffic  ({total} events)"),
-            Style::default().fg(DIM),
-        )),
+        Paragraph::new(Span::styled(title, Style::default().fg(DIM))),
         split[0],
     );
 
     let items: Vec<ListItem> = app
-        .traffic
-        .iter()
+        .visible_traffic()
         .map(|entry| {
             let ts = Span::styled(
                 format!("{} ", entry.timestamp),
                 Style::default().fg(DIM),
             );
-            let msg = Span::styled(entry.message.clone(), traffic_style(&entry.message));
+            let msg = Span::styled(entry.message.clone(), traffic_style(entry.level));
             ListItem::new(Line::from(vec![ts, msg]))
         })
         .collect();
@@ -140,7 +173,34 @@ fn draw_status(frame: &mut Frame, app: &App, area: ratatui::layout::Rect) {
     let peers_connected_str = s.peers_connected.to_string();
     let peers_discovered_str = s.peers_discovered.to_string();
     let chain_str = s.chain_length.to_string();
+    let tip_str = if s.tip_hash.is_empty() { "-".to_string() } else { truncate(&s.tip_hash, 48) };
+    let finalised_block_str = s.last_finalised.map_or("-".to_string(), |i| format!("#{i}"));
     let pending_str = s.pending_txs.to_string();
+    let finalised_str = format!(
+        "{}  ({:.1}/min)",
+        s.finalised_txs.total(),
+        s.finalised_txs.per_minute(Instant::now())
+    );
+    // A few missed heartbeats means the node task is wedged, not just idle.
+    let (heartbeat_str, heartbeat_color) = match s.last_heartbeat {
+        Some(at) if app.node_state == NodeState::Running
+            && at.elapsed() > s.heartbeat_interval * 3 =>
+        {
+            (format!("{} ago, node stalled?", format_duration(at.elapsed())), DANGER)
+        }
+        Some(at) => (format!("{} ago", format_duration(at.elapsed())), BRIGHT),
+        None => ("-".to_string(), MUTED),
+    };
+    let topic_rows: Vec<(String, String)> = s
+        .topic_peers
+        .iter()
+        .map(|(topic, peers)| (format!("{topic} topic"), format!("{peers} peers")))
+        .collect();
+    let kind_rows: Vec<(String, String)> = s
+        .finalised_by_type
+        .iter()
+        .map(|(kind, count)| (format!("{kind} finalised"), count.to_string()))
+        .collect();
 
     let state_color = match app.node_state {
         NodeState::Running => PRIMARY,
@@ -148,19 +208,34 @@ fn draw_status(frame: &mut Frame, app: &App, area: ratatui::layout::Rect) {
         _ => WARN,
     };
 
-    let rows: Vec<Line> = vec![
+    let mut rows: Vec<Line> = vec![
         kv_row("node state", app.node_state.label(), state_color),
         kv_row("peer id", &peer_id_str, MUTED),
         kv_row("listen addr", &listen_str, MUTED),
         kv_row("port", &port_str, PRIMARY),
         kv_row("mode", &s.mode, PRIMARY),
         kv_row("discovery", &s.discovery_mode, PRIMARY),
+        kv_row("port range", &s.port_range, PRIMARY),
         kv_row("sync", &s.sync_strategy, PRIMARY),
         kv_row("peers connected", &peers_connected_str, BRIGHT),
         kv_row("peers discovered", &peers_discovered_str, BRIGHT),
         kv_row("chain length", &chain_str, BRIGHT),
+        kv_row("tip hash", &tip_str, MUTED),
+        kv_row("last finalised", &finalised_block_str, BRIGHT),
         kv_row("pending txs", &pending_str, BRIGHT),
+        kv_row("finalised txs", &finalised_str, BRIGHT),
+        kv_row("last heartbeat", &heartbeat_str, heartbeat_color),
     ];
+    rows.extend(
+        topic_rows
+            .iter()
+            .map(|(label, peers)| kv_row(label, peers, BRIGHT)),
+    );
+    rows.extend(
+        kind_rows
+            .iter()
+            .map(|(label, count)| kv_row(label, count, BRIGHT)),
+    );
 
     let items: Vec<ListItem> = rows.into_iter().map(ListItem::new).collect();
     frame.render_widget(List::new(items), area);
@@ -171,6 +246,8 @@ fn draw_status(frame: &mut Frame, app: &App, area: ratatui::layout::Rect) {
 fn draw_peer_list(
     frame: &mut Frame,
     peers: &[(String, Vec<String>)],
+    since: Option<&HashMap<String, Instant>>,
+    sources: Option<&HashMap<String, Vec<DiscoverySource>>>,
     title: &str,
     area: ratatui::layout::Rect,
 ) {
@@ -196,13 +273,27 @@ fn draw_peer_list(
         peers
             .iter()
             .flat_map(|(pid, addrs)| {
-                let id_item = ListItem::new(Line::from(vec![
+                let mut id_line = vec![
                     Span::raw("  "),
                     Span::styled(
                         truncate(pid, 56),
                         Style::default().fg(PRIMARY).add_modifier(Modifier::BOLD),
                     ),
-                ]));
+                ];
+                if let Some(at) = since.and_then(|s| s.get(pid)) {
+                    id_line.push(Span::styled(
+                        format!("  {}", format_duration(at.elapsed())),
+                        Style::default().fg(DIM),
+                    ));
+                }
+                if let Some(found) = sources.and_then(|s| s.get(pid)) {
+                    let found: Vec<String> = found.iter().map(|s| s.to_string()).collect();
+                    id_line.push(Span::styled(
+                        format!("  via {}", found.join(", ")),
+                        Style::default().fg(DIM),
+                    ));
+                }
+                let id_item = ListItem::new(Line::from(id_line));
                 let addr_items: Vec<ListItem> = addrs
                     .iter()
                     .map(|a| {
@@ -220,6 +311,97 @@ fn draw_peer_list(
     frame.render_widget(List::new(items), split[1]);
 }
 
+// ── Peer detail view ──────────────────────────────────────────────────────────
+
+fn draw_peer_detail(
+    frame: &mut Frame,
+    app: &App,
+    peer_id: &str,
+    stored: bool,
+    area: ratatui::layout::Rect,
+) {
+    let (status, status_color) = if app.connected_peers.iter().any(|(id, _)| id == peer_id) {
+        ("connected", PRIMARY)
+    } else if app.discovered_peers.iter().any(|(id, _)| id == peer_id) {
+        ("discovered", WARN)
+    } else {
+        ("gone", DANGER)
+    };
+    let addrs = app.peer_addrs(peer_id);
+    let addr_count = addrs.len().to_string();
+    let agent = app.peer_agents.get(peer_id).map(|a| truncate(a, 48));
+
+    let mut rows: Vec<Line> = vec![
+        Line::from(Span::styled("peer", Style::default().fg(DIM))),
+        kv_row("peer id", peer_id, MUTED),
+        kv_row("status", status, status_color),
+        kv_row("latency", "-", MUTED),
+        kv_row("agent", agent.as_deref().unwrap_or("-"), MUTED),
+        kv_row("stored", if stored { "yes" } else { "no" }, PRIMARY),
+        kv_row("addresses", &addr_count, BRIGHT),
+    ];
+    rows.extend(addrs.iter().map(|a| {
+        Line::from(Span::styled(
+            format!("    {}", truncate(a, 54)),
+            Style::default().fg(MUTED),
+        ))
+    }));
+
+    let items: Vec<ListItem> = rows.into_iter().map(ListItem::new).collect();
+    frame.render_widget(List::new(items), area);
+}
+
+// ── Block detail view ─────────────────────────────────────────────────────────
+
+fn draw_block_detail(frame: &mut Frame, app: &App, area: ratatui::layout::Rect) {
+    let Some(block) = &app.shown_block else {
+        frame.render_widget(
+            Paragraph::new(Span::styled("  no block selected", Style::default().fg(DIM))),
+            area,
+        );
+        return;
+    };
+
+    let index = block.index.to_string();
+    let hash = block.hash_hex();
+    let prev_hash = hex::encode(block.prev_hash);
+    let merkle_root = hex::encode(block.merkle_root);
+    let created = chrono::DateTime::from_timestamp(block.timestamp, 0)
+        .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| block.timestamp.to_string());
+    let verifications = format!(
+        "{}/{}{}",
+        block.verified_weight / FULL_VERIFICATION_WEIGHT,
+        MIN_VERIFICATIONS,
+        if block.is_finalised() { "  (finalised)" } else { "" }
+    );
+    let tx_count = block.transactions.len().to_string();
+    let metadata = block.metadata.as_deref().map(String::from_utf8_lossy);
+
+    let mut rows: Vec<Line> = vec![
+        Line::from(Span::styled("block", Style::default().fg(DIM))),
+        kv_row("index", &index, BRIGHT),
+        kv_row("hash", &hash, MUTED),
+        kv_row("prev hash", &prev_hash, MUTED),
+        kv_row("merkle root", &merkle_root, MUTED),
+        kv_row("created", &created, PRIMARY),
+        kv_row("verifications", &verifications, PRIMARY),
+        kv_row("transactions", &tx_count, BRIGHT),
+    ];
+    if let Some(metadata) = &metadata {
+        rows.insert(rows.len() - 1, kv_row("metadata", metadata, MUTED));
+    }
+    rows.extend(block.transactions.iter().map(|tx| {
+        Line::from(Span::styled(
+            format!("    {}  {:?}", tx.id, tx.kind),
+            Style::default().fg(MUTED),
+        ))
+    }));
+
+    let items: Vec<ListItem> = rows.into_iter().map(ListItem::new).collect();
+    frame.render_widget(List::new(items), area);
+}
+
 // ── Input area (row 2) ────────────────────────────────────────────────────────
 //
 //   Line 0: key hints (dim)
@@ -239,7 +421,7 @@ fn draw_input_area(frame: &mut Frame, app: &App, area: ratatui::layout::Rect) {
     // Key hints.
     frame.render_widget(
         Paragraph::new(Span::styled(
-            "↑/↓ history · PgUp/PgDn scroll · /help for commands · Ctrl-C quit",
+            "↑/↓ history · PgUp/PgDn scroll · End follow · Ctrl-F filter · /help · Ctrl-C quit",
             Style::default().fg(DIM),
         )),
         rows[0],
@@ -276,21 +458,25 @@ fn kv_row<'a>(label: &'a str, value: &'a str, value_color: Color) -> Line<'a> {
     ])
 }
 
-fn traffic_style(msg: &str) -> Style {
-    if msg.contains("finalised") || msg.contains("synced") {
-        Style::default().fg(BRIGHT)
-    } else if msg.contains("Transaction") || msg.contains("transaction") {
-        Style::default().fg(PRIMARY)
-    } else if msg.contains("Block") || msg.contains("block") {
-        Style::default().fg(Color::Cyan)
-    } else if msg.contains("connected") && !msg.contains("dis") || msg.contains("started") {
-        Style::default().fg(PRIMARY)
-    } else if msg.contains("disconnected") || msg.contains("stopped") || msg.contains("error") {
-        Style::default().fg(DANGER)
-    } else if msg.contains("discovered") || msg.contains("Listening") {
-        Style::default().fg(MUTED)
-    } else {
-        Style::default().fg(MUTED)
+fn traffic_style(level: TrafficLevel) -> Style {
+    let color = match level {
+        TrafficLevel::Info => MUTED,
+        TrafficLevel::Good => BRIGHT,
+        TrafficLevel::Warn => WARN,
+        TrafficLevel::Error => DANGER,
+        TrafficLevel::Block => Color::Cyan,
+        TrafficLevel::Tx => PRIMARY,
+    };
+    Style::default().fg(color)
+}
+
+/// Compact connection age: "45s", "3m 12s", "2h 05m".
+fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    match secs {
+        0..=59 => format!("{secs}s"),
+        60..=3599 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
     }
 }
 
@@ -301,3 +487,25 @@ fn truncate(s: &str, max: usize) -> String {
         format!("{}…", &s[..max.saturating_sub(1)])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn traffic_is_styled_by_level_not_text() {
+        let mut app = App::new();
+        app.push_traffic(TrafficLevel::Info, "Peer discovered: 12D3KooWblock (error)");
+        app.push_traffic(TrafficLevel::Error, "something quiet");
+
+        let styles: Vec<Style> = app.traffic.iter().map(|e| traffic_style(e.level)).collect();
+        assert_eq!(styles, vec![Style::default().fg(MUTED), Style::default().fg(DANGER)]);
+    }
+
+    #[test]
+    fn connection_age_is_compact() {
+        assert_eq!(format_duration(Duration::from_secs(45)), "45s");
+        assert_eq!(format_duration(Duration::from_secs(192)), "3m 12s");
+        assert_eq!(format_duration(Duration::from_secs(2 * 3600 + 300)), "2h 05m");
+    }
+}